@@ -0,0 +1,12 @@
+alter table machines add column processor_id integer references processors(id);
+
+update machines set processor_id = (
+	select processor_id from machine_processors
+	where machine_processors.machine_id = machines.id
+	order by machine_processors.id
+	limit 1
+);
+
+alter table machines alter column processor_id set not null;
+
+drop table machine_processors;
@@ -0,0 +1,14 @@
+create table machine_processors (
+	id serial primary key not null,
+	machine_id integer not null,
+	processor_id integer not null,
+	sockets integer not null default 1,
+
+	foreign key (machine_id) references machines(id),
+	foreign key (processor_id) references processors(id)
+);
+
+insert into machine_processors (machine_id, processor_id, sockets)
+	select id, processor_id, 1 from machines;
+
+alter table machines drop column processor_id;
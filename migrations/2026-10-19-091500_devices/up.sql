@@ -0,0 +1,11 @@
+create table devices (
+	id serial primary key not null,
+	machine_id integer not null,
+	kind text not null,
+	vendor text,
+	model text,
+	pci_address text,
+	attributes text,
+
+	foreign key (machine_id) references machines(id)
+);
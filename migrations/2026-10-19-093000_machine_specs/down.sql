@@ -0,0 +1,2 @@
+drop table machine_tags;
+alter table microarchitectures drop column generation;
@@ -0,0 +1,11 @@
+-- Microarchitectures within an architecture are ordered by generation, so that users can ask
+-- for (e.g.) "Haswell or newer".
+alter table microarchitectures add column generation integer not null default 0;
+
+create table machine_tags (
+	id serial primary key not null,
+	machine_id integer not null,
+	tag text not null,
+
+	foreign key (machine_id) references machines(id)
+);
@@ -0,0 +1,2 @@
+alter table reservations drop column group_id;
+drop table reservation_groups;
@@ -0,0 +1,9 @@
+create table reservation_groups (
+	id serial primary key not null,
+	user_id integer not null,
+	created_at timestamp with time zone not null default now(),
+
+	foreign key (user_id) references users(id)
+);
+
+alter table reservations add column group_id integer references reservation_groups(id);
@@ -0,0 +1,4 @@
+drop trigger set_updated_at on reservations;
+alter table reservations drop column updated_at;
+
+drop table calendar_tokens;
@@ -0,0 +1,14 @@
+create table calendar_tokens (
+	id serial primary key not null,
+	user_id integer not null,
+	token text not null unique,
+	created_at timestamp with time zone not null default now(),
+
+	foreign key (user_id) references users(id)
+);
+
+-- Calendar clients use modification times to notice changed reservations.
+alter table reservations
+	add column updated_at timestamp with time zone not null default now();
+
+select diesel_manage_updated_at('reservations');
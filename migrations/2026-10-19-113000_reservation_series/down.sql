@@ -0,0 +1,2 @@
+alter table reservations drop column series_id;
+drop table reservation_series;
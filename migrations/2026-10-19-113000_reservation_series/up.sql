@@ -0,0 +1,12 @@
+create table reservation_series (
+	id serial primary key not null,
+	user_id integer not null,
+	machine_id integer not null,
+	rule text not null,
+	created_at timestamp with time zone not null default now(),
+
+	foreign key (user_id) references users(id),
+	foreign key (machine_id) references machines(id)
+);
+
+alter table reservations add column series_id integer references reservation_series(id);
@@ -0,0 +1,4 @@
+alter table reservations
+	drop column cancel_reason,
+	drop column cancelled_by,
+	drop column cancelled_at;
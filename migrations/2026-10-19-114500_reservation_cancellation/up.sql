@@ -0,0 +1,4 @@
+alter table reservations
+	add column cancelled_at timestamp with time zone,
+	add column cancelled_by integer references users(id),
+	add column cancel_reason text;
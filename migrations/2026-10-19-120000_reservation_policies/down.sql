@@ -0,0 +1,5 @@
+alter table roles
+	drop column max_weekly_hours,
+	drop column max_concurrent_reservations,
+	drop column max_advance_days,
+	drop column max_reservation_hours;
@@ -0,0 +1,6 @@
+-- A null limit means "unlimited".
+alter table roles
+	add column max_reservation_hours integer,
+	add column max_advance_days integer,
+	add column max_concurrent_reservations integer,
+	add column max_weekly_hours integer;
@@ -0,0 +1,2 @@
+drop table waitlist_entries;
+drop table notifications;
@@ -0,0 +1,38 @@
+create table notifications (
+	id serial primary key not null,
+	user_id integer not null,
+	message text not null,
+	link text,
+	created_at timestamp with time zone not null default now(),
+	read_at timestamp with time zone,
+
+	foreign key (user_id) references users(id)
+);
+
+-- A request for a machine (or, if machine_id is null, any machine matching the spec columns)
+-- over a time window, to be offered to the user if it becomes free.
+create table waitlist_entries (
+	id serial primary key not null,
+	user_id integer not null,
+	machine_id integer,
+	min_memory_gb integer,
+	min_cores integer,
+	arch_id integer,
+	min_microarch_id integer,
+	tags text not null default '',
+	ssd boolean,
+	window_start timestamp with time zone not null,
+	window_end timestamp with time zone not null,
+	created_at timestamp with time zone not null default now(),
+	offered_machine_id integer,
+	offer_expires timestamp with time zone,
+	reservation_id integer,
+	closed_at timestamp with time zone,
+
+	foreign key (user_id) references users(id),
+	foreign key (machine_id) references machines(id),
+	foreign key (arch_id) references architectures(id),
+	foreign key (min_microarch_id) references microarchitectures(id),
+	foreign key (offered_machine_id) references machines(id),
+	foreign key (reservation_id) references reservations(id)
+);
@@ -0,0 +1,8 @@
+alter table reservations
+	drop column review_comment,
+	drop column reviewed_at,
+	drop column reviewed_by,
+	drop column pending;
+
+alter table roles drop column can_approve_reservations;
+alter table machines drop column requires_approval;
@@ -0,0 +1,9 @@
+alter table machines add column requires_approval boolean not null default false;
+alter table roles add column can_approve_reservations boolean not null default false;
+
+-- Rejected reservations are cancelled (by the reviewer), so only approval needs recording here.
+alter table reservations
+	add column pending boolean not null default false,
+	add column reviewed_by integer references users(id),
+	add column reviewed_at timestamp with time zone,
+	add column review_comment text;
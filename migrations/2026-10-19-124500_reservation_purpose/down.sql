@@ -0,0 +1,6 @@
+alter table waitlist_entries drop column purpose;
+
+alter table reservations
+	drop column project,
+	drop column notes,
+	drop column purpose;
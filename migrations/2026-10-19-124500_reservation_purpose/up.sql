@@ -0,0 +1,7 @@
+-- Reservations made before purposes were recorded have an empty purpose.
+alter table reservations
+	add column purpose text not null default '',
+	add column notes text,
+	add column project text;
+
+alter table waitlist_entries add column purpose text not null default '';
@@ -0,0 +1,2 @@
+drop table reservation_transfers;
+alter table roles drop column can_reserve_for_others;
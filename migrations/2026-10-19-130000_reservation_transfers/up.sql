@@ -0,0 +1,15 @@
+alter table roles add column can_reserve_for_others boolean not null default false;
+
+create table reservation_transfers (
+	id serial primary key not null,
+	reservation_id integer not null,
+	from_user_id integer not null,
+	to_user_id integer not null,
+	transferred_by integer not null,
+	transferred_at timestamp with time zone not null default now(),
+
+	foreign key (reservation_id) references reservations(id),
+	foreign key (from_user_id) references users(id),
+	foreign key (to_user_id) references users(id),
+	foreign key (transferred_by) references users(id)
+);
@@ -0,0 +1,3 @@
+alter table reservations drop column team_id;
+drop table team_memberships;
+drop table teams;
@@ -0,0 +1,19 @@
+create table teams (
+	id serial primary key not null,
+	name text not null unique,
+	description text not null default '',
+	created_at timestamp with time zone not null default now()
+);
+
+create table team_memberships (
+	id serial primary key not null,
+	team_id integer not null,
+	user_id integer not null,
+	is_manager boolean not null default false,
+
+	unique (team_id, user_id),
+	foreign key (team_id) references teams(id),
+	foreign key (user_id) references users(id)
+);
+
+alter table reservations add column team_id integer references teams(id);
@@ -0,0 +1,3 @@
+drop table access_rules;
+alter table machines drop column pool_id;
+drop table pools;
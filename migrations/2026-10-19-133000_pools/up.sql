@@ -0,0 +1,31 @@
+create table pools (
+	id serial primary key not null,
+	name text not null unique,
+	description text not null default ''
+);
+
+alter table machines add column pool_id integer references pools(id);
+
+create table access_rules (
+	id serial primary key not null,
+
+	-- what the rule applies to: either a whole pool or a single machine
+	pool_id integer references pools(id),
+	machine_id integer references machines(id),
+
+	-- who the rule applies to: a user, everyone with a role or every member of a team
+	user_id integer references users(id),
+	role_id integer references roles(id),
+	team_id integer references teams(id),
+
+	can_view boolean not null default true,
+	can_reserve boolean not null default false,
+	can_administer boolean not null default false,
+
+	check ((pool_id is null) <> (machine_id is null)),
+	check (
+		(user_id is not null)::integer
+		+ (role_id is not null)::integer
+		+ (team_id is not null)::integer = 1
+	)
+);
@@ -0,0 +1,25 @@
+alter table roles
+	add column can_alter_users boolean not null default false,
+	add column can_view_users boolean not null default false,
+	add column can_alter_machines boolean default false,
+	add column can_create_machines boolean default false,
+	add column can_delete_machines boolean default false,
+	add column can_approve_reservations boolean not null default false,
+	add column can_reserve_for_others boolean not null default false;
+
+update roles set can_alter_machines = true where id in
+	(select role_id from role_capabilities where capability = 'alter_machines');
+update roles set can_alter_users = true where id in
+	(select role_id from role_capabilities where capability = 'alter_users');
+update roles set can_approve_reservations = true where id in
+	(select role_id from role_capabilities where capability = 'approve_reservations');
+update roles set can_create_machines = true where id in
+	(select role_id from role_capabilities where capability = 'create_machines');
+update roles set can_delete_machines = true where id in
+	(select role_id from role_capabilities where capability = 'delete_machines');
+update roles set can_reserve_for_others = true where id in
+	(select role_id from role_capabilities where capability = 'reserve_for_others');
+update roles set can_view_users = true where id in
+	(select role_id from role_capabilities where capability = 'view_users');
+
+drop table role_capabilities;
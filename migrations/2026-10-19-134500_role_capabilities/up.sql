@@ -0,0 +1,32 @@
+create table role_capabilities (
+	id serial primary key not null,
+	role_id integer not null,
+	capability text not null,
+
+	unique (role_id, capability),
+	foreign key (role_id) references roles(id)
+);
+
+insert into role_capabilities (role_id, capability)
+	select id, 'alter_machines' from roles where can_alter_machines;
+insert into role_capabilities (role_id, capability)
+	select id, 'alter_users' from roles where can_alter_users;
+insert into role_capabilities (role_id, capability)
+	select id, 'approve_reservations' from roles where can_approve_reservations;
+insert into role_capabilities (role_id, capability)
+	select id, 'create_machines' from roles where can_create_machines;
+insert into role_capabilities (role_id, capability)
+	select id, 'delete_machines' from roles where can_delete_machines;
+insert into role_capabilities (role_id, capability)
+	select id, 'reserve_for_others' from roles where can_reserve_for_others;
+insert into role_capabilities (role_id, capability)
+	select id, 'view_users' from roles where can_view_users;
+
+alter table roles
+	drop column can_alter_machines,
+	drop column can_alter_users,
+	drop column can_approve_reservations,
+	drop column can_create_machines,
+	drop column can_delete_machines,
+	drop column can_reserve_for_others,
+	drop column can_view_users;
@@ -0,0 +1,12 @@
+create table github_accounts (
+	id serial primary key not null,
+	user_id integer not null,
+	github_username varchar not null,
+
+	foreign key (user_id) references users(id)
+);
+
+insert into github_accounts (user_id, github_username)
+	select user_id, subject from identities where provider = 'github';
+
+drop table identities;
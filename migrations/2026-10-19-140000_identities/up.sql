@@ -0,0 +1,20 @@
+create table identities (
+	id serial primary key not null,
+	user_id integer not null,
+
+	-- the identity provider (e.g., 'github' or a configured OpenID Connect provider)
+	provider text not null,
+
+	-- the provider's identifier for the user (a GitHub username or an OIDC 'sub' claim)
+	subject text not null,
+
+	created_at timestamp with time zone not null default now(),
+
+	unique (provider, subject),
+	foreign key (user_id) references users(id)
+);
+
+insert into identities (user_id, provider, subject)
+	select user_id, 'github', github_username from github_accounts;
+
+drop table github_accounts;
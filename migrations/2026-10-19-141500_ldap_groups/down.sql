@@ -0,0 +1 @@
+drop table ldap_group_roles;
@@ -0,0 +1,10 @@
+create table ldap_group_roles (
+	id serial primary key not null,
+	role_id integer not null,
+
+	-- the name (e.g., cn) of an LDAP group whose members should inhabit the role
+	group_name text not null,
+
+	unique (group_name, role_id),
+	foreign key (role_id) references roles(id)
+);
@@ -0,0 +1,3 @@
+drop table invitation_roles;
+drop table invitations;
+drop table account_requests;
@@ -0,0 +1,50 @@
+create table account_requests (
+	id serial primary key not null,
+
+	-- the identity that the requester authenticated with (see identities)
+	provider text not null,
+	subject text not null,
+
+	username text not null,
+	name text not null,
+	email text not null default '',
+	reason text not null default '',
+	created_at timestamp with time zone not null default now(),
+
+	reviewed_by integer,
+	reviewed_at timestamp with time zone,
+	approved boolean not null default false,
+	review_comment text,
+
+	foreign key (reviewed_by) references users(id)
+);
+
+create table invitations (
+	id serial primary key not null,
+	token text not null unique,
+
+	-- the account to create when the invitation is accepted
+	username text not null,
+	name text not null,
+	email text not null default '',
+
+	created_by integer not null,
+	created_at timestamp with time zone not null default now(),
+	expires_at timestamp with time zone not null,
+
+	accepted_at timestamp with time zone,
+	user_id integer,
+
+	foreign key (created_by) references users(id),
+	foreign key (user_id) references users(id)
+);
+
+create table invitation_roles (
+	id serial primary key not null,
+	invitation_id integer not null,
+	role_id integer not null,
+
+	unique (invitation_id, role_id),
+	foreign key (invitation_id) references invitations(id),
+	foreign key (role_id) references roles(id)
+);
@@ -0,0 +1,15 @@
+create table sessions (
+	id serial primary key not null,
+	user_id integer not null,
+
+	-- the secret that the session cookie holds
+	token text not null unique,
+
+	created_at timestamp with time zone not null default now(),
+	last_seen timestamp with time zone not null default now(),
+	ip_address text,
+	user_agent text,
+
+	-- deleting a user logs them out everywhere
+	foreign key (user_id) references users(id) on delete cascade
+);
@@ -0,0 +1,4 @@
+-- sessions from before CSRF tokens have no token to check forms against: log them out
+delete from sessions;
+
+alter table sessions add column csrf_token text not null;
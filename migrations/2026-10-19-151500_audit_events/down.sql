@@ -0,0 +1,4 @@
+delete from role_capabilities where capability = 'view_audit';
+drop trigger audit_events_append_only on audit_events;
+drop function audit_events_append_only();
+drop table audit_events;
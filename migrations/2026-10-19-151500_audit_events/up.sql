@@ -0,0 +1,37 @@
+create table audit_events (
+	id serial primary key not null,
+
+	-- who did it: not a foreign key, since the log must outlive deleted users
+	actor_id integer,
+	actor text not null,
+
+	-- what they did (e.g., 'reservation.end') and what they did it to
+	action text not null,
+	target_type text not null,
+	target_id integer not null,
+
+	-- JSON snapshots of the target before and after the action
+	before text,
+	after text,
+
+	ip_address text,
+	created_at timestamp with time zone not null default now()
+);
+
+create index audit_events_created_at on audit_events(created_at);
+create index audit_events_target on audit_events(target_type, target_id);
+
+-- the audit log is append-only
+create function audit_events_append_only() returns trigger as $$
+begin
+	raise exception 'audit events cannot be modified or deleted';
+end;
+$$ language plpgsql;
+
+create trigger audit_events_append_only
+	before update or delete on audit_events
+	for each row execute procedure audit_events_append_only();
+
+-- whoever could already administer users may read the audit log
+insert into role_capabilities (role_id, capability)
+	select role_id, 'view_audit' from role_capabilities where capability = 'alter_users';
@@ -0,0 +1 @@
+alter table sessions drop column acting_as;
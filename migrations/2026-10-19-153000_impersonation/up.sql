@@ -0,0 +1,2 @@
+-- the user that an administrator is viewing Clowder as, if any
+alter table sessions add column acting_as integer references users(id) on delete set null;
@@ -0,0 +1,19 @@
+create table ssh_keys (
+	id serial primary key not null,
+	user_id integer not null,
+
+	-- e.g., ssh-ed25519
+	key_type text not null,
+
+	-- the base64-encoded key, as in authorized_keys
+	key_data text not null,
+	comment text,
+
+	-- SHA256:..., as shown by ssh-keygen -l
+	fingerprint text not null,
+
+	created_at timestamp with time zone not null default now(),
+
+	unique (user_id, fingerprint),
+	foreign key (user_id) references users(id) on delete cascade
+);
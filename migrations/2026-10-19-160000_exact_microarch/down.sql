@@ -0,0 +1,2 @@
+alter table waitlist_entries rename column microarch_id to min_microarch_id;
+alter table microarchitectures add column generation integer not null default 0;
@@ -0,0 +1,4 @@
+-- Microarchitecture generations were never filled in (and can't be compared across vendors), so
+-- machine specifications now ask for a particular microarchitecture rather than "X or newer".
+alter table microarchitectures drop column generation;
+alter table waitlist_entries rename column min_microarch_id to microarch_id;
@@ -0,0 +1 @@
+alter table machine_processors drop constraint machine_processors_sockets_positive;
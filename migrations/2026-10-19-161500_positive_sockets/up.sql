@@ -0,0 +1,4 @@
+-- Every processor in a machine occupies at least one socket.
+update machine_processors set sockets = 1 where sockets < 1;
+alter table machine_processors add constraint machine_processors_sockets_positive
+	check (sockets > 0);
@@ -0,0 +1,2 @@
+alter table waitlist_entries rename column min_microarch_id to microarch_id;
+alter table microarchitectures drop column generation;
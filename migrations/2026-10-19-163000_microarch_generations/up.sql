@@ -0,0 +1,30 @@
+-- Bring back "X or newer" specifications, numbering well-known microarchitectures' generations
+-- by the year that they were released (so that, e.g., Zen 2 counts as newer than Haswell).
+alter table microarchitectures add column generation integer not null default 0;
+alter table waitlist_entries rename column microarch_id to min_microarch_id;
+
+update microarchitectures set generation = known.year
+	from (values
+		('Core', 2006),
+		('Penryn', 2007),
+		('Nehalem', 2008),
+		('Westmere', 2010),
+		('Sandy Bridge', 2011),
+		('Ivy Bridge', 2012),
+		('Haswell', 2013),
+		('Broadwell', 2014),
+		('Skylake', 2015),
+		('Kaby Lake', 2016),
+		('Coffee Lake', 2017),
+		('Cascade Lake', 2019),
+		('Ice Lake', 2019),
+		('Sapphire Rapids', 2023),
+		('Bulldozer', 2011),
+		('Piledriver', 2012),
+		('Zen', 2017),
+		('Zen+', 2018),
+		('Zen 2', 2019),
+		('Zen 3', 2020),
+		('Zen 4', 2022)
+	) as known (name, year)
+	where lower(microarchitectures.name) = lower(known.name);
@@ -0,0 +1,7 @@
+-- A secret for each reservation's authorized_keys URL, so that the URL can't be guessed from the
+-- (sequential) reservation ID. New reservations get tokens from Clowder; existing ones get random
+-- UUIDs (without their dashes).
+alter table reservations add column keys_token text;
+update reservations set keys_token = replace(gen_random_uuid()::text, '-', '');
+alter table reservations alter column keys_token set not null;
+alter table reservations add constraint reservations_keys_token_unique unique (keys_token);
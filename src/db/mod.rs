@@ -12,7 +12,9 @@ use diesel::prelude::*;
 use error::Error;
 
 pub mod models;
+pub mod recurrence;
 pub mod schema;
+pub mod ssh;
 
 pub fn establish_connection() -> Result<PgConnection, Error> {
     PgConnection::establish(&super::getenv("DATABASE_URL")?)
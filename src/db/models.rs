@@ -7,17 +7,52 @@
  * copied, modified, or distributed except according to those terms.
  */
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use db::recurrence::Recurrence;
 use db::schema::*;
+use db::ssh::PublicKey;
 use diesel;
+use diesel::dsl::AsExprOf;
+use diesel::expression::operators::Escape;
+use diesel::expression::IntoSql;
+use diesel::pg::expression::helper_types::ILike;
 use diesel::pg::PgConnection as Connection;
+use diesel::sql_types::VarChar;
 use diesel::*;
+use error::Error;
 use itertools::Itertools;
-use std::collections::HashSet;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::str::FromStr;
 
 type DieselResult<T> = Result<T, diesel::result::Error>;
 
-allow_tables_to_appear_in_same_query! { github_accounts, users }
+/// Generate a random, URL-safe token suitable for use as a bearer capability.
+pub fn random_token(len: usize) -> String {
+    thread_rng().sample_iter(&Alphanumeric).take(len).collect()
+}
+
+/// Build a JSON object (e.g., an audit log snapshot) from field names and values.
+pub fn json_object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(
+        fields
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect::<BTreeMap<_, _>>(),
+    )
+}
+
+allow_tables_to_appear_in_same_query! { calendar_tokens, users }
+allow_tables_to_appear_in_same_query! { identities, users }
+allow_tables_to_appear_in_same_query! { invitation_roles, roles }
+allow_tables_to_appear_in_same_query! { ldap_group_roles, roles }
+allow_tables_to_appear_in_same_query! { machine_processors, architectures }
+allow_tables_to_appear_in_same_query! { machine_processors, microarchitectures }
+allow_tables_to_appear_in_same_query! { machine_processors, processors }
 allow_tables_to_appear_in_same_query! { machines, microarchitectures }
 allow_tables_to_appear_in_same_query! { machines, architectures }
 allow_tables_to_appear_in_same_query! { machines, processors }
@@ -25,17 +60,34 @@ allow_tables_to_appear_in_same_query! { machines, users }
 allow_tables_to_appear_in_same_query! { microarchitectures, architectures }
 allow_tables_to_appear_in_same_query! { processors, architectures }
 allow_tables_to_appear_in_same_query! { processors, microarchitectures }
+allow_tables_to_appear_in_same_query! { reservation_groups, users }
+allow_tables_to_appear_in_same_query! { reservation_series, machines }
+allow_tables_to_appear_in_same_query! { reservation_series, users }
 allow_tables_to_appear_in_same_query! { reservations, machines }
 allow_tables_to_appear_in_same_query! { reservations, users }
 allow_tables_to_appear_in_same_query! { role_assignments, roles }
+allow_tables_to_appear_in_same_query! { role_assignments, users }
+allow_tables_to_appear_in_same_query! { sessions, users }
+allow_tables_to_appear_in_same_query! { team_memberships, teams }
+allow_tables_to_appear_in_same_query! { team_memberships, users }
 
-#[derive(Debug, Identifiable, Queryable)]
+#[derive(Clone, Debug, Identifiable, Queryable)]
 pub struct User {
     pub id: i32,
     pub username: String,
     pub name: String,
 }
 
+impl ToJson for User {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("username", self.username.to_json()),
+            ("name", self.name.to_json()),
+        ])
+    }
+}
+
 impl User {
     pub fn all(c: &Connection) -> DieselResult<Vec<User>> {
         use self::users::dsl::*;
@@ -154,6 +206,23 @@ impl User {
             .map(|count| count > 0)
     }
 
+    ///
+    /// A JSON snapshot of this user's details, including their email addresses and roles (for
+    /// the audit log).
+    ///
+    pub fn audit_snapshot(&self, c: &Connection) -> DieselResult<Json> {
+        let emails = self.emails(c)?.into_iter().collect::<BTreeSet<_>>();
+        let roles = self.roles(c)?.into_iter().map(|r| r.name).collect::<BTreeSet<_>>();
+
+        Ok(json_object(vec![
+            ("id", self.id.to_json()),
+            ("username", self.username.to_json()),
+            ("name", self.name.to_json()),
+            ("emails", emails.into_iter().collect::<Vec<_>>().to_json()),
+            ("roles", roles.into_iter().collect::<Vec<_>>().to_json()),
+        ]))
+    }
+
     pub fn roles(&self, c: &Connection) -> DieselResult<Vec<Role>> {
         use self::role_assignments::dsl::*;
         role_assignments
@@ -163,345 +232,2645 @@ impl User {
             .map(|roles: Vec<(RoleAssignment, Role)>| roles.into_iter().map(|(_, r)| r).collect())
     }
 
-    pub fn can_alter_machines(&self, c: &Connection) -> DieselResult<bool> {
-        self.has_role(c, |ref role| role.can_alter_machines)
-    }
-
-    pub fn can_alter_users(&self, c: &Connection) -> DieselResult<bool> {
-        self.has_role(c, |ref role| role.can_alter_users)
-    }
-
-    pub fn can_create_machines(&self, c: &Connection) -> DieselResult<bool> {
-        self.has_role(c, |ref role| role.can_create_machines)
+    /// Does any of this user's roles grant a capability?
+    pub fn can(&self, capability: Capability, c: &Connection) -> DieselResult<bool> {
+        self.capabilities(c).map(|caps| caps.contains(&capability))
     }
 
-    pub fn can_delete_machines(&self, c: &Connection) -> DieselResult<bool> {
-        self.has_role(c, |ref role| role.can_delete_machines)
-    }
+    /// Find all of the capabilities granted by any of this user's roles.
+    pub fn capabilities(&self, c: &Connection) -> DieselResult<BTreeSet<Capability>> {
+        let role_ids = self.roles(c)?.iter().map(|r| r.id).collect::<Vec<_>>();
 
-    pub fn can_view_users(&self, c: &Connection) -> DieselResult<bool> {
-        self.has_role(c, |ref role| role.can_view_users)
+        role_capabilities::table
+            .filter(role_capabilities::role_id.eq_any(role_ids))
+            .select(role_capabilities::capability)
+            .load::<String>(c)
+            .map(|names| names.iter().filter_map(|n| n.parse().ok()).collect())
     }
 
-    /// Does any of this user's roles satisfy a predicate?
-    fn has_role<Pred>(&self, c: &Connection, predicate: Pred) -> DieselResult<bool>
-    where
-        Pred: Fn(&Role) -> bool,
-    {
-        self.roles(c).map(|roles| roles.iter().any(predicate))
+    /// Find the teams that this user belongs to, ordered by name.
+    pub fn teams(&self, c: &Connection) -> DieselResult<Vec<Team>> {
+        team_memberships::table
+            .inner_join(teams::table)
+            .filter(team_memberships::user_id.eq(self.id))
+            .order(teams::name)
+            .load(c)
+            .map(|ts: Vec<(TeamMembership, Team)>| ts.into_iter().map(|(_, t)| t).collect())
     }
-}
-
-#[derive(Associations, Debug, Identifiable, Queryable)]
-pub struct GithubAccount {
-    pub id: i32,
-    pub user_id: i32,
-    pub github_username: String,
-}
 
-impl GithubAccount {
-    pub fn get(gh_username: &str, conn: &Connection) -> DieselResult<(GithubAccount, User)> {
-        use self::github_accounts::dsl::*;
-        github_accounts
-            .inner_join(users::table)
-            .filter(github_username.eq(gh_username))
-            .first(conn)
+    ///
+    /// The most permissive reservation policy of any of this user's roles, or the configured
+    /// default policy (see `ReservationPolicy::without_roles`) if they don't inhabit any.
+    ///
+    pub fn reservation_policy(&self, c: &Connection) -> Result<ReservationPolicy, Error> {
+        match ReservationPolicy::most_permissive(self.roles(c)?.iter().map(Role::policy)) {
+            Some(policy) => Ok(policy),
+            None => ReservationPolicy::without_roles(),
+        }
     }
 }
 
+///
+/// An external identity (e.g., a GitHub account or an OpenID Connect subject) that a user can
+/// log in with.
+///
 #[derive(Associations, Debug, Identifiable, Queryable)]
 #[belongs_to(User)]
-pub struct Email {
+#[table_name = "identities"]
+pub struct Identity {
     pub id: i32,
     pub user_id: i32,
-    pub email: String,
+    pub provider: String,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Insertable)]
-#[table_name = "emails"]
-struct EmailInserter {
+#[table_name = "identities"]
+struct IdentityInserter {
     user_id: i32,
-    email: String,
+    provider: String,
+    subject: String,
 }
 
-impl Email {
-    pub fn insert<S>(user: &User, email: S, conn: &Connection) -> DieselResult<Email>
-    where
-        S: Into<String>,
-    {
-        diesel::insert_into(emails::table)
-            .values(&EmailInserter {
-                user_id: user.id,
-                email: email.into(),
-            })
-            .get_result(conn)
+impl ToJson for Identity {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("user_id", self.user_id.to_json()),
+            ("provider", self.provider.to_json()),
+            ("subject", self.subject.to_json()),
+        ])
     }
 }
 
-#[derive(Associations, Debug, Identifiable, Queryable)]
-pub struct Role {
-    pub id: i32,
-    pub name: String,
-    pub can_alter_machines: bool,
-    pub can_alter_users: bool,
-    pub can_create_machines: bool,
-    pub can_delete_machines: bool,
-    pub can_view_users: bool,
-}
+impl Identity {
+    pub fn get(identity_id: i32, c: &Connection) -> DieselResult<Identity> {
+        use self::identities::dsl::*;
+        identities.find(identity_id).first(c)
+    }
 
-impl Role {
-    pub fn all(c: &Connection) -> DieselResult<Vec<Role>> {
-        use self::roles::dsl::*;
-        roles.order(name).load(c)
+    /// Find the user that an external identity belongs to.
+    pub fn user(id_provider: &str, id_subject: &str, c: &Connection) -> DieselResult<User> {
+        use self::identities::dsl::*;
+        identities
+            .inner_join(users::table)
+            .filter(provider.eq(id_provider))
+            .filter(subject.eq(id_subject))
+            .first(c)
+            .map(|(_, u): (Identity, User)| u)
     }
 
-    pub fn with_name(role_name: &str, c: &Connection) -> DieselResult<Role> {
-        use self::roles::dsl::*;
-        roles.filter(name.eq(role_name)).first(c)
+    pub fn for_user(user: &User, c: &Connection) -> DieselResult<Vec<Identity>> {
+        Identity::belonging_to(user)
+            .order((identities::provider, identities::subject))
+            .load(c)
+    }
+
+    pub fn insert(
+        user: &User,
+        id_provider: String,
+        id_subject: String,
+        c: &Connection,
+    ) -> DieselResult<Identity> {
+        insert_into(identities::table)
+            .values(&IdentityInserter {
+                user_id: user.id,
+                provider: id_provider,
+                subject: id_subject,
+            })
+            .get_result(c)
+    }
+
+    pub fn delete(self, c: &Connection) -> DieselResult<()> {
+        diesel::delete(&self).execute(c).map(|_| ())
     }
 }
 
+///
+/// An SSH public key that a user can log in to their reserved machines with.
+///
 #[derive(Associations, Debug, Identifiable, Queryable)]
-#[belongs_to(Role)]
 #[belongs_to(User)]
-pub struct RoleAssignment {
+pub struct SshKey {
     pub id: i32,
     pub user_id: i32,
-    pub role_id: i32,
+    pub key_type: String,
+    pub key_data: String,
+    pub comment: Option<String>,
+
+    /// The key's SHA-256 fingerprint (as shown by `ssh-keygen -l`)
+    pub fingerprint: String,
+
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Insertable)]
-#[table_name = "role_assignments"]
-struct RoleAssigner {
+#[table_name = "ssh_keys"]
+struct SshKeyInserter {
     user_id: i32,
-    role_id: i32,
+    key_type: String,
+    key_data: String,
+    comment: Option<String>,
+    fingerprint: String,
 }
 
-impl RoleAssignment {
-    pub fn insert(user: &User, role: &Role, conn: &Connection) -> DieselResult<RoleAssignment> {
-        diesel::insert_into(role_assignments::table)
-            .values(&RoleAssigner {
-                user_id: user.id,
-                role_id: role.id,
-            })
-            .get_result(conn)
+impl ToJson for SshKey {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("user_id", self.user_id.to_json()),
+            ("key_type", self.key_type.to_json()),
+            ("comment", self.comment.to_json()),
+            ("fingerprint", self.fingerprint.to_json()),
+        ])
     }
 }
 
-#[derive(Debug, Identifiable, Queryable)]
-pub struct Architecture {
-    pub id: i32,
-    pub name: String,
-}
-
-#[derive(Associations, Debug, Identifiable, Queryable)]
-#[belongs_to(Architecture, foreign_key = "arch_id")]
-pub struct Microarchitecture {
-    pub id: i32,
-    pub arch_id: i32,
-    pub name: String,
-    pub url: Option<String>,
-}
+impl SshKey {
+    pub fn get(key_id: i32, c: &Connection) -> DieselResult<SshKey> {
+        use self::ssh_keys::dsl::*;
+        ssh_keys.find(key_id).first(c)
+    }
 
-impl Microarchitecture {
-    pub fn arch(&self, c: &Connection) -> DieselResult<Architecture> {
-        use self::architectures::dsl::*;
-        architectures.find(self.arch_id).first(c)
+    pub fn for_user(user: &User, c: &Connection) -> DieselResult<Vec<SshKey>> {
+        SshKey::belonging_to(user).order(ssh_keys::created_at).load(c)
     }
-}
 
-#[derive(Associations, Debug, Identifiable, Queryable)]
-#[belongs_to(Microarchitecture, foreign_key = "microarch_id")]
-pub struct Processor {
-    pub id: i32,
-    pub microarch_id: i32,
-    pub name: String,
-    pub cores: i32,
-    pub threads: i32,
-    pub freq_ghz: f64,
-    pub url: Option<String>,
-}
+    ///
+    /// All of the keys that may log in to a reserved machine: those of the reservation's owner
+    /// and, if it is shared with a team, of the team's members.
+    ///
+    pub fn for_reservation(r: &Reservation, c: &Connection) -> DieselResult<Vec<SshKey>> {
+        let mut owners = vec![r.user_id];
+        if let Some(team) = r.team_id {
+            owners.extend(Team::get(team, c)?.members(c)?.into_iter().map(|(_, u)| u.id));
+        }
 
-impl Processor {
-    pub fn all(c: &Connection) -> DieselResult<Vec<Processor>> {
-        use self::processors::dsl::*;
-        processors.order(name).load(c)
+        ssh_keys::table
+            .filter(ssh_keys::user_id.eq_any(owners))
+            .order((ssh_keys::user_id, ssh_keys::created_at))
+            .load(c)
     }
 
-    pub fn get(processor_id: i32, c: &Connection) -> DieselResult<Processor> {
-        use self::processors::dsl::*;
-        processors.find(processor_id).first(c)
-    }
-}
+    /// Store a (validated) public key for a user, unless they already have it.
+    pub fn insert(user: &User, key: &PublicKey, c: &Connection) -> Result<Option<SshKey>, Error> {
+        let fingerprint = key.fingerprint()?;
 
-#[derive(Associations, Debug, Identifiable, Queryable)]
-#[belongs_to(Processor)]
-pub struct Machine {
-    pub id: i32,
-    pub name: String,
-    pub processor_id: i32,
-    pub memory_gb: i32,
-}
+        let existing = ssh_keys::table
+            .filter(ssh_keys::user_id.eq(user.id))
+            .filter(ssh_keys::fingerprint.eq(&fingerprint))
+            .first::<SshKey>(c)
+            .optional()?;
 
-impl Machine {
-    pub fn all(c: &Connection) -> DieselResult<Vec<Machine>> {
-        use self::machines::dsl::*;
-        machines.order(name).load(c)
-    }
+        if existing.is_some() {
+            return Ok(None);
+        }
 
-    pub fn disks(&self, c: &Connection) -> DieselResult<Vec<Disk>> {
-        Disk::belonging_to(self).load(c)
+        Ok(Some(
+            insert_into(ssh_keys::table)
+                .values(&SshKeyInserter {
+                    user_id: user.id,
+                    key_type: key.key_type.clone(),
+                    key_data: key.data.clone(),
+                    comment: key.comment.clone(),
+                    fingerprint: fingerprint,
+                })
+                .get_result(c)?,
+        ))
     }
 
-    pub fn nics(&self, c: &Connection) -> DieselResult<Vec<Nic>> {
-        Nic::belonging_to(self).load(c)
+    /// This key as one line of an `authorized_keys` file.
+    pub fn authorized_keys_line(&self) -> String {
+        PublicKey {
+            key_type: self.key_type.clone(),
+            data: self.key_data.clone(),
+            comment: self.comment.clone(),
+        }
+        .to_string()
     }
 
-    pub fn with_name(machine_name: &str, c: &Connection) -> DieselResult<Machine> {
-        use self::machines::dsl::*;
-        machines.filter(name.eq(machine_name)).first(c)
+    pub fn delete(self, c: &Connection) -> DieselResult<()> {
+        diesel::delete(&self).execute(c).map(|_| ())
     }
 }
 
 #[derive(Debug, Insertable)]
-#[table_name = "machines"]
-pub struct MachineBuilder {
+#[table_name = "users"]
+struct UserInserter {
+    username: String,
     name: String,
-    processor_id: Option<i32>,
-    memory_gb: Option<i32>,
 }
 
-impl MachineBuilder {
-    pub fn new(name: String) -> MachineBuilder {
-        MachineBuilder {
-            name: name,
-            processor_id: None,
-            memory_gb: None,
-        }
-    }
-
-    pub fn insert(self, conn: &Connection) -> DieselResult<Machine> {
-        insert_into(machines::table).values(&self).get_result(conn)
+///
+/// Create a user who logs in with an external identity, along with their email address (if any)
+/// and roles.
+///
+fn create_account(
+    username: String,
+    name: String,
+    email: &str,
+    roles: &HashSet<String>,
+    provider: &str,
+    subject: &str,
+    c: &Connection,
+) -> DieselResult<User> {
+    let user: User = insert_into(users::table)
+        .values(&UserInserter { username, name })
+        .get_result(c)?;
+
+    if !email.is_empty() {
+        Email::insert(&user, email, c)?;
     }
 
-    pub fn memory_gb(mut self, mem: i32) -> MachineBuilder {
-        self.memory_gb = Some(mem);
-        self
-    }
+    user.set_roles(roles, c)?;
+    Identity::insert(&user, provider.to_string(), subject.to_string(), c)?;
 
-    pub fn processor(mut self, p: &Processor) -> MachineBuilder {
-        self.processor_id = Some(p.id);
-        self
-    }
+    Ok(user)
 }
 
 ///
-/// A FullMachine is a complete representation of a machine and all of its architectural details.
+/// A request for an account from someone who has authenticated with an identity provider but
+/// isn't (yet) a Clowder user.
 ///
-pub struct FullMachine {
-    machine: Machine,
-    processor: Processor,
-    microarch: Microarchitecture,
-    arch: Architecture,
+#[derive(Debug, Identifiable, Queryable)]
+pub struct AccountRequest {
+    pub id: i32,
+    pub provider: String,
+    pub subject: String,
+    pub username: String,
+    pub name: String,
+    pub email: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub approved: bool,
+    pub review_comment: Option<String>,
 }
 
-type FullMachineJoin = (Machine, (Processor, (Microarchitecture, Architecture)));
-
-impl FullMachine {
-    fn from(data: FullMachineJoin) -> FullMachine {
-        let (machine, (processor, (microarch, arch))) = data;
+#[derive(Debug, Insertable)]
+#[table_name = "account_requests"]
+struct AccountRequestInserter {
+    provider: String,
+    subject: String,
+    username: String,
+    name: String,
+    email: String,
+    reason: String,
+}
 
-        FullMachine {
-            machine: machine,
-            processor: processor,
-            microarch: microarch,
-            arch: arch,
-        }
+impl AccountRequest {
+    pub fn get(request_id: i32, c: &Connection) -> DieselResult<AccountRequest> {
+        use self::account_requests::dsl::*;
+        account_requests.find(request_id).first(c)
     }
 
-    pub fn all(c: &Connection) -> DieselResult<Vec<FullMachine>> {
-        use self::machines::dsl::*;
-        let m = machines
-            .order(name)
-            .inner_join(
-                processors::table
-                    .inner_join(microarchitectures::table.inner_join(architectures::table)),
-            )
-            .load(c)?;
-
-        Ok(m.into_iter().map(FullMachine::from).collect())
+    /// Requests that nobody has approved or rejected yet, oldest first.
+    pub fn pending(c: &Connection) -> DieselResult<Vec<AccountRequest>> {
+        use self::account_requests::dsl::*;
+        account_requests
+            .filter(reviewed_at.is_null())
+            .order(created_at)
+            .load(c)
     }
 
-    pub fn with_name(machine_name: &str, c: &Connection) -> DieselResult<FullMachine> {
-        use self::machines::dsl::*;
-        machines
-            .filter(name.eq(machine_name))
-            .inner_join(
-                processors::table
-                    .inner_join(microarchitectures::table.inner_join(architectures::table)),
-            )
+    /// The most recent request made with an identity, if any.
+    pub fn latest(
+        id_provider: &str,
+        id_subject: &str,
+        c: &Connection,
+    ) -> DieselResult<Option<AccountRequest>> {
+        use self::account_requests::dsl::*;
+        account_requests
+            .filter(provider.eq(id_provider))
+            .filter(subject.eq(id_subject))
+            .order(created_at.desc())
             .first(c)
-            .map(FullMachine::from)
+            .optional()
     }
 
-    pub fn architecture(&self) -> &Architecture {
-        &self.arch
-    }
-
-    pub fn cores(&self) -> i32 {
-        self.processor.cores
+    pub fn insert(
+        id_provider: String,
+        id_subject: String,
+        requested_username: String,
+        requested_name: String,
+        requested_email: String,
+        requested_because: String,
+        c: &Connection,
+    ) -> DieselResult<AccountRequest> {
+        insert_into(account_requests::table)
+            .values(&AccountRequestInserter {
+                provider: id_provider,
+                subject: id_subject,
+                username: requested_username,
+                name: requested_name,
+                email: requested_email,
+                reason: requested_because,
+            })
+            .get_result(c)
     }
 
-    pub fn freq_ghz(&self) -> f64 {
-        self.processor.freq_ghz
+    pub fn is_pending(&self) -> bool {
+        self.reviewed_at.is_none()
     }
 
-    pub fn machine(&self) -> &Machine {
-        &self.machine
+    /// Approve this request, creating the requested user with some roles.
+    pub fn approve(
+        self,
+        reviewer: &User,
+        roles: &HashSet<String>,
+        comment: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<User> {
+        c.transaction(|| {
+            let user = create_account(
+                self.username.clone(),
+                self.name.clone(),
+                &self.email,
+                roles,
+                &self.provider,
+                &self.subject,
+                c,
+            )?;
+
+            self.review(reviewer, true, comment, c)?;
+            Ok(user)
+        })
     }
 
-    pub fn memory_gb(&self) -> i32 {
-        self.machine.memory_gb
+    pub fn reject(
+        self,
+        reviewer: &User,
+        comment: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<AccountRequest> {
+        self.review(reviewer, false, comment, c)
     }
 
-    pub fn microarchitecture(&self) -> &Microarchitecture {
-        &self.microarch
+    fn review(
+        &self,
+        reviewer: &User,
+        approve: bool,
+        comment: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<AccountRequest> {
+        use self::account_requests::dsl::*;
+        diesel::update(self)
+            .set((
+                reviewed_by.eq(Some(reviewer.id)),
+                reviewed_at.eq(Some(Utc::now())),
+                approved.eq(approve),
+                review_comment.eq(comment),
+            ))
+            .get_result(c)
     }
+}
 
-    pub fn name(&self) -> &str {
-        &self.machine.name
-    }
+///
+/// An invitation to create an account: whoever first logs in after following the invitation's
+/// link becomes a new user with the invitation's username, name, email address and roles.
+///
+#[derive(Debug, Identifiable, Queryable)]
+pub struct Invitation {
+    pub id: i32,
+    pub token: String,
+    pub username: String,
+    pub name: String,
+    pub email: String,
+    pub created_by: i32,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub user_id: Option<i32>,
+}
 
-    pub fn processor(&self) -> &Processor {
-        &self.processor
-    }
+#[derive(Debug, Insertable)]
+#[table_name = "invitations"]
+struct InvitationInserter {
+    token: String,
+    username: String,
+    name: String,
+    email: String,
+    created_by: i32,
+    expires_at: DateTime<Utc>,
 }
 
 #[derive(Associations, Debug, Identifiable, Queryable)]
-#[belongs_to(Machine)]
-pub struct Disk {
+#[belongs_to(Invitation)]
+#[belongs_to(Role)]
+pub struct InvitationRole {
     pub id: i32,
-    pub machine_id: i32,
-    pub vendor: Option<String>,
-    pub model: Option<String>,
-    pub capacity_gb: i32,
-    pub ssd: bool,
+    pub invitation_id: i32,
+    pub role_id: i32,
 }
 
-impl Disk {
-    pub fn short_description(&self) -> String {
-        let v = self
-            .vendor
-            .as_ref()
-            .map(|vendor| format!["{} ", vendor])
-            .unwrap_or(String::new());
+#[derive(Debug, Insertable)]
+#[table_name = "invitation_roles"]
+struct InvitationRoleInserter {
+    invitation_id: i32,
+    role_id: i32,
+}
 
-        let ssd = if self.ssd { "SSD" } else { "non-SSD" };
+impl Invitation {
+    pub fn all(c: &Connection) -> DieselResult<Vec<Invitation>> {
+        use self::invitations::dsl::*;
+        invitations.order(created_at.desc()).load(c)
+    }
 
-        format!["{}{} GiB ({})", v, self.capacity_gb, ssd]
+    pub fn get(invitation_id: i32, c: &Connection) -> DieselResult<Invitation> {
+        use self::invitations::dsl::*;
+        invitations.find(invitation_id).first(c)
     }
 
-    pub fn vendor_name(&self) -> &str {
+    pub fn with_token(secret: &str, c: &Connection) -> DieselResult<Invitation> {
+        use self::invitations::dsl::*;
+        invitations.filter(token.eq(secret)).first(c)
+    }
+
+    pub fn insert(
+        new_username: String,
+        new_name: String,
+        new_email: String,
+        roles: &[Role],
+        creator: &User,
+        valid_for: Duration,
+        c: &Connection,
+    ) -> DieselResult<Invitation> {
+        c.transaction(|| {
+            let invitation: Invitation = insert_into(invitations::table)
+                .values(&InvitationInserter {
+                    token: random_token(32),
+                    username: new_username,
+                    name: new_name,
+                    email: new_email,
+                    created_by: creator.id,
+                    expires_at: Utc::now() + valid_for,
+                })
+                .get_result(c)?;
+
+            let rows = roles
+                .iter()
+                .map(|r| InvitationRoleInserter {
+                    invitation_id: invitation.id,
+                    role_id: r.id,
+                })
+                .collect::<Vec<_>>();
+
+            insert_into(invitation_roles::table)
+                .values(&rows)
+                .execute(c)?;
+
+            Ok(invitation)
+        })
+    }
+
+    /// The roles that the invited user will inhabit.
+    pub fn roles(&self, c: &Connection) -> DieselResult<Vec<Role>> {
+        InvitationRole::belonging_to(self)
+            .inner_join(roles::table)
+            .order(roles::name)
+            .load(c)
+            .map(|roles: Vec<(InvitationRole, Role)>| roles.into_iter().map(|(_, r)| r).collect())
+    }
+
+    /// Can this invitation still be accepted?
+    pub fn is_open(&self) -> bool {
+        self.accepted_at.is_none() && self.expires_at > Utc::now()
+    }
+
+    /// Accept this invitation, creating a user who logs in with the given identity.
+    pub fn accept(self, provider: &str, subject: &str, c: &Connection) -> DieselResult<User> {
+        c.transaction(|| {
+            let roles = self
+                .roles(c)?
+                .into_iter()
+                .map(|r| r.name)
+                .collect::<HashSet<_>>();
+
+            let user = create_account(
+                self.username.clone(),
+                self.name.clone(),
+                &self.email,
+                &roles,
+                provider,
+                subject,
+                c,
+            )?;
+
+            diesel::update(&self)
+                .set((
+                    invitations::accepted_at.eq(Some(Utc::now())),
+                    invitations::user_id.eq(Some(user.id)),
+                ))
+                .execute(c)?;
+
+            Ok(user)
+        })
+    }
+
+    /// Withdraw this invitation (it must not have been accepted).
+    pub fn revoke(self, c: &Connection) -> DieselResult<()> {
+        c.transaction(|| {
+            diesel::delete(InvitationRole::belonging_to(&self)).execute(c)?;
+            diesel::delete(&self).execute(c).map(|_| ())
+        })
+    }
+}
+
+///
+/// A logged-in browser: the session cookie holds the token, and the session ends when it is
+/// deleted (by logging out or revoking it) or times out.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(User)]
+pub struct Session {
+    pub id: i32,
+    pub user_id: i32,
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+
+    /// The secret that every form submitted in this session must carry (see `html::forms`)
+    pub csrf_token: String,
+
+    /// The user that this session's (administrator) user is viewing Clowder as, if any
+    pub acting_as: Option<i32>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "sessions"]
+struct SessionInserter {
+    user_id: i32,
+    token: String,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+    csrf_token: String,
+}
+
+/// Sessions are audited without their secrets.
+impl ToJson for Session {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("user_id", self.user_id.to_json()),
+            ("created_at", self.created_at.to_rfc3339().to_json()),
+            ("last_seen", self.last_seen.to_rfc3339().to_json()),
+            ("ip_address", self.ip_address.to_json()),
+            ("user_agent", self.user_agent.to_json()),
+            ("acting_as", self.acting_as.to_json()),
+        ])
+    }
+}
+
+impl Session {
+    pub fn get(session_id: i32, c: &Connection) -> DieselResult<Session> {
+        use self::sessions::dsl::*;
+        sessions.find(session_id).first(c)
+    }
+
+    pub fn with_token(secret: &str, c: &Connection) -> DieselResult<Session> {
+        use self::sessions::dsl::*;
+        sessions.filter(token.eq(secret)).first(c)
+    }
+
+    /// A user's sessions, most recently used first.
+    pub fn for_user(user: &User, c: &Connection) -> DieselResult<Vec<Session>> {
+        Session::belonging_to(user)
+            .order(sessions::last_seen.desc())
+            .load(c)
+    }
+
+    pub fn insert(
+        user: &User,
+        ip: Option<String>,
+        agent: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<Session> {
+        insert_into(sessions::table)
+            .values(&SessionInserter {
+                user_id: user.id,
+                token: random_token(32),
+                ip_address: ip,
+                user_agent: agent,
+                csrf_token: random_token(32),
+            })
+            .get_result(c)
+    }
+
+    /// Has this session outlived its maximum lifetime, or been idle for too long?
+    pub fn is_expired(&self, lifetime: Duration, idle: Duration) -> bool {
+        let now = Utc::now();
+        self.created_at + lifetime < now || self.last_seen + idle < now
+    }
+
+    /// Start (or, given `None`, stop) viewing Clowder as another user in this session.
+    pub fn act_as(self, user: Option<&User>, c: &Connection) -> DieselResult<Session> {
+        diesel::update(&self)
+            .set(sessions::acting_as.eq(user.map(|u| u.id)))
+            .get_result(c)
+    }
+
+    /// Record that this session has just been used.
+    pub fn touch(self, c: &Connection) -> DieselResult<Session> {
+        diesel::update(&self)
+            .set(sessions::last_seen.eq(Utc::now()))
+            .get_result(c)
+    }
+
+    pub fn delete(self, c: &Connection) -> DieselResult<()> {
+        diesel::delete(&self).execute(c).map(|_| ())
+    }
+}
+
+///
+/// A record of something that someone did, e.g., ending a reservation or changing a user's roles.
+///
+/// The audit log is append-only: the database refuses to update or delete events, and events
+/// don't refer to users by foreign key (so that they outlive the users in question).
+///
+#[derive(Debug, Identifiable, Queryable)]
+pub struct AuditEvent {
+    pub id: i32,
+    pub actor_id: Option<i32>,
+    pub actor: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: i32,
+
+    /// A JSON snapshot of the target before the action (if it existed)
+    pub before: Option<String>,
+
+    /// A JSON snapshot of the target after the action (if it still exists)
+    pub after: Option<String>,
+
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "audit_events"]
+struct AuditEventInserter {
+    actor_id: Option<i32>,
+    actor: String,
+    action: String,
+    target_type: String,
+    target_id: i32,
+    before: Option<String>,
+    after: Option<String>,
+    ip_address: Option<String>,
+}
+
+/// An action to be recorded in the audit log (see `AuditEvent::build`).
+pub struct AuditEventBuilder {
+    action: String,
+    target_type: String,
+    target_id: i32,
+    before: Option<Json>,
+    after: Option<Json>,
+}
+
+impl AuditEventBuilder {
+    pub fn before<T: ToJson>(mut self, target: &T) -> AuditEventBuilder {
+        self.before = Some(target.to_json());
+        self
+    }
+
+    pub fn after<T: ToJson>(mut self, target: &T) -> AuditEventBuilder {
+        self.after = Some(target.to_json());
+        self
+    }
+
+    pub fn insert(
+        self,
+        actor: &User,
+        ip: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<AuditEvent> {
+        insert_into(audit_events::table)
+            .values(&AuditEventInserter {
+                actor_id: Some(actor.id),
+                actor: actor.username.clone(),
+                action: self.action,
+                target_type: self.target_type,
+                target_id: self.target_id,
+                before: self.before.map(|j| j.to_string()),
+                after: self.after.map(|j| j.to_string()),
+                ip_address: ip,
+            })
+            .get_result(c)
+    }
+}
+
+/// Which audit events to look at (every field that is set must match).
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<i32>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditEvent {
+    ///
+    /// Describe an action (e.g., `reservation.end`) taken on a target (e.g., `reservation` 42),
+    /// to be recorded with `AuditEventBuilder::insert`.
+    ///
+    pub fn build<S1, S2>(action: S1, target_type: S2, target_id: i32) -> AuditEventBuilder
+    where
+        S1: Into<String>,
+        S2: Into<String>,
+    {
+        AuditEventBuilder {
+            action: action.into(),
+            target_type: target_type.into(),
+            target_id,
+            before: None,
+            after: None,
+        }
+    }
+
+    /// Find the events that match a filter, most recent first (at most `limit` of them, if given).
+    pub fn search(
+        filter: &AuditFilter,
+        limit: Option<i64>,
+        c: &Connection,
+    ) -> DieselResult<Vec<AuditEvent>> {
+        let mut query = audit_events::table.into_boxed();
+
+        if let Some(ref actor) = filter.actor {
+            query = query.filter(audit_events::actor.eq(actor.clone()));
+        }
+        if let Some(ref action) = filter.action {
+            query = query.filter(audit_events::action.eq(action.clone()));
+        }
+        if let Some(ref target_type) = filter.target_type {
+            query = query.filter(audit_events::target_type.eq(target_type.clone()));
+        }
+        if let Some(target_id) = filter.target_id {
+            query = query.filter(audit_events::target_id.eq(target_id));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(audit_events::created_at.ge(since));
+        }
+        if let Some(until) = filter.until {
+            query = query.filter(audit_events::created_at.lt(until));
+        }
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        query
+            .order((audit_events::created_at.desc(), audit_events::id.desc()))
+            .load(c)
+    }
+
+    /// Every kind of action that has been recorded, for filtering by.
+    pub fn actions(c: &Connection) -> DieselResult<Vec<String>> {
+        audit_events::table
+            .select(audit_events::action)
+            .distinct()
+            .order(audit_events::action)
+            .load(c)
+    }
+}
+
+impl ToJson for AuditEvent {
+    fn to_json(&self) -> Json {
+        let snapshot = |s: &Option<String>| {
+            s.as_ref()
+                .and_then(|s| Json::from_str(s).ok())
+                .unwrap_or(Json::Null)
+        };
+
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("time", self.created_at.to_rfc3339().to_json()),
+            ("actor_id", self.actor_id.to_json()),
+            ("actor", self.actor.to_json()),
+            ("action", self.action.to_json()),
+            ("target_type", self.target_type.to_json()),
+            ("target_id", self.target_id.to_json()),
+            ("before", snapshot(&self.before)),
+            ("after", snapshot(&self.after)),
+            ("ip_address", self.ip_address.to_json()),
+        ])
+    }
+}
+
+///
+/// A secret token that lets calendar clients (which can't log in) fetch a user's iCalendar feeds.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(User)]
+pub struct CalendarToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub token: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "calendar_tokens"]
+struct CalendarTokenInserter {
+    user_id: i32,
+    token: String,
+}
+
+impl CalendarToken {
+    /// Look up the user that a calendar token belongs to.
+    pub fn user(secret: &str, c: &Connection) -> DieselResult<User> {
+        use self::calendar_tokens::dsl::*;
+        calendar_tokens
+            .inner_join(users::table)
+            .filter(token.eq(secret))
+            .first(c)
+            .map(|(_, u): (CalendarToken, User)| u)
+    }
+
+    /// Find a user's calendar token, if they have created one.
+    pub fn for_user(u: &User, c: &Connection) -> DieselResult<Option<CalendarToken>> {
+        CalendarToken::belonging_to(u).first(c).optional()
+    }
+
+    ///
+    /// Create a calendar token for a user or replace their existing one (e.g., if it has leaked),
+    /// invalidating any feed URLs that used the old token.
+    ///
+    pub fn regenerate(u: &User, c: &Connection) -> DieselResult<CalendarToken> {
+        c.transaction(|| {
+            diesel::delete(CalendarToken::belonging_to(u)).execute(c)?;
+
+            insert_into(calendar_tokens::table)
+                .values(&CalendarTokenInserter {
+                    user_id: u.id,
+                    token: random_token(32),
+                })
+                .get_result(c)
+        })
+    }
+}
+
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(User)]
+pub struct Email {
+    pub id: i32,
+    pub user_id: i32,
+    pub email: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "emails"]
+struct EmailInserter {
+    user_id: i32,
+    email: String,
+}
+
+impl Email {
+    pub fn insert<S>(user: &User, email: S, conn: &Connection) -> DieselResult<Email>
+    where
+        S: Into<String>,
+    {
+        diesel::insert_into(emails::table)
+            .values(&EmailInserter {
+                user_id: user.id,
+                email: email.into(),
+            })
+            .get_result(conn)
+    }
+}
+
+#[derive(Associations, Debug, Identifiable, Queryable)]
+pub struct Role {
+    pub id: i32,
+    pub name: String,
+    pub max_reservation_hours: Option<i32>,
+    pub max_advance_days: Option<i32>,
+    pub max_concurrent_reservations: Option<i32>,
+    pub max_weekly_hours: Option<i32>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "roles"]
+struct RoleInserter {
+    name: String,
+}
+
+impl ToJson for Role {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("name", self.name.to_json()),
+            ("max_reservation_hours", self.max_reservation_hours.to_json()),
+            ("max_advance_days", self.max_advance_days.to_json()),
+            ("max_concurrent_reservations", self.max_concurrent_reservations.to_json()),
+            ("max_weekly_hours", self.max_weekly_hours.to_json()),
+        ])
+    }
+}
+
+impl Role {
+    pub fn all(c: &Connection) -> DieselResult<Vec<Role>> {
+        use self::roles::dsl::*;
+        roles.order(name).load(c)
+    }
+
+    pub fn get(role_id: i32, c: &Connection) -> DieselResult<Role> {
+        use self::roles::dsl::*;
+        roles.find(role_id).first(c)
+    }
+
+    pub fn with_name(role_name: &str, c: &Connection) -> DieselResult<Role> {
+        use self::roles::dsl::*;
+        roles.filter(name.eq(role_name)).first(c)
+    }
+
+    /// Create a new role with no capabilities and no reservation limits.
+    pub fn insert(role_name: String, c: &Connection) -> DieselResult<Role> {
+        insert_into(roles::table)
+            .values(&RoleInserter { name: role_name })
+            .get_result(c)
+    }
+
+    pub fn rename(self, new_name: String, c: &Connection) -> DieselResult<Role> {
+        diesel::update(&self)
+            .set(roles::name.eq(new_name))
+            .get_result(c)
+    }
+
+    ///
+    /// Delete this role, removing it from every user that inhabits it (along with its
+    /// capabilities and any machine access rules that name it).
+    ///
+    pub fn delete(self, c: &Connection) -> DieselResult<()> {
+        c.transaction(|| {
+            diesel::delete(RoleAssignment::belonging_to(&self)).execute(c)?;
+            diesel::delete(RoleCapability::belonging_to(&self)).execute(c)?;
+            diesel::delete(LdapGroupRole::belonging_to(&self)).execute(c)?;
+            diesel::delete(InvitationRole::belonging_to(&self)).execute(c)?;
+            diesel::delete(access_rules::table.filter(access_rules::role_id.eq(self.id)))
+                .execute(c)?;
+            diesel::delete(&self).execute(c)?;
+
+            Ok(())
+        })
+    }
+
+    /// Find the users who inhabit this role, ordered by username.
+    pub fn members(&self, c: &Connection) -> DieselResult<Vec<User>> {
+        RoleAssignment::belonging_to(self)
+            .inner_join(users::table)
+            .order(users::username)
+            .load(c)
+            .map(|users: Vec<(RoleAssignment, User)>| users.into_iter().map(|(_, u)| u).collect())
+    }
+
+    pub fn capabilities(&self, c: &Connection) -> DieselResult<BTreeSet<Capability>> {
+        RoleCapability::belonging_to(self)
+            .select(role_capabilities::capability)
+            .load::<String>(c)
+            .map(|names| names.iter().filter_map(|n| n.parse().ok()).collect())
+    }
+
+    /// Replace the complete set of capabilities granted by this role.
+    pub fn set_capabilities(
+        &self,
+        capabilities: &BTreeSet<Capability>,
+        c: &Connection,
+    ) -> DieselResult<()> {
+        c.transaction(|| {
+            diesel::delete(RoleCapability::belonging_to(self)).execute(c)?;
+
+            let rows = capabilities
+                .iter()
+                .map(|cap| RoleCapabilityInserter {
+                    role_id: self.id,
+                    capability: cap.name(),
+                })
+                .collect::<Vec<_>>();
+
+            insert_into(role_capabilities::table)
+                .values(&rows)
+                .execute(c)
+                .map(|_| ())
+        })
+    }
+
+    ///
+    /// A JSON snapshot of this role, including its capabilities and LDAP groups (for the audit
+    /// log).
+    ///
+    pub fn audit_snapshot(&self, c: &Connection) -> DieselResult<Json> {
+        let capabilities = self.capabilities(c)?.iter().map(Capability::name).collect::<Vec<_>>();
+        let groups = self.ldap_groups(c)?.into_iter().map(|g| g.group_name).collect::<Vec<_>>();
+
+        let mut snapshot = self.to_json();
+        if let Json::Object(ref mut fields) = snapshot {
+            fields.insert(String::from("capabilities"), capabilities.to_json());
+            fields.insert(String::from("ldap_groups"), groups.to_json());
+        }
+
+        Ok(snapshot)
+    }
+
+    /// The LDAP groups whose members inhabit this role.
+    pub fn ldap_groups(&self, c: &Connection) -> DieselResult<Vec<LdapGroupRole>> {
+        LdapGroupRole::belonging_to(self)
+            .order(ldap_group_roles::group_name)
+            .load(c)
+    }
+
+    pub fn add_ldap_group(&self, group: String, c: &Connection) -> DieselResult<LdapGroupRole> {
+        insert_into(ldap_group_roles::table)
+            .values(&LdapGroupRoleInserter {
+                role_id: self.id,
+                group_name: group,
+            })
+            .get_result(c)
+    }
+
+    /// Change the reservation limits that this role imposes on its members.
+    pub fn set_policy(self, policy: &ReservationPolicy, c: &Connection) -> DieselResult<Role> {
+        diesel::update(&self)
+            .set((
+                roles::max_reservation_hours.eq(policy.max_hours),
+                roles::max_advance_days.eq(policy.max_advance_days),
+                roles::max_concurrent_reservations.eq(policy.max_concurrent),
+                roles::max_weekly_hours.eq(policy.max_weekly_hours),
+            ))
+            .get_result(c)
+    }
+
+    /// The reservation limits that this role imposes on its members.
+    pub fn policy(&self) -> ReservationPolicy {
+        ReservationPolicy {
+            max_hours: self.max_reservation_hours,
+            max_advance_days: self.max_advance_days,
+            max_concurrent: self.max_concurrent_reservations,
+            max_weekly_hours: self.max_weekly_hours,
+        }
+    }
+}
+
+///
+/// Something that a role permits its members to do.
+///
+/// Capabilities are stored by name (see `Capability::name`), so adding a new one only requires a
+/// new variant here (and a check wherever it applies), not a schema change.
+///
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Capability {
+    AlterMachines,
+    AlterUsers,
+    ApproveReservations,
+    CreateMachines,
+    DeleteMachines,
+    ReserveForOthers,
+    ViewAudit,
+    ViewUsers,
+}
+
+impl Capability {
+    /// Every capability, in the order they should be presented to administrators.
+    pub fn all() -> &'static [Capability] {
+        use self::Capability::*;
+        &[
+            AlterMachines,
+            AlterUsers,
+            ApproveReservations,
+            CreateMachines,
+            DeleteMachines,
+            ReserveForOthers,
+            ViewAudit,
+            ViewUsers,
+        ]
+    }
+
+    /// The name under which this capability is stored in the database.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Capability::AlterMachines => "alter_machines",
+            Capability::AlterUsers => "alter_users",
+            Capability::ApproveReservations => "approve_reservations",
+            Capability::CreateMachines => "create_machines",
+            Capability::DeleteMachines => "delete_machines",
+            Capability::ReserveForOthers => "reserve_for_others",
+            Capability::ViewAudit => "view_audit",
+            Capability::ViewUsers => "view_users",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Capability::AlterMachines => "Alter any machine, its pool and its access rules",
+            Capability::AlterUsers => "Alter users, roles and anyone's reservations",
+            Capability::ApproveReservations => "Approve or reject reservations",
+            Capability::CreateMachines => "Add machines to the inventory",
+            Capability::DeleteMachines => "Remove machines from the inventory",
+            Capability::ReserveForOthers => "Reserve machines on behalf of other users",
+            Capability::ViewAudit => "Read the audit log of privileged and reservation actions",
+            Capability::ViewUsers => "View other users' details",
+        }
+    }
+
+    /// Does any user hold this capability (through any of their roles)?
+    pub fn is_held(&self, c: &Connection) -> DieselResult<bool> {
+        let granting_roles = role_capabilities::table
+            .filter(role_capabilities::capability.eq(self.name()))
+            .select(role_capabilities::role_id);
+
+        diesel::select(dsl::exists(
+            role_assignments::table.filter(role_assignments::role_id.eq_any(granting_roles)),
+        ))
+        .get_result(c)
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Capability, Error> {
+        Capability::all()
+            .iter()
+            .find(|c| c.name() == s)
+            .cloned()
+            .ok_or_else(|| Error::InvalidData(format!["unknown capability '{}'", s]))
+    }
+}
+
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Role)]
+#[table_name = "role_capabilities"]
+pub struct RoleCapability {
+    pub id: i32,
+    pub role_id: i32,
+    pub capability: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "role_capabilities"]
+struct RoleCapabilityInserter {
+    role_id: i32,
+    capability: &'static str,
+}
+
+///
+/// A mapping from an LDAP group to a role: members of the group inhabit the role.
+///
+/// Roles that have at least one LDAP group are managed by the directory: each time a user logs in
+/// via LDAP, they are added to or removed from such roles according to their group memberships.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Role)]
+pub struct LdapGroupRole {
+    pub id: i32,
+    pub role_id: i32,
+    pub group_name: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "ldap_group_roles"]
+struct LdapGroupRoleInserter {
+    role_id: i32,
+    group_name: String,
+}
+
+impl LdapGroupRole {
+    pub fn get(mapping_id: i32, c: &Connection) -> DieselResult<LdapGroupRole> {
+        use self::ldap_group_roles::dsl::*;
+        ldap_group_roles.find(mapping_id).first(c)
+    }
+
+    pub fn delete(self, c: &Connection) -> DieselResult<()> {
+        diesel::delete(&self).execute(c).map(|_| ())
+    }
+
+    ///
+    /// Bring a user's directory-managed roles into line with the LDAP groups they belong to,
+    /// leaving any roles that aren't mapped from LDAP groups alone.
+    ///
+    pub fn sync_roles(user: &User, groups: &[String], c: &Connection) -> DieselResult<()> {
+        let mappings = ldap_group_roles::table
+            .inner_join(roles::table)
+            .load::<(LdapGroupRole, Role)>(c)?;
+
+        let managed = mappings
+            .iter()
+            .map(|&(ref m, _)| m.role_id)
+            .collect::<HashSet<_>>();
+
+        let mut new_roles = user
+            .roles(c)?
+            .into_iter()
+            .filter(|r| !managed.contains(&r.id))
+            .map(|r| r.name)
+            .collect::<HashSet<_>>();
+
+        for (m, r) in mappings {
+            // LDAP group names (e.g., cn values) are case-insensitive.
+            if groups.iter().any(|g| g.eq_ignore_ascii_case(&m.group_name)) {
+                new_roles.insert(r.name);
+            }
+        }
+
+        user.set_roles(&new_roles, c)
+    }
+}
+
+///
+/// Limits on the reservations that a user may make.
+///
+/// Each limit is optional: `None` means "unlimited".
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReservationPolicy {
+    /// Longest permitted reservation, in hours.
+    pub max_hours: Option<i32>,
+
+    /// How far in advance a reservation may start, in days.
+    pub max_advance_days: Option<i32>,
+
+    /// Maximum number of current-or-upcoming reservations that a user may hold at once.
+    pub max_concurrent: Option<i32>,
+
+    /// Maximum number of machine-hours that may be reserved in any one week (Monday to Sunday).
+    pub max_weekly_hours: Option<i32>,
+}
+
+impl ReservationPolicy {
+    ///
+    /// The limits on users who don't inhabit any role, so that merely being able to log in
+    /// doesn't allow unlimited reservations. Each limit can be configured with
+    /// `CLOWDER_DEFAULT_MAX_HOURS`, `CLOWDER_DEFAULT_MAX_ADVANCE_DAYS`,
+    /// `CLOWDER_DEFAULT_MAX_CONCURRENT` or `CLOWDER_DEFAULT_MAX_WEEKLY_HOURS`, any of which may be
+    /// set to `unlimited`.
+    ///
+    pub fn without_roles() -> Result<ReservationPolicy, Error> {
+        let limit = |name: &str, default: i32| match env::var(name) {
+            Err(_) => Ok(Some(default)),
+            Ok(ref s) if s == "unlimited" => Ok(None),
+            Ok(s) => match s.parse::<i32>() {
+                Ok(n) if n >= 0 => Ok(Some(n)),
+                _ => Err(Error::ConfigError(format!["invalid {}: '{}'", name, s])),
+            },
+        };
+
+        Ok(ReservationPolicy {
+            max_hours: limit("CLOWDER_DEFAULT_MAX_HOURS", 24)?,
+            max_advance_days: limit("CLOWDER_DEFAULT_MAX_ADVANCE_DAYS", 7)?,
+            max_concurrent: limit("CLOWDER_DEFAULT_MAX_CONCURRENT", 1)?,
+            max_weekly_hours: limit("CLOWDER_DEFAULT_MAX_WEEKLY_HOURS", 24)?,
+        })
+    }
+
+    ///
+    /// Combine the policies of several roles, taking the most permissive value of each limit.
+    ///
+    /// Returns `None` if there are no policies to combine: the caller must decide what limits
+    /// apply to a user without any roles.
+    ///
+    pub fn most_permissive<I>(policies: I) -> Option<ReservationPolicy>
+    where
+        I: IntoIterator<Item = ReservationPolicy>,
+    {
+        let mut policies = policies.into_iter();
+        let first = policies.next()?;
+
+        // None (unlimited) beats any limit; otherwise the larger limit wins.
+        let loosest = |a: Option<i32>, b: Option<i32>| match (a, b) {
+            (Some(x), Some(y)) => Some(x.max(y)),
+            (_, _) => None,
+        };
+
+        Some(policies.fold(first, |acc, p| ReservationPolicy {
+            max_hours: loosest(acc.max_hours, p.max_hours),
+            max_advance_days: loosest(acc.max_advance_days, p.max_advance_days),
+            max_concurrent: loosest(acc.max_concurrent, p.max_concurrent),
+            max_weekly_hours: loosest(acc.max_weekly_hours, p.max_weekly_hours),
+        }))
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == ReservationPolicy::default()
+    }
+
+    ///
+    /// Check whether `user` may make a set of new reservations, each described by its start and
+    /// (optional) end time.
+    ///
+    /// All of the new reservations are considered together, so (e.g.) a recurring reservation
+    /// can't exceed a weekly quota by being checked one occurrence at a time.
+    ///
+    pub fn check(
+        &self,
+        user: &User,
+        new: &[(DateTime<Utc>, Option<DateTime<Utc>>)],
+        c: &Connection,
+    ) -> Result<(), Error> {
+        self.check_replacing(user, new, &[], c)
+    }
+
+    ///
+    /// Like `check`, but for changes to existing reservations (e.g., extending them): the
+    /// windows in `new` replace the reservations in `replaced` rather than adding to them.
+    ///
+    pub fn check_replacing(
+        &self,
+        user: &User,
+        new: &[(DateTime<Utc>, Option<DateTime<Utc>>)],
+        replaced: &[i32],
+        c: &Connection,
+    ) -> Result<(), Error> {
+        if self.is_unlimited() || new.is_empty() {
+            return Ok(());
+        }
+
+        let now = Utc::now();
+        let mut violations = Vec::new();
+
+        for &(start, end) in new {
+            if let Some(max) = self.max_hours {
+                match end {
+                    Some(end) if end - start > Duration::hours(max as i64) => {
+                        violations.push(format![
+                            "reservations may last at most {} hours, but the reservation \
+                             starting {} lasts {} hours",
+                            max,
+                            start,
+                            (end - start).num_minutes() as f64 / 60.0
+                        ]);
+                    }
+                    Some(_) => {}
+                    None => violations.push(format![
+                        "reservations may last at most {} hours, so they must have an end time",
+                        max
+                    ]),
+                }
+            }
+
+            if let Some(max) = self.max_advance_days {
+                if start - now > Duration::days(max as i64) {
+                    violations.push(format![
+                        "reservations may be made at most {} days in advance, but {} is {} days \
+                         away",
+                        max,
+                        start,
+                        (start - now).num_days()
+                    ]);
+                }
+            }
+        }
+
+        if self.max_concurrent.is_none() && self.max_weekly_hours.is_none() {
+            return ReservationPolicy::verdict(violations);
+        }
+
+        // Existing reservations that still (or will) occupy a machine.
+        let existing = Reservation::for_user(user, c)?
+            .into_iter()
+            .map(|(r, _)| r)
+            .filter(|r| !r.is_cancelled() && r.busy_until().map(|t| t > now).unwrap_or(true))
+            .filter(|r| !replaced.contains(&r.id))
+            .collect::<Vec<_>>();
+
+        if let Some(max) = self.max_concurrent {
+            let total = existing.len() + new.len();
+            if total > max as usize {
+                violations.push(format![
+                    "at most {} current or upcoming reservations may be held at once, but {} \
+                     already has {} and has requested {} more",
+                    max,
+                    user.username,
+                    existing.len(),
+                    new.len()
+                ]);
+            }
+        }
+
+        if let Some(max) = self.max_weekly_hours {
+            let mut windows = existing
+                .iter()
+                .map(|r| (r.start(), r.busy_until()))
+                .collect::<Vec<_>>();
+            windows.extend_from_slice(new);
+
+            let weeks = new
+                .iter()
+                .flat_map(|&(start, end)| weeks_spanned(start, end))
+                .collect::<BTreeSet<_>>();
+
+            for week in weeks {
+                let week_end = week + Duration::weeks(1);
+                let minutes: i64 = windows
+                    .iter()
+                    .map(|&(start, end)| {
+                        let from = start.max(week);
+                        let to = end.unwrap_or(week_end).min(week_end);
+                        if to > from {
+                            (to - from).num_minutes()
+                        } else {
+                            0
+                        }
+                    })
+                    .sum();
+
+                if minutes > max as i64 * 60 {
+                    violations.push(format![
+                        "at most {} machine-hours may be reserved per week, but the week of {} \
+                         would have {} machine-hours reserved",
+                        max,
+                        week.format("%e %b %Y"),
+                        minutes as f64 / 60.0
+                    ]);
+                }
+            }
+        }
+
+        ReservationPolicy::verdict(violations)
+    }
+
+    fn verdict(violations: Vec<String>) -> Result<(), Error> {
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::PolicyViolation(violations))
+        }
+    }
+}
+
+/// The starts (midnight UTC on Monday) of all weeks that a time window overlaps.
+fn weeks_spanned(start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> Vec<DateTime<Utc>> {
+    let day = start.date();
+    let monday = day - Duration::days(day.weekday().num_days_from_monday() as i64);
+    let mut week = monday.and_hms(0, 0, 0);
+
+    // An open-ended reservation counts against the week that it starts in.
+    let end = end.unwrap_or(start);
+
+    let mut weeks = vec![week];
+    week = week + Duration::weeks(1);
+    while week < end {
+        weeks.push(week);
+        week = week + Duration::weeks(1);
+    }
+
+    weeks
+}
+
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Role)]
+#[belongs_to(User)]
+pub struct RoleAssignment {
+    pub id: i32,
+    pub user_id: i32,
+    pub role_id: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "role_assignments"]
+struct RoleAssigner {
+    user_id: i32,
+    role_id: i32,
+}
+
+impl RoleAssignment {
+    pub fn insert(user: &User, role: &Role, conn: &Connection) -> DieselResult<RoleAssignment> {
+        diesel::insert_into(role_assignments::table)
+            .values(&RoleAssigner {
+                user_id: user.id,
+                role_id: role.id,
+            })
+            .get_result(conn)
+    }
+}
+
+///
+/// A group of users (e.g., a research group) that can share ownership of reservations.
+///
+#[derive(Debug, Identifiable, Queryable)]
+pub struct Team {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "teams"]
+struct TeamInserter {
+    name: String,
+    description: String,
+}
+
+impl ToJson for Team {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("name", self.name.to_json()),
+            ("description", self.description.to_json()),
+        ])
+    }
+}
+
+impl Team {
+    pub fn all(c: &Connection) -> DieselResult<Vec<Team>> {
+        use self::teams::dsl::*;
+        teams.order(name).load(c)
+    }
+
+    pub fn get(team_id: i32, c: &Connection) -> DieselResult<Team> {
+        use self::teams::dsl::*;
+        teams.find(team_id).first(c)
+    }
+
+    pub fn with_name(team_name: &str, c: &Connection) -> DieselResult<Team> {
+        use self::teams::dsl::*;
+        teams.filter(name.eq(team_name)).first(c)
+    }
+
+    /// Create a new team, with its creator as the first manager.
+    pub fn insert(
+        team_name: String,
+        desc: String,
+        creator: &User,
+        c: &Connection,
+    ) -> DieselResult<Team> {
+        c.transaction(|| {
+            let team: Team = insert_into(teams::table)
+                .values(&TeamInserter {
+                    name: team_name,
+                    description: desc,
+                })
+                .get_result(c)?;
+
+            team.add_member(creator, true, c)?;
+            Ok(team)
+        })
+    }
+
+    /// Find this team's members, ordered by username.
+    pub fn members(&self, c: &Connection) -> DieselResult<Vec<(TeamMembership, User)>> {
+        use self::team_memberships::dsl::*;
+        team_memberships
+            .inner_join(users::table)
+            .filter(team_id.eq(self.id))
+            .order(users::username)
+            .load(c)
+    }
+
+    fn membership(&self, user: &User, c: &Connection) -> DieselResult<Option<TeamMembership>> {
+        use self::team_memberships::dsl::*;
+        team_memberships
+            .filter(team_id.eq(self.id))
+            .filter(user_id.eq(user.id))
+            .first(c)
+            .optional()
+    }
+
+    pub fn has_member(&self, user: &User, c: &Connection) -> DieselResult<bool> {
+        self.membership(user, c).map(|m| m.is_some())
+    }
+
+    /// Can a user add and remove this team's members?
+    pub fn is_managed_by(&self, user: &User, c: &Connection) -> DieselResult<bool> {
+        self.membership(user, c)
+            .map(|m| m.map(|m| m.is_manager).unwrap_or(false))
+    }
+
+    /// Add a user to this team (or change their role if they are already a member).
+    pub fn add_member(
+        &self,
+        user: &User,
+        manager: bool,
+        c: &Connection,
+    ) -> DieselResult<TeamMembership> {
+        use self::team_memberships::dsl::*;
+
+        match self.membership(user, c)? {
+            Some(m) => diesel::update(&m).set(is_manager.eq(manager)).get_result(c),
+            None => insert_into(team_memberships)
+                .values(&TeamMembershipInserter {
+                    team_id: self.id,
+                    user_id: user.id,
+                    is_manager: manager,
+                })
+                .get_result(c),
+        }
+    }
+
+    pub fn remove_member(&self, user: &User, c: &Connection) -> DieselResult<()> {
+        use self::team_memberships::dsl::*;
+        diesel::delete(team_memberships.filter(team_id.eq(self.id)).filter(user_id.eq(user.id)))
+            .execute(c)
+            .map(|_| ())
+    }
+
+    /// Find the (uncancelled) reservations shared with this team, most recent first.
+    pub fn reservations(&self, c: &Connection) -> DieselResult<Vec<FullReservation>> {
+        use self::reservations::dsl::*;
+        reservations
+            .inner_join(machines::table)
+            .inner_join(users::table)
+            .filter(team_id.eq(self.id))
+            .filter(cancelled_at.is_null())
+            .order(scheduled_start.desc())
+            .load(c)
+    }
+}
+
+///
+/// A user's membership in a team. Every member can manage the team's reservations;
+/// managers can also change the team's membership.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Team)]
+#[belongs_to(User)]
+pub struct TeamMembership {
+    pub id: i32,
+    pub team_id: i32,
+    pub user_id: i32,
+    pub is_manager: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "team_memberships"]
+struct TeamMembershipInserter {
+    team_id: i32,
+    user_id: i32,
+    is_manager: bool,
+}
+
+#[derive(Debug, Identifiable, Queryable)]
+pub struct Architecture {
+    pub id: i32,
+    pub name: String,
+}
+
+impl Architecture {
+    pub fn all(c: &Connection) -> DieselResult<Vec<Architecture>> {
+        use self::architectures::dsl::*;
+        architectures.order(name).load(c)
+    }
+
+    pub fn get(arch_id: i32, c: &Connection) -> DieselResult<Architecture> {
+        use self::architectures::dsl::*;
+        architectures.find(arch_id).first(c)
+    }
+}
+
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Architecture, foreign_key = "arch_id")]
+pub struct Microarchitecture {
+    pub id: i32,
+    pub arch_id: i32,
+    pub name: String,
+    pub url: Option<String>,
+    pub generation: i32,
+}
+
+impl Microarchitecture {
+    pub fn all(c: &Connection) -> DieselResult<Vec<Microarchitecture>> {
+        use self::microarchitectures::dsl::*;
+        microarchitectures.order((arch_id, generation, name)).load(c)
+    }
+
+    pub fn get(microarch_id: i32, c: &Connection) -> DieselResult<Microarchitecture> {
+        use self::microarchitectures::dsl::*;
+        microarchitectures.find(microarch_id).first(c)
+    }
+
+    pub fn arch(&self, c: &Connection) -> DieselResult<Architecture> {
+        use self::architectures::dsl::*;
+        architectures.find(self.arch_id).first(c)
+    }
+}
+
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Microarchitecture, foreign_key = "microarch_id")]
+pub struct Processor {
+    pub id: i32,
+    pub microarch_id: i32,
+    pub name: String,
+    pub cores: i32,
+    pub threads: i32,
+    pub freq_ghz: f64,
+    pub url: Option<String>,
+}
+
+impl Processor {
+    pub fn all(c: &Connection) -> DieselResult<Vec<Processor>> {
+        use self::processors::dsl::*;
+        processors.order(name).load(c)
+    }
+
+    pub fn get(processor_id: i32, c: &Connection) -> DieselResult<Processor> {
+        use self::processors::dsl::*;
+        processors.find(processor_id).first(c)
+    }
+}
+
+#[derive(Clone, Debug, Identifiable, Queryable)]
+pub struct Machine {
+    pub id: i32,
+    pub name: String,
+    pub memory_gb: i32,
+
+    /// Reservations of this machine must be approved before they take effect.
+    pub requires_approval: bool,
+
+    /// The pool whose access control list (along with the machine's own) governs this machine.
+    pub pool_id: Option<i32>,
+}
+
+impl ToJson for Machine {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("name", self.name.to_json()),
+            ("memory_gb", self.memory_gb.to_json()),
+            ("requires_approval", self.requires_approval.to_json()),
+            ("pool_id", self.pool_id.to_json()),
+        ])
+    }
+}
+
+/// Escape `LIKE` wildcards (and the escape character itself) so that text only matches literally.
+fn like_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+type EscapedILike<T> = Escape<ILike<T, String>, AsExprOf<String, VarChar>>;
+
+/// `column ILIKE pattern ESCAPE '\'` (Diesel only provides `escape` for case-sensitive `LIKE`).
+fn ilike_escaped<T>(column: T, pattern: &str) -> EscapedILike<T>
+where
+    T: PgTextExpressionMethods + Expression,
+{
+    Escape::new(column.ilike(pattern.to_string()), String::from("\\").into_sql::<VarChar>())
+}
+
+impl Machine {
+    pub fn all(c: &Connection) -> DieselResult<Vec<Machine>> {
+        use self::machines::dsl::*;
+        machines.order(name).load(c)
+    }
+
+    pub fn devices(&self, c: &Connection) -> DieselResult<Vec<Device>> {
+        Device::belonging_to(self).load(c)
+    }
+
+    pub fn disks(&self, c: &Connection) -> DieselResult<Vec<Disk>> {
+        Disk::belonging_to(self).load(c)
+    }
+
+    pub fn nics(&self, c: &Connection) -> DieselResult<Vec<Nic>> {
+        Nic::belonging_to(self).load(c)
+    }
+
+    pub fn tags(&self, c: &Connection) -> DieselResult<Vec<String>> {
+        MachineTag::belonging_to(self)
+            .order(machine_tags::tag)
+            .load(c)
+            .map(|tags| tags.into_iter().map(|t: MachineTag| t.tag).collect())
+    }
+
+    ///
+    /// Find machines whose names, or whose devices' kind, vendor, model or attributes, contain
+    /// a (case-insensitive) search string.
+    ///
+    pub fn search(query: &str, c: &Connection) -> DieselResult<Vec<Machine>> {
+        use self::machines::dsl::*;
+
+        let pattern = format!["%{}%", like_escape(query)];
+        let with_devices = devices::table.select(devices::machine_id).filter(
+            ilike_escaped(devices::kind, &pattern)
+                .or(ilike_escaped(devices::vendor, &pattern))
+                .or(ilike_escaped(devices::model, &pattern))
+                .or(ilike_escaped(devices::attributes, &pattern)),
+        );
+
+        machines
+            .filter(ilike_escaped(name, &pattern).or(id.eq_any(with_devices)))
+            .order(name)
+            .load(c)
+    }
+
+    ///
+    /// Lock a set of machines (until the end of the current transaction) so that concurrent
+    /// reservation requests for them are serialised. Locks are always taken in id order, so that
+    /// requests for overlapping sets of machines can't deadlock.
+    ///
+    pub fn lock(ms: &[&Machine], c: &Connection) -> DieselResult<Vec<Machine>> {
+        use self::machines::dsl::*;
+        machines
+            .filter(id.eq_any(ms.iter().map(|m| m.id).collect::<Vec<_>>()))
+            .order(id)
+            .for_update()
+            .load(c)
+    }
+
+    pub fn get(machine_id: i32, c: &Connection) -> DieselResult<Machine> {
+        use self::machines::dsl::*;
+        machines.find(machine_id).first(c)
+    }
+
+    pub fn set_requires_approval(self, required: bool, c: &Connection) -> DieselResult<Machine> {
+        diesel::update(&self)
+            .set(machines::requires_approval.eq(required))
+            .get_result(c)
+    }
+
+    pub fn with_name(machine_name: &str, c: &Connection) -> DieselResult<Machine> {
+        use self::machines::dsl::*;
+        machines.filter(name.eq(machine_name)).first(c)
+    }
+
+    /// Move this machine into a pool (or out of any pool, if `pool` is `None`).
+    pub fn set_pool(self, pool: Option<&Pool>, c: &Connection) -> DieselResult<Machine> {
+        diesel::update(&self)
+            .set(machines::pool_id.eq(pool.map(|p| p.id)))
+            .get_result(c)
+    }
+
+    /// Find the access rules that apply to this machine specifically (not via its pool).
+    pub fn access_rules(&self, c: &Connection) -> DieselResult<Vec<AccessRule>> {
+        use self::access_rules::dsl::*;
+        access_rules.filter(machine_id.eq(self.id)).order(id).load(c)
+    }
+
+    /// Grant some permissions on this machine to a user, role or team.
+    pub fn grant(&self, to: Grantee, access: Access, c: &Connection) -> DieselResult<AccessRule> {
+        AccessRuleInserter::new(None, Some(self.id), to, access).insert(c)
+    }
+}
+
+///
+/// A named set of machines that share an access control list.
+///
+#[derive(Debug, Identifiable, Queryable)]
+pub struct Pool {
+    pub id: i32,
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "pools"]
+struct PoolInserter {
+    name: String,
+    description: String,
+}
+
+impl ToJson for Pool {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("name", self.name.to_json()),
+            ("description", self.description.to_json()),
+        ])
+    }
+}
+
+impl Pool {
+    pub fn all(c: &Connection) -> DieselResult<Vec<Pool>> {
+        use self::pools::dsl::*;
+        pools.order(name).load(c)
+    }
+
+    pub fn get(pool_id: i32, c: &Connection) -> DieselResult<Pool> {
+        use self::pools::dsl::*;
+        pools.find(pool_id).first(c)
+    }
+
+    pub fn with_name(pool_name: &str, c: &Connection) -> DieselResult<Pool> {
+        use self::pools::dsl::*;
+        pools.filter(name.eq(pool_name)).first(c)
+    }
+
+    pub fn insert(pool_name: String, desc: String, c: &Connection) -> DieselResult<Pool> {
+        insert_into(pools::table)
+            .values(&PoolInserter {
+                name: pool_name,
+                description: desc,
+            })
+            .get_result(c)
+    }
+
+    pub fn machines(&self, c: &Connection) -> DieselResult<Vec<Machine>> {
+        use self::machines::dsl::*;
+        machines.filter(pool_id.eq(self.id)).order(name).load(c)
+    }
+
+    pub fn access_rules(&self, c: &Connection) -> DieselResult<Vec<AccessRule>> {
+        use self::access_rules::dsl::*;
+        access_rules.filter(pool_id.eq(self.id)).order(id).load(c)
+    }
+
+    /// Grant some permissions on every machine in this pool to a user, role or team.
+    pub fn grant(&self, to: Grantee, access: Access, c: &Connection) -> DieselResult<AccessRule> {
+        AccessRuleInserter::new(Some(self.id), None, to, access).insert(c)
+    }
+}
+
+///
+/// What a user may do with a machine.
+///
+/// Each permission implies the ones before it: a user who may administer a machine may also
+/// reserve it, and a user who may reserve a machine may also see it.
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Access {
+    pub view: bool,
+    pub reserve: bool,
+    pub administer: bool,
+}
+
+impl Access {
+    /// The access that everyone has to machines without any access rules.
+    pub fn open() -> Access {
+        Access {
+            view: true,
+            reserve: true,
+            administer: false,
+        }
+    }
+
+    pub fn full() -> Access {
+        Access {
+            view: true,
+            reserve: true,
+            administer: true,
+        }
+    }
+
+    /// Fill in the permissions implied by the ones that are set.
+    fn implied(self) -> Access {
+        Access {
+            view: self.view || self.reserve || self.administer,
+            reserve: self.reserve || self.administer,
+            administer: self.administer,
+        }
+    }
+
+    fn union(self, other: Access) -> Access {
+        Access {
+            view: self.view || other.view,
+            reserve: self.reserve || other.reserve,
+            administer: self.administer || other.administer,
+        }
+    }
+}
+
+/// Who an access rule applies to.
+#[derive(Clone, Copy, Debug)]
+pub enum Grantee<'a> {
+    User(&'a User),
+    Role(&'a Role),
+    Team(&'a Team),
+}
+
+///
+/// An entry in a pool's or a machine's access control list, granting permissions to a user,
+/// to everyone with a role or to every member of a team.
+///
+#[derive(Debug, Identifiable, Queryable)]
+pub struct AccessRule {
+    pub id: i32,
+    pub pool_id: Option<i32>,
+    pub machine_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub role_id: Option<i32>,
+    pub team_id: Option<i32>,
+    pub can_view: bool,
+    pub can_reserve: bool,
+    pub can_administer: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "access_rules"]
+struct AccessRuleInserter {
+    pool_id: Option<i32>,
+    machine_id: Option<i32>,
+    user_id: Option<i32>,
+    role_id: Option<i32>,
+    team_id: Option<i32>,
+    can_view: bool,
+    can_reserve: bool,
+    can_administer: bool,
+}
+
+impl ToJson for AccessRule {
+    fn to_json(&self) -> Json {
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("pool_id", self.pool_id.to_json()),
+            ("machine_id", self.machine_id.to_json()),
+            ("user_id", self.user_id.to_json()),
+            ("role_id", self.role_id.to_json()),
+            ("team_id", self.team_id.to_json()),
+            ("can_view", self.can_view.to_json()),
+            ("can_reserve", self.can_reserve.to_json()),
+            ("can_administer", self.can_administer.to_json()),
+        ])
+    }
+}
+
+impl AccessRuleInserter {
+    fn new(
+        pool: Option<i32>,
+        machine: Option<i32>,
+        to: Grantee,
+        access: Access,
+    ) -> AccessRuleInserter {
+        let access = access.implied();
+
+        AccessRuleInserter {
+            pool_id: pool,
+            machine_id: machine,
+            user_id: match to {
+                Grantee::User(u) => Some(u.id),
+                _ => None,
+            },
+            role_id: match to {
+                Grantee::Role(r) => Some(r.id),
+                _ => None,
+            },
+            team_id: match to {
+                Grantee::Team(t) => Some(t.id),
+                _ => None,
+            },
+            can_view: access.view,
+            can_reserve: access.reserve,
+            can_administer: access.administer,
+        }
+    }
+
+    fn insert(self, c: &Connection) -> DieselResult<AccessRule> {
+        insert_into(access_rules::table).values(&self).get_result(c)
+    }
+}
+
+impl AccessRule {
+    pub fn get(rule_id: i32, c: &Connection) -> DieselResult<AccessRule> {
+        use self::access_rules::dsl::*;
+        access_rules.find(rule_id).first(c)
+    }
+
+    /// Does this rule apply to a machine (either directly or via the machine's pool)?
+    pub fn covers(&self, m: &Machine) -> bool {
+        self.machine_id == Some(m.id) || (m.pool_id.is_some() && self.pool_id == m.pool_id)
+    }
+
+    pub fn access(&self) -> Access {
+        Access {
+            view: self.can_view,
+            reserve: self.can_reserve,
+            administer: self.can_administer,
+        }
+        .implied()
+    }
+
+    /// A human-readable description of who this rule applies to.
+    pub fn grantee(&self, c: &Connection) -> DieselResult<String> {
+        match (self.user_id, self.role_id, self.team_id) {
+            (Some(u), _, _) => User::get(u, c).map(|u| format!["user {}", u.username]),
+            (_, Some(r), _) => roles::table
+                .find(r)
+                .first::<Role>(c)
+                .map(|r| format!["role {}", r.name]),
+            (_, _, Some(t)) => Team::get(t, c).map(|t| format!["team {}", t.name]),
+            (None, None, None) => Ok(String::from("nobody")),
+        }
+    }
+
+    pub fn delete(self, c: &Connection) -> DieselResult<()> {
+        diesel::delete(&self).execute(c).map(|_| ())
+    }
+}
+
+///
+/// A user's access to machines, loaded once so that many machines can be checked cheaply.
+///
+/// A machine that has no access rules of its own (and isn't in a pool with access rules) is open
+/// to everyone. Otherwise, a user has whatever access is granted by the rules that name them,
+/// one of their roles or one of their teams. Users who can alter machines have full access to
+/// every machine.
+///
+pub struct AccessControl {
+    user_id: i32,
+    role_ids: HashSet<i32>,
+    team_ids: HashSet<i32>,
+    superuser: bool,
+    rules: Vec<AccessRule>,
+}
+
+impl AccessControl {
+    pub fn for_user(user: &User, c: &Connection) -> DieselResult<AccessControl> {
+        let roles = user.roles(c)?;
+
+        Ok(AccessControl {
+            user_id: user.id,
+            role_ids: roles.iter().map(|r| r.id).collect(),
+            team_ids: user.teams(c)?.iter().map(|t| t.id).collect(),
+            superuser: user.can(Capability::AlterMachines, c)?,
+            rules: access_rules::table.load(c)?,
+        })
+    }
+
+    pub fn access(&self, m: &Machine) -> Access {
+        if self.superuser {
+            return Access::full();
+        }
+
+        let applicable = self
+            .rules
+            .iter()
+            .filter(|r| r.covers(m))
+            .collect::<Vec<_>>();
+
+        if applicable.is_empty() {
+            return Access::open();
+        }
+
+        applicable
+            .into_iter()
+            .filter(|r| self.applies_to_me(r))
+            .fold(Access::default(), |a, r| a.union(r.access()))
+    }
+
+    pub fn can_view(&self, m: &Machine) -> bool {
+        self.access(m).view
+    }
+
+    pub fn can_reserve(&self, m: &Machine) -> bool {
+        self.access(m).reserve
+    }
+
+    pub fn can_administer(&self, m: &Machine) -> bool {
+        self.access(m).administer
+    }
+
+    /// Can this user change the access rules of a pool?
+    pub fn can_administer_pool(&self, p: &Pool) -> bool {
+        self.superuser
+            || self
+                .rules
+                .iter()
+                .any(|r| r.pool_id == Some(p.id) && r.can_administer && self.applies_to_me(r))
+    }
+
+    fn applies_to_me(&self, r: &AccessRule) -> bool {
+        r.user_id == Some(self.user_id)
+            || r.role_id.map(|id| self.role_ids.contains(&id)).unwrap_or(false)
+            || r.team_id.map(|id| self.team_ids.contains(&id)).unwrap_or(false)
+    }
+}
+
+///
+/// An association between a machine and the processor installed in some number of its sockets.
+///
+/// A dual-socket machine with identical processors has one of these with `sockets == 2`;
+/// a heterogeneous (e.g., big.LITTLE) machine has one for each kind of processor.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Machine)]
+#[belongs_to(Processor)]
+pub struct MachineProcessor {
+    pub id: i32,
+    pub machine_id: i32,
+    pub processor_id: i32,
+    pub sockets: i32,
+}
+
+/// A free-form label attached to a machine (e.g., "infiniband" or "quiet").
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Machine)]
+pub struct MachineTag {
+    pub id: i32,
+    pub machine_id: i32,
+    pub tag: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "machine_processors"]
+struct MachineProcessorInserter {
+    machine_id: i32,
+    processor_id: i32,
+    sockets: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "machines"]
+struct MachineInserter {
+    name: String,
+    memory_gb: Option<i32>,
+    requires_approval: bool,
+}
+
+pub struct MachineBuilder {
+    name: String,
+    memory_gb: Option<i32>,
+    processors: Vec<(i32, i32)>,
+    requires_approval: bool,
+}
+
+impl MachineBuilder {
+    pub fn new(name: String) -> MachineBuilder {
+        MachineBuilder {
+            name: name,
+            memory_gb: None,
+            processors: Vec::new(),
+            requires_approval: false,
+        }
+    }
+
+    pub fn insert(self, conn: &Connection) -> DieselResult<Machine> {
+        conn.transaction(|| {
+            let machine: Machine = insert_into(machines::table)
+                .values(&MachineInserter {
+                    name: self.name,
+                    memory_gb: self.memory_gb,
+                    requires_approval: self.requires_approval,
+                })
+                .get_result(conn)?;
+
+            let sockets = self
+                .processors
+                .iter()
+                .map(|&(processor_id, sockets)| MachineProcessorInserter {
+                    machine_id: machine.id,
+                    processor_id: processor_id,
+                    sockets: sockets,
+                })
+                .collect::<Vec<_>>();
+
+            insert_into(machine_processors::table)
+                .values(&sockets)
+                .execute(conn)?;
+
+            Ok(machine)
+        })
+    }
+
+    pub fn memory_gb(mut self, mem: i32) -> MachineBuilder {
+        self.memory_gb = Some(mem);
+        self
+    }
+
+    pub fn requires_approval(mut self, required: bool) -> MachineBuilder {
+        self.requires_approval = required;
+        self
+    }
+
+    /// Install a processor in `sockets` of this machine's sockets.
+    pub fn processor(mut self, p: &Processor, sockets: i32) -> MachineBuilder {
+        self.processors.push((p.id, sockets));
+        self
+    }
+}
+
+///
+/// One kind of processor installed in a machine, together with its architectural details and
+/// the number of sockets it occupies.
+///
+pub struct Socket {
+    pub count: i32,
+    pub processor: Processor,
+    pub microarch: Microarchitecture,
+    pub arch: Architecture,
+}
+
+impl Socket {
+    /// Total number of physical cores across all of the sockets with this processor.
+    pub fn cores(&self) -> i32 {
+        self.count * self.processor.cores
+    }
+
+    /// Total number of hardware threads across all of the sockets with this processor.
+    pub fn threads(&self) -> i32 {
+        self.count * self.processor.threads
+    }
+}
+
+///
+/// A FullMachine is a complete representation of a machine and all of its architectural details.
+///
+pub struct FullMachine {
+    machine: Machine,
+    sockets: Vec<Socket>,
+}
+
+type SocketJoin = (MachineProcessor, (Processor, (Microarchitecture, Architecture)));
+
+impl FullMachine {
+    ///
+    /// Look up the processors installed in a set of machines and combine them into FullMachine
+    /// objects (preserving the order of `machines`).
+    ///
+    fn load(machines: Vec<Machine>, c: &Connection) -> DieselResult<Vec<FullMachine>> {
+        use self::machine_processors::dsl::*;
+
+        let ids = machines.iter().map(|m| m.id).collect::<Vec<_>>();
+        let joined: Vec<SocketJoin> = machine_processors
+            .filter(machine_id.eq_any(ids))
+            .inner_join(
+                processors::table
+                    .inner_join(microarchitectures::table.inner_join(architectures::table)),
+            )
+            .order(id)
+            .load(c)?;
+
+        let mut sockets_by_machine = HashMap::new();
+        for (mp, (processor, (microarch, arch))) in joined {
+            sockets_by_machine
+                .entry(mp.machine_id)
+                .or_insert_with(Vec::new)
+                .push(Socket {
+                    count: mp.sockets,
+                    processor: processor,
+                    microarch: microarch,
+                    arch: arch,
+                });
+        }
+
+        Ok(machines
+            .into_iter()
+            .map(|m| {
+                let machine_sockets = sockets_by_machine.remove(&m.id).unwrap_or(Vec::new());
+                FullMachine {
+                    machine: m,
+                    sockets: machine_sockets,
+                }
+            })
+            .collect())
+    }
+
+    pub fn all(c: &Connection) -> DieselResult<Vec<FullMachine>> {
+        Machine::all(c).and_then(|machines| FullMachine::load(machines, c))
+    }
+
+    /// Find machines matching a search string (see `Machine::search`).
+    pub fn search(query: &str, c: &Connection) -> DieselResult<Vec<FullMachine>> {
+        Machine::search(query, c).and_then(|machines| FullMachine::load(machines, c))
+    }
+
+    pub fn with_name(machine_name: &str, c: &Connection) -> DieselResult<FullMachine> {
+        Machine::with_name(machine_name, c)
+            .and_then(|m| FullMachine::load(vec![m], c))
+            .map(|mut v| v.remove(0))
+    }
+
+    /// Total number of physical cores in all sockets.
+    pub fn cores(&self) -> i32 {
+        self.sockets.iter().map(Socket::cores).sum()
+    }
+
+    /// The clock speed of the fastest processor in the machine.
+    pub fn freq_ghz(&self) -> f64 {
+        self.sockets
+            .iter()
+            .map(|s| s.processor.freq_ghz)
+            .fold(0.0, f64::max)
+    }
+
+    pub fn into_machine(self) -> Machine {
+        self.machine
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn memory_gb(&self) -> i32 {
+        self.machine.memory_gb
+    }
+
+    pub fn name(&self) -> &str {
+        &self.machine.name
+    }
+
+    pub fn sockets(&self) -> &[Socket] {
+        &self.sockets
+    }
+
+    /// Total number of hardware threads in all sockets.
+    pub fn threads(&self) -> i32 {
+        self.sockets.iter().map(Socket::threads).sum()
+    }
+}
+
+///
+/// Constraints on the kind of machine a user wants to reserve, for when they don't care which
+/// particular machine they get.
+///
+pub struct MachineSpec {
+    pub min_memory_gb: Option<i32>,
+    pub min_cores: Option<i32>,
+    pub arch: Option<Architecture>,
+    pub min_microarch: Option<Microarchitecture>,
+    pub tags: Vec<String>,
+    pub ssd: Option<bool>,
+}
+
+impl MachineSpec {
+    /// A short, human-readable description of this specification.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(mem) = self.min_memory_gb {
+            parts.push(format!["≥ {} GiB", mem]);
+        }
+        if let Some(cores) = self.min_cores {
+            parts.push(format!["≥ {} cores", cores]);
+        }
+        if let Some(ref arch) = self.arch {
+            parts.push(arch.name.clone());
+        }
+        if let Some(ref min) = self.min_microarch {
+            parts.push(format!["{} or newer", min.name]);
+        }
+        for t in &self.tags {
+            parts.push(format!["tagged '{}'", t]);
+        }
+        match self.ssd {
+            Some(true) => parts.push(String::from("SSD")),
+            Some(false) => parts.push(String::from("non-SSD disk")),
+            None => {}
+        }
+
+        if parts.is_empty() {
+            String::from("any machine")
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    ///
+    /// Explain all of the ways in which a machine fails to satisfy this specification
+    /// (without considering whether or not it is available).
+    ///
+    pub fn rejections(&self, m: &FullMachine, c: &Connection) -> DieselResult<Vec<String>> {
+        let mut reasons = Vec::new();
+
+        if let Some(mem) = self.min_memory_gb {
+            if m.memory_gb() < mem {
+                reasons.push(format!["has {} GiB of memory (need {})", m.memory_gb(), mem]);
+            }
+        }
+
+        if let Some(cores) = self.min_cores {
+            if m.cores() < cores {
+                reasons.push(format!["has {} cores (need {})", m.cores(), cores]);
+            }
+        }
+
+        if let Some(ref arch) = self.arch {
+            for s in m.sockets().iter().filter(|s| s.arch.id != arch.id) {
+                reasons.push(format!["{} is {}, not {}", s.processor.name, s.arch.name, arch.name]);
+            }
+        }
+
+        if let Some(ref min) = self.min_microarch {
+            for s in m.sockets() {
+                if s.microarch.arch_id != min.arch_id || s.microarch.generation < min.generation {
+                    reasons.push(format![
+                        "{} is {}, not {} or newer",
+                        s.processor.name, s.microarch.name, min.name
+                    ]);
+                }
+            }
+        }
+
+        if !self.tags.is_empty() {
+            let tags = m.machine().tags(c)?;
+            for t in self.tags.iter().filter(|t| !tags.contains(t)) {
+                reasons.push(format!["not tagged '{}'", t]);
+            }
+        }
+
+        if let Some(ssd) = self.ssd {
+            if !m.machine().disks(c)?.iter().any(|d| d.ssd == ssd) {
+                reasons.push(String::from(if ssd { "has no SSD" } else { "has no non-SSD disk" }));
+            }
+        }
+
+        Ok(reasons)
+    }
+
+    ///
+    /// Evaluate every machine against this specification and its availability over a time
+    /// window. Machines that satisfy the spec and are free have no rejection reasons.
+    ///
+    /// Results are ordered from least to most capable (by memory, then cores), so the first
+    /// acceptable machine is the one that leaves the most for everyone else.
+    ///
+    pub fn evaluate(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        c: &Connection,
+    ) -> DieselResult<Vec<(FullMachine, Vec<String>)>> {
+        let mut results = Vec::new();
+
+        for m in FullMachine::all(c)? {
+            let mut reasons = self.rejections(&m, c)?;
+
+            let conflicts = Reservation::overlapping(m.machine(), start, end, c)?;
+            if !conflicts.is_empty() {
+                reasons.push(format![
+                    "already reserved (reservation {})",
+                    conflicts.iter().map(|r| r.id.to_string()).join(", ")
+                ]);
+            }
+
+            results.push((m, reasons));
+        }
+
+        results.sort_by_key(|&(ref m, _)| (m.memory_gb(), m.cores()));
+        Ok(results)
+    }
+}
+
+///
+/// An accelerator or add-in card (FPGA, GPU, SmartNIC, crypto card...) installed in a machine.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Machine)]
+pub struct Device {
+    pub id: i32,
+    pub machine_id: i32,
+    pub kind: String,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub pci_address: Option<String>,
+    pub attributes: Option<String>,
+}
+
+impl Device {
+    pub fn short_description(&self) -> String {
+        let v = self
+            .vendor
+            .as_ref()
+            .map(|vendor| format!["{} ", vendor])
+            .unwrap_or(String::new());
+
+        let m = self
+            .model
+            .as_ref()
+            .map(|model| format!["{} ", model])
+            .unwrap_or(String::new());
+
+        let pci = self
+            .pci_address
+            .as_ref()
+            .map(|addr| format![" at {}", addr])
+            .unwrap_or(String::new());
+
+        let attrs = self
+            .attributes
+            .as_ref()
+            .map(|a| format![" ({})", a])
+            .unwrap_or(String::new());
+
+        format!["{}{}{}{}{}", v, m, self.kind, pci, attrs]
+    }
+}
+
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Machine)]
+pub struct Disk {
+    pub id: i32,
+    pub machine_id: i32,
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub capacity_gb: i32,
+    pub ssd: bool,
+}
+
+impl Disk {
+    pub fn short_description(&self) -> String {
+        let v = self
+            .vendor
+            .as_ref()
+            .map(|vendor| format!["{} ", vendor])
+            .unwrap_or(String::new());
+
+        let ssd = if self.ssd { "SSD" } else { "non-SSD" };
+
+        format!["{}{} GiB ({})", v, self.capacity_gb, ssd]
+    }
+
+    pub fn vendor_name(&self) -> &str {
         self.vendor
             .as_ref()
             .map(|s| s.as_str())
@@ -582,12 +2951,71 @@ pub struct Reservation {
     pub actual_end: Option<DateTime<Utc>>,
     pub pxe_path: Option<String>,
     pub nfs_root: Option<String>,
+    pub group_id: Option<i32>,
+    pub updated_at: DateTime<Utc>,
+    pub series_id: Option<i32>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub cancelled_by: Option<i32>,
+    pub cancel_reason: Option<String>,
+
+    /// Awaiting approval: the slot is held, but the machine mustn't be booted for this user.
+    pub pending: bool,
+
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_comment: Option<String>,
+
+    /// What the reservation is for (empty for reservations made before this was recorded).
+    pub purpose: String,
+
+    /// Free-form notes, in Markdown.
+    pub notes: Option<String>,
+
+    /// Project or grant code to attribute the machine time to.
+    pub project: Option<String>,
+
+    /// A team whose members share ownership of this reservation.
+    pub team_id: Option<i32>,
+
+    /// The secret in this reservation's `authorized_keys` URL (not included in audit records).
+    pub keys_token: String,
+}
+
+impl ToJson for Reservation {
+    fn to_json(&self) -> Json {
+        let time = |t: &Option<DateTime<Utc>>| t.map(|t| t.to_rfc3339()).to_json();
+
+        json_object(vec![
+            ("id", self.id.to_json()),
+            ("user_id", self.user_id.to_json()),
+            ("machine_id", self.machine_id.to_json()),
+            ("scheduled_start", self.scheduled_start.to_rfc3339().to_json()),
+            ("scheduled_end", time(&self.scheduled_end)),
+            ("actual_end", time(&self.actual_end)),
+            ("pxe_path", self.pxe_path.to_json()),
+            ("nfs_root", self.nfs_root.to_json()),
+            ("group_id", self.group_id.to_json()),
+            ("series_id", self.series_id.to_json()),
+            ("cancelled_at", time(&self.cancelled_at)),
+            ("cancelled_by", self.cancelled_by.to_json()),
+            ("cancel_reason", self.cancel_reason.to_json()),
+            ("pending", self.pending.to_json()),
+            ("reviewed_by", self.reviewed_by.to_json()),
+            ("review_comment", self.review_comment.to_json()),
+            ("purpose", self.purpose.to_json()),
+            ("notes", self.notes.to_json()),
+            ("project", self.project.to_json()),
+            ("team_id", self.team_id.to_json()),
+        ])
+    }
 }
 
 type FullReservation = (Reservation, Machine, User);
 
 impl Reservation {
     /// Find all reservations, ordered by end time.
+    ///
+    /// Cancelled reservations are only included when `only_current` is false.
     pub fn all(only_current: bool, c: &Connection) -> DieselResult<Vec<FullReservation>> {
         use self::reservations::dsl::*;
 
@@ -598,12 +3026,36 @@ impl Reservation {
             .order(machine_id);
 
         if only_current {
-            query.filter(actual_end.is_null()).load(c)
+            query
+                .filter(actual_end.is_null())
+                .filter(cancelled_at.is_null())
+                .load(c)
         } else {
             query.load(c)
         }
     }
 
+    ///
+    /// Find all (uncancelled) reservations that overlap with a time window, ordered by start
+    /// time.
+    ///
+    pub fn during(
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        c: &Connection,
+    ) -> DieselResult<Vec<FullReservation>> {
+        use self::reservations::dsl::*;
+        reservations
+            .inner_join(machines::table)
+            .inner_join(users::table)
+            .filter(scheduled_start.lt(end))
+            .filter(scheduled_end.is_null().or(scheduled_end.gt(start)))
+            .filter(actual_end.is_null().or(actual_end.gt(start)))
+            .filter(cancelled_at.is_null())
+            .order(scheduled_start)
+            .load(c)
+    }
+
     ///
     /// Find all of a machine's reservations (and the User that reserved it in each case).
     ///
@@ -619,26 +3071,225 @@ impl Reservation {
     }
 
     ///
-    /// Find all of a user's machine reservations (and the details of those machines).
+    /// Find all of a user's machine reservations (and the details of those machines).
+    ///
+    pub fn for_user(user: &User, c: &Connection) -> DieselResult<Vec<(Reservation, Machine)>> {
+        use self::reservations::dsl::*;
+        reservations
+            .inner_join(machines::table)
+            .filter(user_id.eq(user.id()))
+            .order(actual_end.desc())
+            .order(scheduled_end.desc())
+            .load(c)
+    }
+
+    ///
+    /// Find a machine's reservations that overlap with a time window (i.e., that would conflict
+    /// with a new reservation over that window).
+    ///
+    pub fn overlapping(
+        m: &Machine,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        c: &Connection,
+    ) -> DieselResult<Vec<Reservation>> {
+        use self::reservations::dsl::*;
+        reservations
+            .filter(machine_id.eq(m.id))
+            .filter(scheduled_start.lt(end))
+            .filter(scheduled_end.is_null().or(scheduled_end.gt(start)))
+            .filter(actual_end.is_null().or(actual_end.gt(start)))
+            .filter(cancelled_at.is_null())
+            .order(scheduled_start)
+            .load(c)
+    }
+
+    ///
+    /// Find the earliest windows (of at least `duration`) during which a machine is not reserved,
+    /// starting no earlier than `from`.
+    ///
+    /// Each window is described by its start time and, unless it is open-ended (i.e., it starts
+    /// after the last scheduled reservation), its end time.
+    ///
+    pub fn free_windows(
+        m: &Machine,
+        from: DateTime<Utc>,
+        duration: Duration,
+        limit: usize,
+        c: &Connection,
+    ) -> DieselResult<Vec<(DateTime<Utc>, Option<DateTime<Utc>>)>> {
+        use self::reservations::dsl::*;
+
+        let upcoming: Vec<Reservation> = reservations
+            .filter(machine_id.eq(m.id))
+            .filter(scheduled_end.is_null().or(scheduled_end.gt(from)))
+            .filter(actual_end.is_null().or(actual_end.gt(from)))
+            .filter(cancelled_at.is_null())
+            .order(scheduled_start)
+            .load(c)?;
+
+        let mut windows = Vec::new();
+        let mut cursor = from;
+
+        for r in upcoming {
+            if windows.len() >= limit {
+                return Ok(windows);
+            }
+
+            if r.scheduled_start - cursor >= duration {
+                windows.push((cursor, Some(r.scheduled_start)));
+            }
+
+            match r.busy_until() {
+                Some(t) if t > cursor => cursor = t,
+                Some(_) => {}
+                None => return Ok(windows),
+            }
+        }
+
+        if windows.len() < limit {
+            windows.push((cursor, None));
+        }
+
+        Ok(windows)
+    }
+
+    pub fn get(res_id: i32, c: &Connection) -> DieselResult<FullReservation> {
+        use db::schema::reservations::dsl::*;
+
+        reservations
+            .inner_join(machines::table)
+            .inner_join(users::table)
+            .filter(id.eq(res_id))
+            .first(c)
+    }
+
+    /// Look up the reservation whose `authorized_keys` URL contains a secret token.
+    pub fn with_keys_token(secret: &str, c: &Connection) -> DieselResult<Reservation> {
+        use db::schema::reservations::dsl::*;
+        reservations.filter(keys_token.eq(secret)).first(c)
+    }
+
+    ///
+    /// Cancel a reservation that has not yet started.
+    ///
+    /// Unlike `end`, this doesn't pretend that the reservation ever took place: the row is kept
+    /// (recording who cancelled it, when and why) but it no longer occupies the machine.
+    ///
+    pub fn cancel(
+        self,
+        by: &User,
+        reason: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<Reservation> {
+        let now = Utc::now();
+        let cancelled = diesel::update(&self)
+            .set((
+                reservations::cancelled_at.eq(Some(now)),
+                reservations::cancelled_by.eq(Some(by.id)),
+                reservations::cancel_reason.eq(reason),
+            ))
+            .get_result::<Reservation>(c)?;
+
+        cancelled.hand_off(now.max(cancelled.scheduled_start), c)?;
+        Ok(cancelled)
+    }
+
+    ///
+    /// Offer the time that this reservation no longer needs (from `from` until its scheduled
+    /// end) to anyone waiting for the machine.
+    ///
+    fn hand_off(&self, from: DateTime<Utc>, c: &Connection) -> DieselResult<()> {
+        if let Some(end) = self.scheduled_end {
+            if end <= from {
+                return Ok(());
+            }
+        }
+
+        let machine = Machine::get(self.machine_id, c)?;
+        WaitlistEntry::offer_slot(&machine, from, self.scheduled_end, c).map(|_| ())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled_at.is_some()
+    }
+
+    ///
+    /// Find all uncancelled reservations that are awaiting approval, oldest first.
+    ///
+    pub fn pending_approval(c: &Connection) -> DieselResult<Vec<FullReservation>> {
+        use self::reservations::dsl::*;
+        reservations
+            .inner_join(machines::table)
+            .inner_join(users::table)
+            .filter(pending.eq(true))
+            .filter(cancelled_at.is_null())
+            .order(scheduled_start)
+            .load(c)
+    }
+
+    /// Approve a pending reservation.
+    pub fn approve(
+        self,
+        reviewer: &User,
+        comment: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<Reservation> {
+        diesel::update(&self)
+            .set((
+                reservations::pending.eq(false),
+                reservations::reviewed_by.eq(Some(reviewer.id)),
+                reservations::reviewed_at.eq(Some(Utc::now())),
+                reservations::review_comment.eq(comment),
+            ))
+            .get_result::<Reservation>(c)
+    }
+
+    ///
+    /// Reject a pending reservation, cancelling it (and releasing its slot) on the reviewer's
+    /// behalf.
+    ///
+    pub fn reject(
+        self,
+        reviewer: &User,
+        comment: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<Reservation> {
+        let reason = match comment {
+            Some(ref text) => format!["rejected by {}: {}", reviewer.username, text],
+            None => format!["rejected by {}", reviewer.username],
+        };
+
+        diesel::update(&self)
+            .set((
+                reservations::reviewed_by.eq(Some(reviewer.id)),
+                reservations::reviewed_at.eq(Some(Utc::now())),
+                reservations::review_comment.eq(comment),
+            ))
+            .get_result::<Reservation>(c)?
+            .cancel(reviewer, Some(reason), c)
+    }
+
+    ///
+    /// The boot configuration (PXE path and NFS root) to serve for this reservation, if any.
     ///
-    pub fn for_user(user: &User, c: &Connection) -> DieselResult<Vec<(Reservation, Machine)>> {
-        use self::reservations::dsl::*;
-        reservations
-            .inner_join(machines::table)
-            .filter(user_id.eq(user.id()))
-            .order(actual_end.desc())
-            .order(scheduled_end.desc())
-            .load(c)
+    /// Reservations that are pending approval, cancelled or over never have a boot
+    /// configuration, even if paths have been recorded for them.
+    ///
+    pub fn boot_config(&self) -> Option<(Option<&str>, Option<&str>)> {
+        if self.pending || self.is_closed() {
+            None
+        } else {
+            Some((
+                self.pxe_path.as_ref().map(String::as_str),
+                self.nfs_root.as_ref().map(String::as_str),
+            ))
+        }
     }
 
-    pub fn get(res_id: i32, c: &Connection) -> DieselResult<FullReservation> {
-        use db::schema::reservations::dsl::*;
-
-        reservations
-            .inner_join(machines::table)
-            .inner_join(users::table)
-            .filter(id.eq(res_id))
-            .first(c)
+    /// Is this reservation over (either ended or cancelled)?
+    pub fn is_closed(&self) -> bool {
+        self.actual_end.is_some() || self.cancelled_at.is_some()
     }
 
     ///
@@ -648,15 +3299,121 @@ impl Reservation {
     /// completion) is to mark it as completed right now.
     ///
     pub fn end(self, c: &Connection) -> DieselResult<Reservation> {
+        let now = Utc::now();
+        let ended = diesel::update(&self)
+            .set(reservations::actual_end.eq(Some(now)))
+            .get_result::<Reservation>(c)?;
+
+        ended.hand_off(now, c)?;
+        Ok(ended)
+    }
+
+    ///
+    /// Move this reservation's scheduled end time (e.g., to extend it).
+    ///
+    pub fn extend(self, new_end: DateTime<Utc>, c: &Connection) -> DieselResult<Reservation> {
         diesel::update(&self)
-            .set(reservations::actual_end.eq(Some(Utc::now())))
+            .set(reservations::scheduled_end.eq(Some(new_end)))
             .get_result::<Reservation>(c)
     }
 
+    ///
+    /// When will the reserved machine become free again?
+    ///
+    /// This is the actual end time if the reservation has ended, or else the scheduled end time
+    /// (`None` means the reservation is open-ended).
+    ///
+    pub fn busy_until(&self) -> Option<DateTime<Utc>> {
+        self.actual_end.or(self.scheduled_end)
+    }
+
     pub fn id(&self) -> i32 {
         self.id
     }
 
+    /// Change the boot configuration (PXE loader and NFS root) of this reservation.
+    pub fn set_boot_paths(
+        self,
+        pxe: Option<String>,
+        nfs: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<Reservation> {
+        diesel::update(&self)
+            .set((reservations::pxe_path.eq(pxe), reservations::nfs_root.eq(nfs)))
+            .get_result::<Reservation>(c)
+    }
+
+    ///
+    /// Give this reservation to another user, recording the previous owner.
+    ///
+    pub fn transfer(self, to: &User, by: &User, c: &Connection) -> DieselResult<Reservation> {
+        c.transaction(|| {
+            insert_into(reservation_transfers::table)
+                .values(&ReservationTransferInserter {
+                    reservation_id: self.id,
+                    from_user_id: self.user_id,
+                    to_user_id: to.id,
+                    transferred_by: by.id,
+                })
+                .execute(c)?;
+
+            diesel::update(&self)
+                .set(reservations::user_id.eq(to.id))
+                .get_result::<Reservation>(c)
+        })
+    }
+
+    /// Find all of the times that this reservation has changed hands, oldest first.
+    pub fn transfers(&self, c: &Connection) -> DieselResult<Vec<ReservationTransfer>> {
+        ReservationTransfer::belonging_to(self)
+            .order(reservation_transfers::transferred_at)
+            .load(c)
+    }
+
+    /// Change the description of this reservation (its purpose, notes and project code).
+    pub fn set_details(
+        self,
+        purpose: String,
+        notes: Option<String>,
+        project: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<Reservation> {
+        diesel::update(&self)
+            .set((
+                reservations::purpose.eq(purpose),
+                reservations::notes.eq(notes),
+                reservations::project.eq(project),
+            ))
+            .get_result::<Reservation>(c)
+    }
+
+    /// Share this reservation with a team (or stop sharing it, if `team` is `None`).
+    pub fn set_team(self, team: Option<&Team>, c: &Connection) -> DieselResult<Reservation> {
+        diesel::update(&self)
+            .set(reservations::team_id.eq(team.map(|t| t.id)))
+            .get_result::<Reservation>(c)
+    }
+
+    ///
+    /// Can a user manage (end, extend, cancel or reconfigure) this reservation?
+    ///
+    /// This is true for the reservation's owner, for members of the team it is shared with and
+    /// for users who can alter other users.
+    ///
+    pub fn may_manage(&self, user: &User, c: &Connection) -> DieselResult<bool> {
+        if self.user_id == user.id {
+            return Ok(true);
+        }
+
+        if let Some(t) = self.team_id {
+            if Team::get(t, c)?.has_member(user, c)? {
+                return Ok(true);
+            }
+        }
+
+        user.can(Capability::AlterUsers, c)
+    }
+
     pub fn start(&self) -> DateTime<Utc> {
         self.scheduled_start
     }
@@ -680,6 +3437,14 @@ pub struct ReservationBuilder {
     actual_end: Option<DateTime<Utc>>,
     pxe_path: Option<String>,
     nfs_root: Option<String>,
+    group_id: Option<i32>,
+    series_id: Option<i32>,
+    pending: bool,
+    purpose: String,
+    notes: Option<String>,
+    project: Option<String>,
+    team_id: Option<i32>,
+    keys_token: String,
 }
 
 impl ReservationBuilder {
@@ -692,14 +3457,52 @@ impl ReservationBuilder {
             actual_end: None,
             pxe_path: None,
             nfs_root: None,
+            group_id: None,
+            series_id: None,
+            pending: machine.requires_approval,
+            purpose: String::new(),
+            notes: None,
+            project: None,
+            team_id: None,
+            keys_token: random_token(32),
         }
     }
 
+    pub fn group(&mut self, g: &ReservationGroup) -> &mut ReservationBuilder {
+        self.group_id = Some(g.id);
+        self
+    }
+
     pub fn end(&mut self, time: DateTime<Utc>) -> &mut ReservationBuilder {
         self.scheduled_end = Some(time);
         self
     }
 
+    pub fn series(&mut self, s: &ReservationSeries) -> &mut ReservationBuilder {
+        self.series_id = Some(s.id);
+        self
+    }
+
+    pub fn purpose(&mut self, purpose: String) -> &mut ReservationBuilder {
+        self.purpose = purpose;
+        self
+    }
+
+    pub fn notes(&mut self, notes: String) -> &mut ReservationBuilder {
+        self.notes = Some(notes);
+        self
+    }
+
+    pub fn project(&mut self, project: String) -> &mut ReservationBuilder {
+        self.project = Some(project);
+        self
+    }
+
+    pub fn team(&mut self, t: &Team) -> &mut ReservationBuilder {
+        self.team_id = Some(t.id);
+        self
+    }
+
     pub fn pxe(&mut self, path: String) -> &mut ReservationBuilder {
         self.pxe_path = Some(path);
         self
@@ -716,3 +3519,555 @@ impl ReservationBuilder {
             .get_result(conn)
     }
 }
+
+///
+/// A record of a reservation changing hands.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Reservation)]
+pub struct ReservationTransfer {
+    pub id: i32,
+    pub reservation_id: i32,
+    pub from_user_id: i32,
+    pub to_user_id: i32,
+    pub transferred_by: i32,
+    pub transferred_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "reservation_transfers"]
+struct ReservationTransferInserter {
+    reservation_id: i32,
+    from_user_id: i32,
+    to_user_id: i32,
+    transferred_by: i32,
+}
+
+///
+/// A set of reservations that were made together (e.g., several identical machines for a
+/// distributed-systems experiment) and that can be extended or ended as a unit.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(User)]
+pub struct ReservationGroup {
+    pub id: i32,
+    pub user_id: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "reservation_groups"]
+struct ReservationGroupInserter {
+    user_id: i32,
+}
+
+impl ReservationGroup {
+    pub fn get(group_id: i32, c: &Connection) -> DieselResult<(ReservationGroup, User)> {
+        use self::reservation_groups::dsl::*;
+        reservation_groups
+            .inner_join(users::table)
+            .filter(id.eq(group_id))
+            .first(c)
+    }
+
+    pub fn insert(user: &User, c: &Connection) -> DieselResult<ReservationGroup> {
+        insert_into(reservation_groups::table)
+            .values(&ReservationGroupInserter { user_id: user.id })
+            .get_result(c)
+    }
+
+    /// Find all of the reservations in this group (and the machines they reserve).
+    pub fn members(&self, c: &Connection) -> DieselResult<Vec<(Reservation, Machine)>> {
+        use self::reservations::dsl::*;
+        reservations
+            .inner_join(machines::table)
+            .filter(group_id.eq(self.id))
+            .order(machines::name)
+            .load(c)
+    }
+}
+
+///
+/// A recurring reservation: a recurrence rule whose occurrences are materialised as individual
+/// reservations (which can then be edited or cancelled one at a time or together).
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(Machine)]
+#[belongs_to(User)]
+#[table_name = "reservation_series"]
+pub struct ReservationSeries {
+    pub id: i32,
+    pub user_id: i32,
+    pub machine_id: i32,
+    pub rule: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "reservation_series"]
+struct ReservationSeriesInserter {
+    user_id: i32,
+    machine_id: i32,
+    rule: String,
+}
+
+impl ReservationSeries {
+    pub fn get(series_id: i32, c: &Connection) -> DieselResult<(ReservationSeries, Machine, User)> {
+        use self::reservation_series::dsl::*;
+        reservation_series
+            .inner_join(machines::table)
+            .inner_join(users::table)
+            .filter(id.eq(series_id))
+            .first(c)
+    }
+
+    pub fn insert(
+        user: &User,
+        machine: &Machine,
+        recurrence: &Recurrence,
+        c: &Connection,
+    ) -> DieselResult<ReservationSeries> {
+        insert_into(reservation_series::table)
+            .values(&ReservationSeriesInserter {
+                user_id: user.id,
+                machine_id: machine.id,
+                rule: recurrence.to_string(),
+            })
+            .get_result(c)
+    }
+
+    /// Find all of this series' occurrences, in chronological order.
+    pub fn members(&self, c: &Connection) -> DieselResult<Vec<Reservation>> {
+        use self::reservations::dsl::*;
+        reservations
+            .filter(series_id.eq(self.id))
+            .order(scheduled_start)
+            .load(c)
+    }
+
+    pub fn recurrence(&self) -> Result<Recurrence, Error> {
+        Recurrence::parse(&self.rule)
+    }
+}
+
+///
+/// A message for a user about something that happened without them (e.g., a waitlisted slot
+/// becoming free).
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(User)]
+pub struct Notification {
+    pub id: i32,
+    pub user_id: i32,
+    pub message: String,
+
+    /// Where to go to act on this notification, relative to the application's route prefix.
+    pub link: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "notifications"]
+struct NotificationInserter {
+    user_id: i32,
+    message: String,
+    link: Option<String>,
+}
+
+impl Notification {
+    pub fn get(notification_id: i32, c: &Connection) -> DieselResult<Notification> {
+        use self::notifications::dsl::*;
+        notifications.find(notification_id).first(c)
+    }
+
+    pub fn insert(
+        user_id: i32,
+        message: String,
+        link: Option<String>,
+        c: &Connection,
+    ) -> DieselResult<Notification> {
+        insert_into(notifications::table)
+            .values(&NotificationInserter {
+                user_id: user_id,
+                message: message,
+                link: link,
+            })
+            .get_result(c)
+    }
+
+    /// Find a user's unread notifications, newest first.
+    pub fn unread(user: &User, c: &Connection) -> DieselResult<Vec<Notification>> {
+        use self::notifications::dsl::*;
+        notifications
+            .filter(user_id.eq(user.id))
+            .filter(read_at.is_null())
+            .order(created_at.desc())
+            .load(c)
+    }
+
+    pub fn mark_read(self, c: &Connection) -> DieselResult<Notification> {
+        diesel::update(&self)
+            .set(notifications::read_at.eq(Some(Utc::now())))
+            .get_result(c)
+    }
+}
+
+/// How long a waiting user has to claim a slot that has been offered to them.
+pub const WAITLIST_OFFER_MINUTES: i64 = 60;
+
+///
+/// A user's request to be offered a machine (or any machine matching a specification) over a
+/// time window if a conflicting reservation is cancelled or ended early.
+///
+#[derive(Associations, Debug, Identifiable, Queryable)]
+#[belongs_to(User)]
+#[table_name = "waitlist_entries"]
+pub struct WaitlistEntry {
+    pub id: i32,
+    pub user_id: i32,
+    pub machine_id: Option<i32>,
+    pub min_memory_gb: Option<i32>,
+    pub min_cores: Option<i32>,
+    pub arch_id: Option<i32>,
+    pub min_microarch_id: Option<i32>,
+    pub tags: String,
+    pub ssd: Option<bool>,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub offered_machine_id: Option<i32>,
+    pub offer_expires: Option<DateTime<Utc>>,
+    pub reservation_id: Option<i32>,
+    pub closed_at: Option<DateTime<Utc>>,
+
+    /// The purpose of the reservation that will be made if this entry's offer is accepted.
+    pub purpose: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "waitlist_entries"]
+struct WaitlistInserter {
+    user_id: i32,
+    machine_id: Option<i32>,
+    min_memory_gb: Option<i32>,
+    min_cores: Option<i32>,
+    arch_id: Option<i32>,
+    min_microarch_id: Option<i32>,
+    tags: String,
+    ssd: Option<bool>,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    purpose: String,
+}
+
+/// Where a waitlist entry is up to.
+#[derive(Debug, PartialEq)]
+pub enum WaitlistState {
+    /// Still waiting for a slot to become free.
+    Waiting,
+
+    /// A machine has been offered and may be claimed until the given time.
+    Offered(i32, DateTime<Utc>),
+
+    /// The offer was accepted, creating the given reservation.
+    Accepted(i32),
+
+    /// The entry was withdrawn, declined or its offer lapsed.
+    Closed,
+}
+
+impl WaitlistEntry {
+    pub fn get(entry_id: i32, c: &Connection) -> DieselResult<WaitlistEntry> {
+        use self::waitlist_entries::dsl::*;
+        waitlist_entries.find(entry_id).first(c)
+    }
+
+    ///
+    /// Join the waitlist for a particular machine or, if `machine` is `None`, for any machine
+    /// that satisfies `spec`.
+    ///
+    pub fn insert(
+        user: &User,
+        machine: Option<&Machine>,
+        spec: &MachineSpec,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        purpose: String,
+        c: &Connection,
+    ) -> DieselResult<WaitlistEntry> {
+        insert_into(waitlist_entries::table)
+            .values(&WaitlistInserter {
+                user_id: user.id,
+                machine_id: machine.map(|m| m.id),
+                min_memory_gb: spec.min_memory_gb,
+                min_cores: spec.min_cores,
+                arch_id: spec.arch.as_ref().map(|a| a.id),
+                min_microarch_id: spec.min_microarch.as_ref().map(|u| u.id),
+                tags: spec.tags.join(","),
+                ssd: spec.ssd,
+                window_start: start,
+                window_end: end,
+                purpose: purpose,
+            })
+            .get_result(c)
+    }
+
+    /// Find all of a user's waitlist entries, newest first.
+    pub fn for_user(user: &User, c: &Connection) -> DieselResult<Vec<WaitlistEntry>> {
+        use self::waitlist_entries::dsl::*;
+        waitlist_entries
+            .filter(user_id.eq(user.id))
+            .order(created_at.desc())
+            .load(c)
+    }
+
+    /// The specification that any machine offered to this entry must satisfy.
+    pub fn spec(&self, c: &Connection) -> DieselResult<MachineSpec> {
+        Ok(MachineSpec {
+            min_memory_gb: self.min_memory_gb,
+            min_cores: self.min_cores,
+            arch: match self.arch_id {
+                Some(a) => Some(Architecture::get(a, c)?),
+                None => None,
+            },
+            min_microarch: match self.min_microarch_id {
+                Some(u) => Some(Microarchitecture::get(u, c)?),
+                None => None,
+            },
+            tags: self
+                .tags
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(str::to_string)
+                .collect(),
+            ssd: self.ssd,
+        })
+    }
+
+    pub fn state(&self) -> WaitlistState {
+        match (self.reservation_id, self.closed_at, self.offered_machine_id, self.offer_expires) {
+            (Some(r), _, _, _) => WaitlistState::Accepted(r),
+            (None, Some(_), _, _) => WaitlistState::Closed,
+            (None, None, Some(m), Some(expires)) if expires > Utc::now() => {
+                WaitlistState::Offered(m, expires)
+            }
+            (None, None, Some(_), Some(_)) => WaitlistState::Closed,
+            (None, None, _, _) if self.window_end <= Utc::now() => WaitlistState::Closed,
+            (None, None, _, _) => WaitlistState::Waiting,
+        }
+    }
+
+    ///
+    /// Claim the slot that was offered to this entry, converting it into a reservation.
+    ///
+    pub fn accept(self, c: &Connection) -> Result<Reservation, Error> {
+        WaitlistEntry::expire_offers(c)?;
+
+        let machine_id = match self.state() {
+            WaitlistState::Offered(m, _) => m,
+            _ => {
+                return Err(Error::BadRequest(format![
+                    "waitlist entry {} has no outstanding offer",
+                    self.id
+                ]));
+            }
+        };
+
+        c.transaction::<_, Error, _>(|| {
+            let machine = Machine::get(machine_id, c)?;
+            Machine::lock(&[&machine], c)?;
+
+            let conflicts =
+                Reservation::overlapping(&machine, self.window_start, self.window_end, c)?;
+            if let Some(r) = conflicts.first() {
+                return Err(Error::BadRequest(format![
+                    "{} has been reserved in the meantime (reservation {})",
+                    machine.name,
+                    r.id()
+                ]));
+            }
+
+            let user = User::get(self.user_id, c)?;
+            if !AccessControl::for_user(&user, c)?.can_reserve(&machine) {
+                return Err(Error::NotAuthorized(format!["reserve {}", machine.name]));
+            }
+
+            user.reservation_policy(c)?
+                .check(&user, &[(self.window_start, Some(self.window_end))], c)?;
+
+            let mut rb = ReservationBuilder::new(&user, &machine, self.window_start);
+            rb.end(self.window_end).purpose(self.purpose.clone());
+            let reservation = rb.insert(c)?;
+
+            diesel::update(&self)
+                .set((
+                    waitlist_entries::reservation_id.eq(Some(reservation.id)),
+                    waitlist_entries::closed_at.eq(Some(Utc::now())),
+                ))
+                .execute(c)?;
+
+            Ok(reservation)
+        })
+    }
+
+    ///
+    /// Leave the waitlist (or decline an offer), passing any offered slot on to the next user.
+    ///
+    pub fn close(self, c: &Connection) -> DieselResult<()> {
+        let offered = match self.state() {
+            WaitlistState::Offered(m, _) => Some(m),
+            _ => None,
+        };
+
+        diesel::update(&self)
+            .set(waitlist_entries::closed_at.eq(Some(Utc::now())))
+            .execute(c)?;
+
+        if let Some(m) = offered {
+            let machine = Machine::get(m, c)?;
+            WaitlistEntry::offer_slot(&machine, self.window_start, Some(self.window_end), c)?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Close all entries whose offers have lapsed, passing their slots on to the next user.
+    ///
+    /// Nothing runs in the background to do this, so it is done whenever offers are made or
+    /// accepted and whenever users look at their home or waitlist pages.
+    ///
+    pub fn expire_offers(c: &Connection) -> DieselResult<()> {
+        let lapsed: Vec<WaitlistEntry> = {
+            use self::waitlist_entries::dsl::*;
+            waitlist_entries
+                .filter(closed_at.is_null())
+                .filter(reservation_id.is_null())
+                .filter(offer_expires.le(Utc::now()))
+                .load(c)?
+        };
+
+        for entry in lapsed {
+            let machine = entry.offered_machine_id;
+            let (start, end) = (entry.window_start, entry.window_end);
+
+            diesel::update(&entry)
+                .set(waitlist_entries::closed_at.eq(entry.offer_expires))
+                .execute(c)?;
+
+            if let Some(m) = machine {
+                WaitlistEntry::offer_freed(&Machine::get(m, c)?, start, Some(end), c)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Offer a newly-freed period of time on a machine to the longest-waiting users whose
+    /// windows overlap it and are now entirely free (and who would accept, and may reserve,
+    /// that machine), after passing on any offers that have lapsed.
+    ///
+    /// Each offer is recorded against its waitlist entry, expires after
+    /// `WAITLIST_OFFER_MINUTES` and is announced to the user via a `Notification`.
+    ///
+    pub fn offer_slot(
+        machine: &Machine,
+        from: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+        c: &Connection,
+    ) -> DieselResult<Vec<WaitlistEntry>> {
+        WaitlistEntry::expire_offers(c)?;
+        WaitlistEntry::offer_freed(machine, from, until, c)
+    }
+
+    /// Make the offers described by `offer_slot`, without first expiring lapsed offers.
+    fn offer_freed(
+        machine: &Machine,
+        from: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+        c: &Connection,
+    ) -> DieselResult<Vec<WaitlistEntry>> {
+        let now = Utc::now();
+
+        let candidates: Vec<WaitlistEntry> = {
+            use self::waitlist_entries::dsl::*;
+            let query = waitlist_entries
+                .filter(closed_at.is_null())
+                .filter(offer_expires.is_null())
+                .filter(machine_id.eq(machine.id).or(machine_id.is_null()))
+                .filter(window_end.gt(from.max(now)))
+                .order(created_at);
+
+            match until {
+                Some(t) => query.filter(window_start.lt(t)).load(c)?,
+                None => query.load(c)?,
+            }
+        };
+
+        // Slots that are still on offer to someone else can't be offered again.
+        let outstanding: Vec<WaitlistEntry> = {
+            use self::waitlist_entries::dsl::*;
+            waitlist_entries
+                .filter(closed_at.is_null())
+                .filter(reservation_id.is_null())
+                .filter(offered_machine_id.eq(machine.id))
+                .filter(offer_expires.gt(now))
+                .load(c)?
+        };
+
+        let full_machine = FullMachine::load(vec![machine.clone()], c)?.remove(0);
+        let mut offered: Vec<WaitlistEntry> = Vec::new();
+
+        for entry in candidates {
+            if entry.machine_id.is_none()
+                && !entry.spec(c)?.rejections(&full_machine, c)?.is_empty()
+            {
+                continue;
+            }
+
+            // Access rules may have changed since the user joined the waitlist.
+            let user = User::get(entry.user_id, c)?;
+            if !AccessControl::for_user(&user, c)?.can_reserve(machine) {
+                continue;
+            }
+
+            let clashes = |e: &WaitlistEntry| {
+                e.window_start < entry.window_end && entry.window_start < e.window_end
+            };
+            if outstanding.iter().chain(&offered).any(clashes) {
+                continue;
+            }
+
+            if !Reservation::overlapping(machine, entry.window_start, entry.window_end, c)?
+                .is_empty()
+            {
+                continue;
+            }
+
+            let expires = now + Duration::minutes(WAITLIST_OFFER_MINUTES);
+            let entry = diesel::update(&entry)
+                .set((
+                    waitlist_entries::offered_machine_id.eq(Some(machine.id)),
+                    waitlist_entries::offer_expires.eq(Some(expires)),
+                ))
+                .get_result::<WaitlistEntry>(c)?;
+
+            Notification::insert(
+                entry.user_id,
+                format![
+                    "{} is free from {} to {}: claim it before {}",
+                    machine.name, entry.window_start, entry.window_end, expires
+                ],
+                Some(format!["waitlist#entry-{}", entry.id]),
+                c,
+            )?;
+
+            offered.push(entry);
+        }
+
+        Ok(offered)
+    }
+}
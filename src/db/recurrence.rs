@@ -0,0 +1,360 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Weekday};
+use error::Error;
+use std::fmt;
+
+/// The most occurrences that a single recurrence rule may generate.
+pub const MAX_OCCURRENCES: usize = 366;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+}
+
+///
+/// A (subset of an iCalendar RFC 5545) recurrence rule, e.g., "every weekday until 30 June".
+///
+/// Rules are stored in RRULE syntax, e.g., `FREQ=WEEKLY;BYDAY=MO,WE,FR;COUNT=12`.
+/// Every rule must be bounded by a count or an end date.
+///
+#[derive(Clone, Debug)]
+pub struct Recurrence {
+    pub freq: Frequency,
+    pub by_day: Vec<Weekday>,
+    pub count: Option<usize>,
+    pub until: Option<NaiveDate>,
+}
+
+impl Recurrence {
+    pub fn parse(rule: &str) -> Result<Recurrence, Error> {
+        let mut freq = None;
+        let mut by_day = Vec::new();
+        let mut count = None;
+        let mut until = None;
+
+        for part in rule.split(';').filter(|p| !p.is_empty()) {
+            let mut kv = part.splitn(2, '=');
+            let key = kv.next().unwrap_or("");
+            let value = kv
+                .next()
+                .ok_or(Error::InvalidData(format!["malformed recurrence rule part '{}'", part]))?;
+
+            match key {
+                "FREQ" => {
+                    freq = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        _ => {
+                            return Err(Error::InvalidData(format![
+                                "unsupported recurrence frequency '{}'",
+                                value
+                            ]));
+                        }
+                    })
+                }
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        by_day.push(parse_weekday(day)?);
+                    }
+                }
+                "COUNT" => {
+                    count = Some(value.parse().map_err(|_| {
+                        Error::InvalidData(format!["invalid recurrence count '{}'", value])
+                    })?)
+                }
+                "UNTIL" => until = Some(NaiveDate::parse_from_str(value, "%Y%m%d")?),
+                _ => {
+                    return Err(Error::InvalidData(format![
+                        "unsupported recurrence rule part '{}'",
+                        key
+                    ]));
+                }
+            }
+        }
+
+        let recurrence = Recurrence {
+            freq: freq.ok_or(Error::InvalidData(String::from("recurrence rule has no FREQ")))?,
+            by_day: by_day,
+            count: count,
+            until: until,
+        };
+
+        recurrence.check()?;
+        Ok(recurrence)
+    }
+
+    /// Check that this rule is bounded (and not excessively long).
+    pub fn check(&self) -> Result<(), Error> {
+        match (self.count, self.until) {
+            (None, None) => Err(Error::BadRequest(String::from(
+                "a recurring reservation needs a number of occurrences or an end date",
+            ))),
+            (Some(n), _) if n == 0 || n > MAX_OCCURRENCES => Err(Error::BadRequest(format![
+                "a recurring reservation must have between 1 and {} occurrences",
+                MAX_OCCURRENCES
+            ])),
+            _ => Ok(()),
+        }
+    }
+
+    ///
+    /// Generate the start times of all occurrences of this rule, beginning with `first`.
+    ///
+    /// Days are counted in `first`'s time zone, so that (e.g.) a 23:00 reservation every Monday
+    /// happens on the user's Monday rather than UTC's. Each occurrence has the same local time
+    /// as `first`, so in a zone with daylight saving time (e.g., a `chrono_tz::Tz`) a nightly
+    /// 01:00 reservation stays at 01:00 after the clocks change. A time that the clocks skip
+    /// happens an hour later, and a time that they repeat happens the first time around.
+    ///
+    /// Rules that generate no occurrences (e.g., ending before they start) or more than
+    /// `MAX_OCCURRENCES` of them are rejected.
+    ///
+    pub fn occurrences<Tz: TimeZone>(
+        &self,
+        first: DateTime<Tz>,
+    ) -> Result<Vec<DateTime<Tz>>, Error> {
+        let zone = first.timezone();
+        let mut starts = Vec::new();
+        let mut day = first.naive_local();
+
+        // Generate one more than the limit so that we can tell when a rule exceeds it.
+        while starts.len() < self.count.unwrap_or(MAX_OCCURRENCES + 1) {
+            if let Some(until) = self.until {
+                if day.date() > until {
+                    break;
+                }
+            }
+
+            let included = if self.by_day.is_empty() {
+                self.freq == Frequency::Daily || day.weekday() == first.weekday()
+            } else {
+                self.by_day.contains(&day.weekday())
+            };
+
+            if included {
+                // A local time that is skipped by a daylight saving change happens an hour later.
+                let start = zone
+                    .from_local_datetime(&day)
+                    .earliest()
+                    .or_else(|| zone.from_local_datetime(&(day + Duration::hours(1))).earliest());
+
+                if let Some(start) = start {
+                    starts.push(start);
+                }
+            }
+
+            day = day + Duration::days(1);
+        }
+
+        if starts.is_empty() {
+            return Err(Error::BadRequest(String::from(
+                "the recurrence rule doesn't match any dates from the first reservation onward",
+            )));
+        }
+
+        if starts.len() > MAX_OCCURRENCES {
+            return Err(Error::BadRequest(format![
+                "a recurring reservation must have between 1 and {} occurrences",
+                MAX_OCCURRENCES
+            ]));
+        }
+
+        Ok(starts)
+    }
+}
+
+impl fmt::Display for Recurrence {
+    /// Format this rule in RRULE syntax.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write![
+            f,
+            "FREQ={}",
+            match self.freq {
+                Frequency::Daily => "DAILY",
+                Frequency::Weekly => "WEEKLY",
+            }
+        ]?;
+
+        if !self.by_day.is_empty() {
+            let days = self.by_day.iter().map(|d| weekday_code(*d)).collect::<Vec<_>>();
+            write![f, ";BYDAY={}", days.join(",")]?;
+        }
+
+        if let Some(count) = self.count {
+            write![f, ";COUNT={}", count]?;
+        }
+
+        if let Some(until) = self.until {
+            write![f, ";UNTIL={}", until.format("%Y%m%d")]?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a two-letter iCalendar weekday code (e.g., "MO").
+pub fn parse_weekday(code: &str) -> Result<Weekday, Error> {
+    match code {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(Error::InvalidData(format!["invalid weekday '{}'", code])),
+    }
+}
+
+/// The two-letter iCalendar code for a weekday (e.g., "MO").
+pub fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, Timelike, Utc};
+    use chrono_tz::America::Toronto;
+
+    fn at(offset_hours: i32, day: u32, hour: u32) -> DateTime<FixedOffset> {
+        FixedOffset::east(offset_hours * 3600).ymd(2026, 10, day).and_hms(hour, 0, 0)
+    }
+
+    fn days<Tz: TimeZone>(starts: &[DateTime<Tz>]) -> Vec<(u32, u32)> {
+        starts.iter().map(|s| (s.month(), s.day())).collect()
+    }
+
+    #[test]
+    fn parse_round_trip() {
+        let rule = "FREQ=WEEKLY;BYDAY=MO,WE,FR;COUNT=12";
+        let r = Recurrence::parse(rule).unwrap();
+
+        assert_eq!(r.freq, Frequency::Weekly);
+        assert_eq!(r.by_day, vec![Weekday::Mon, Weekday::Wed, Weekday::Fri]);
+        assert_eq!(r.count, Some(12));
+        assert_eq!(r.until, None);
+        assert_eq!(r.to_string(), rule);
+    }
+
+    #[test]
+    fn parse_until() {
+        let r = Recurrence::parse("FREQ=DAILY;UNTIL=20261031").unwrap();
+
+        assert_eq!(r.freq, Frequency::Daily);
+        assert_eq!(r.until, Some(NaiveDate::from_ymd(2026, 10, 31)));
+        assert_eq!(r.to_string(), "FREQ=DAILY;UNTIL=20261031");
+    }
+
+    #[test]
+    fn parse_rejects_invalid_rules() {
+        for rule in &[
+            "",
+            "COUNT=3",
+            "FREQ=WEEKLY",
+            "FREQ=MONTHLY;COUNT=3",
+            "FREQ=WEEKLY;BYDAY=XX;COUNT=3",
+            "FREQ=DAILY;COUNT",
+            "FREQ=DAILY;COUNT=none",
+            "FREQ=DAILY;COUNT=0",
+            "FREQ=DAILY;COUNT=367",
+            "FREQ=DAILY;UNTIL=2026-10-31",
+            "FREQ=DAILY;INTERVAL=2;COUNT=3",
+        ] {
+            assert!(Recurrence::parse(rule).is_err(), "accepted '{}'", rule);
+        }
+    }
+
+    #[test]
+    fn weekly_by_day() {
+        let r = Recurrence::parse("FREQ=WEEKLY;BYDAY=MO,WE,FR;COUNT=4").unwrap();
+        let starts = r.occurrences(at(0, 19, 9)).unwrap();
+
+        assert_eq!(days(&starts), vec![(10, 19), (10, 21), (10, 23), (10, 26)]);
+        assert!(starts.iter().all(|s| s.hour() == 9));
+    }
+
+    #[test]
+    fn weekly_defaults_to_first_weekday() {
+        let r = Recurrence::parse("FREQ=WEEKLY;COUNT=3").unwrap();
+        let starts = r.occurrences(at(0, 21, 9)).unwrap();
+
+        assert_eq!(days(&starts), vec![(10, 21), (10, 28), (11, 4)]);
+    }
+
+    #[test]
+    fn daily_until_is_inclusive() {
+        let r = Recurrence::parse("FREQ=DAILY;UNTIL=20261023").unwrap();
+        let starts = r.occurrences(at(0, 19, 9)).unwrap();
+
+        assert_eq!(days(&starts), vec![(10, 19), (10, 20), (10, 21), (10, 22), (10, 23)]);
+    }
+
+    #[test]
+    fn days_are_local() {
+        // 23:00 on a Monday at UTC-5 is already Tuesday in UTC.
+        let r = Recurrence::parse("FREQ=WEEKLY;BYDAY=MO;COUNT=2").unwrap();
+        let starts = r.occurrences(at(-5, 19, 23)).unwrap();
+
+        assert_eq!(days(&starts), vec![(10, 19), (10, 26)]);
+        assert!(starts.iter().all(|s| s.hour() == 23));
+        assert!(starts.iter().all(|s| s.with_timezone(&Utc).weekday() == Weekday::Tue));
+    }
+
+    #[test]
+    fn rejects_empty_expansion() {
+        let r = Recurrence::parse("FREQ=WEEKLY;BYDAY=SA;UNTIL=20261023").unwrap();
+        assert!(r.occurrences(at(0, 19, 9)).is_err());
+    }
+
+    #[test]
+    fn rejects_too_many_occurrences() {
+        let r = Recurrence::parse("FREQ=DAILY;UNTIL=20281231").unwrap();
+        assert!(r.occurrences(at(0, 19, 9)).is_err());
+
+        let r = Recurrence::parse(&format!["FREQ=DAILY;COUNT={}", MAX_OCCURRENCES]).unwrap();
+        assert_eq!(r.occurrences(at(0, 19, 9)).unwrap().len(), MAX_OCCURRENCES);
+    }
+
+    #[test]
+    fn keeps_local_time_across_dst() {
+        // Toronto's clocks go back an hour at 02:00 on 1 November 2026.
+        let r = Recurrence::parse("FREQ=DAILY;COUNT=4").unwrap();
+        let first = Toronto.ymd(2026, 10, 30).and_hms(1, 0, 0);
+        let starts = r.occurrences(first).unwrap();
+
+        assert_eq!(days(&starts), vec![(10, 30), (10, 31), (11, 1), (11, 2)]);
+        assert!(starts.iter().all(|s| s.hour() == 1));
+
+        let utc = starts.iter().map(|s| s.with_timezone(&Utc).hour()).collect::<Vec<_>>();
+        assert_eq!(utc, vec![5, 5, 5, 6]);
+    }
+
+    #[test]
+    fn skipped_times_happen_later() {
+        // Toronto's clocks go forward an hour at 02:00 on 8 March 2026.
+        let r = Recurrence::parse("FREQ=DAILY;COUNT=3").unwrap();
+        let first = Toronto.ymd(2026, 3, 7).and_hms(2, 30, 0);
+        let starts = r.occurrences(first).unwrap();
+
+        let local = starts.iter().map(|s| (s.day(), s.hour())).collect::<Vec<_>>();
+        assert_eq!(local, vec![(7, 2), (8, 3), (9, 2)]);
+    }
+}
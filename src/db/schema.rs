@@ -4,6 +4,37 @@
  * BEGIN AUTO-GENERATED PART
  */
 
+table! {
+    access_rules (id) {
+        id -> Int4,
+        pool_id -> Nullable<Int4>,
+        machine_id -> Nullable<Int4>,
+        user_id -> Nullable<Int4>,
+        role_id -> Nullable<Int4>,
+        team_id -> Nullable<Int4>,
+        can_view -> Bool,
+        can_reserve -> Bool,
+        can_administer -> Bool,
+    }
+}
+
+table! {
+    account_requests (id) {
+        id -> Int4,
+        provider -> Text,
+        subject -> Text,
+        username -> Text,
+        name -> Text,
+        email -> Text,
+        reason -> Text,
+        created_at -> Timestamptz,
+        reviewed_by -> Nullable<Int4>,
+        reviewed_at -> Nullable<Timestamptz>,
+        approved -> Bool,
+        review_comment -> Nullable<Text>,
+    }
+}
+
 table! {
     architectures (id) {
         id -> Int4,
@@ -11,6 +42,42 @@ table! {
     }
 }
 
+table! {
+    audit_events (id) {
+        id -> Int4,
+        actor_id -> Nullable<Int4>,
+        actor -> Text,
+        action -> Text,
+        target_type -> Text,
+        target_id -> Int4,
+        before -> Nullable<Text>,
+        after -> Nullable<Text>,
+        ip_address -> Nullable<Text>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    calendar_tokens (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    devices (id) {
+        id -> Int4,
+        machine_id -> Int4,
+        kind -> Text,
+        vendor -> Nullable<Text>,
+        model -> Nullable<Text>,
+        pci_address -> Nullable<Text>,
+        attributes -> Nullable<Text>,
+    }
+}
+
 table! {
     disks (id) {
         id -> Int4,
@@ -31,10 +98,60 @@ table! {
 }
 
 table! {
-    github_accounts (id) {
+    identities (id) {
         id -> Int4,
         user_id -> Int4,
-        github_username -> Varchar,
+        provider -> Text,
+        subject -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    invitation_roles (id) {
+        id -> Int4,
+        invitation_id -> Int4,
+        role_id -> Int4,
+    }
+}
+
+table! {
+    invitations (id) {
+        id -> Int4,
+        token -> Text,
+        username -> Text,
+        name -> Text,
+        email -> Text,
+        created_by -> Int4,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        accepted_at -> Nullable<Timestamptz>,
+        user_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    ldap_group_roles (id) {
+        id -> Int4,
+        role_id -> Int4,
+        group_name -> Text,
+    }
+}
+
+table! {
+    machine_processors (id) {
+        id -> Int4,
+        machine_id -> Int4,
+        processor_id -> Int4,
+        sockets -> Int4,
+    }
+}
+
+table! {
+    machine_tags (id) {
+        id -> Int4,
+        machine_id -> Int4,
+        tag -> Text,
     }
 }
 
@@ -42,8 +159,9 @@ table! {
     machines (id) {
         id -> Int4,
         name -> Varchar,
-        processor_id -> Int4,
         memory_gb -> Int4,
+        requires_approval -> Bool,
+        pool_id -> Nullable<Int4>,
     }
 }
 
@@ -53,6 +171,7 @@ table! {
         arch_id -> Int4,
         name -> Varchar,
         url -> Nullable<Varchar>,
+        generation -> Int4,
     }
 }
 
@@ -67,6 +186,25 @@ table! {
     }
 }
 
+table! {
+    notifications (id) {
+        id -> Int4,
+        user_id -> Int4,
+        message -> Text,
+        link -> Nullable<Text>,
+        created_at -> Timestamptz,
+        read_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    pools (id) {
+        id -> Int4,
+        name -> Text,
+        description -> Text,
+    }
+}
+
 table! {
     processors (id) {
         id -> Int4,
@@ -79,6 +217,35 @@ table! {
     }
 }
 
+table! {
+    reservation_groups (id) {
+        id -> Int4,
+        user_id -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    reservation_series (id) {
+        id -> Int4,
+        user_id -> Int4,
+        machine_id -> Int4,
+        rule -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    reservation_transfers (id) {
+        id -> Int4,
+        reservation_id -> Int4,
+        from_user_id -> Int4,
+        to_user_id -> Int4,
+        transferred_by -> Int4,
+        transferred_at -> Timestamptz,
+    }
+}
+
 table! {
     reservations (id) {
         id -> Int4,
@@ -89,6 +256,21 @@ table! {
         actual_end -> Nullable<Timestamptz>,
         pxe_path -> Nullable<Text>,
         nfs_root -> Nullable<Text>,
+        group_id -> Nullable<Int4>,
+        updated_at -> Timestamptz,
+        series_id -> Nullable<Int4>,
+        cancelled_at -> Nullable<Timestamptz>,
+        cancelled_by -> Nullable<Int4>,
+        cancel_reason -> Nullable<Text>,
+        pending -> Bool,
+        reviewed_by -> Nullable<Int4>,
+        reviewed_at -> Nullable<Timestamptz>,
+        review_comment -> Nullable<Text>,
+        purpose -> Text,
+        notes -> Nullable<Text>,
+        project -> Nullable<Text>,
+        team_id -> Nullable<Int4>,
+        keys_token -> Text,
     }
 }
 
@@ -100,15 +282,66 @@ table! {
     }
 }
 
+table! {
+    role_capabilities (id) {
+        id -> Int4,
+        role_id -> Int4,
+        capability -> Text,
+    }
+}
+
 table! {
     roles (id) {
         id -> Int4,
         name -> Text,
-        can_alter_machines -> Bool,
-        can_alter_users -> Bool,
-        can_create_machines -> Bool,
-        can_delete_machines -> Bool,
-        can_view_users -> Bool,
+        max_reservation_hours -> Nullable<Int4>,
+        max_advance_days -> Nullable<Int4>,
+        max_concurrent_reservations -> Nullable<Int4>,
+        max_weekly_hours -> Nullable<Int4>,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        token -> Text,
+        created_at -> Timestamptz,
+        last_seen -> Timestamptz,
+        ip_address -> Nullable<Text>,
+        user_agent -> Nullable<Text>,
+        csrf_token -> Text,
+        acting_as -> Nullable<Int4>,
+    }
+}
+
+table! {
+    ssh_keys (id) {
+        id -> Int4,
+        user_id -> Int4,
+        key_type -> Text,
+        key_data -> Text,
+        comment -> Nullable<Text>,
+        fingerprint -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    team_memberships (id) {
+        id -> Int4,
+        team_id -> Int4,
+        user_id -> Int4,
+        is_manager -> Bool,
+    }
+}
+
+table! {
+    teams (id) {
+        id -> Int4,
+        name -> Text,
+        description -> Text,
+        created_at -> Timestamptz,
     }
 }
 
@@ -120,14 +353,60 @@ table! {
     }
 }
 
+table! {
+    waitlist_entries (id) {
+        id -> Int4,
+        user_id -> Int4,
+        machine_id -> Nullable<Int4>,
+        min_memory_gb -> Nullable<Int4>,
+        min_cores -> Nullable<Int4>,
+        arch_id -> Nullable<Int4>,
+        min_microarch_id -> Nullable<Int4>,
+        tags -> Text,
+        ssd -> Nullable<Bool>,
+        window_start -> Timestamptz,
+        window_end -> Timestamptz,
+        created_at -> Timestamptz,
+        offered_machine_id -> Nullable<Int4>,
+        offer_expires -> Nullable<Timestamptz>,
+        reservation_id -> Nullable<Int4>,
+        closed_at -> Nullable<Timestamptz>,
+        purpose -> Text,
+    }
+}
+
 joinable!(microarchitectures -> architectures (arch_id));
 joinable!(processors -> microarchitectures (microarch_id));
-joinable!(machines -> processors (processor_id));
+joinable!(machine_processors -> machines (machine_id));
+joinable!(machine_processors -> processors (processor_id));
+joinable!(machine_tags -> machines (machine_id));
+joinable!(devices -> machines (machine_id));
 joinable!(disks -> machines (machine_id));
 joinable!(nics -> machines (machine_id));
+joinable!(machines -> pools (pool_id));
+joinable!(access_rules -> pools (pool_id));
+joinable!(access_rules -> machines (machine_id));
 joinable!(emails -> users (user_id));
 joinable!(role_assignments -> users (user_id));
 joinable!(role_assignments -> roles (role_id));
+joinable!(role_capabilities -> roles (role_id));
+joinable!(ldap_group_roles -> roles (role_id));
 joinable!(reservations -> users (user_id));
 joinable!(reservations -> machines (machine_id));
-joinable!(github_accounts -> users (user_id));
+joinable!(reservations -> reservation_groups (group_id));
+joinable!(reservation_groups -> users (user_id));
+joinable!(reservations -> reservation_series (series_id));
+joinable!(reservation_series -> machines (machine_id));
+joinable!(reservation_series -> users (user_id));
+joinable!(reservation_transfers -> reservations (reservation_id));
+joinable!(reservations -> teams (team_id));
+joinable!(team_memberships -> teams (team_id));
+joinable!(team_memberships -> users (user_id));
+joinable!(identities -> users (user_id));
+joinable!(sessions -> users (user_id));
+joinable!(ssh_keys -> users (user_id));
+joinable!(invitation_roles -> invitations (invitation_id));
+joinable!(invitation_roles -> roles (role_id));
+joinable!(calendar_tokens -> users (user_id));
+joinable!(notifications -> users (user_id));
+joinable!(waitlist_entries -> users (user_id));
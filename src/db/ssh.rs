@@ -0,0 +1,119 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use error::Error;
+use rustc_serialize::base64::{self, FromBase64, ToBase64};
+use std::fmt;
+
+/// The kinds of SSH public keys that we accept (DSA keys are no longer accepted by OpenSSH).
+pub const KEY_TYPES: &'static [&'static str] = &[
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+
+///
+/// An SSH public key, as found on one line of an `authorized_keys` file
+/// (e.g., `ssh-ed25519 AAAAC3Nz... alice@laptop`).
+///
+#[derive(Clone, Debug)]
+pub struct PublicKey {
+    pub key_type: String,
+    pub data: String,
+    pub comment: Option<String>,
+}
+
+impl PublicKey {
+    ///
+    /// Parse a public key in OpenSSH format, checking that it is of a type we accept and that
+    /// the encoded key really is of the type that it claims to be.
+    ///
+    pub fn parse(line: &str) -> Result<PublicKey, Error> {
+        let mut parts = line.trim().splitn(3, char::is_whitespace);
+        let key_type = parts.next().unwrap_or("");
+        let data = parts
+            .next()
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .ok_or(Error::InvalidData(String::from("SSH public key has no key data")))?;
+        let comment = parts.next().map(str::trim).filter(|c| !c.is_empty());
+
+        if !KEY_TYPES.contains(&key_type) {
+            return Err(Error::InvalidData(format![
+                "unsupported SSH key type '{}' (expected one of: {})",
+                key_type,
+                KEY_TYPES.join(", ")
+            ]));
+        }
+
+        // The key blob starts with its own type, as a length-prefixed string.
+        let blob = data
+            .from_base64()
+            .map_err(|e| Error::InvalidData(format!["malformed SSH key data: {}", e]))?;
+
+        let blob_type = if blob.len() >= 4 {
+            let len = ((blob[0] as usize) << 24)
+                | ((blob[1] as usize) << 16)
+                | ((blob[2] as usize) << 8)
+                | (blob[3] as usize);
+            blob.get(4..4 + len)
+        } else {
+            None
+        };
+
+        if blob_type != Some(key_type.as_bytes()) {
+            return Err(Error::InvalidData(format![
+                "SSH key data is not a valid '{}' key",
+                key_type
+            ]));
+        }
+
+        Ok(PublicKey {
+            key_type: key_type.to_string(),
+            data: data.to_string(),
+            comment: comment.map(str::to_string),
+        })
+    }
+
+    ///
+    /// The key's SHA-256 fingerprint, as shown by `ssh-keygen -l` (e.g., `SHA256:nThbg6kX...`).
+    ///
+    pub fn fingerprint(&self) -> Result<String, Error> {
+        let blob = self
+            .data
+            .from_base64()
+            .map_err(|e| Error::InvalidData(format!["malformed SSH key data: {}", e]))?;
+
+        let mut hasher = Sha256::new();
+        hasher.input(&blob);
+
+        let mut digest = [0; 32];
+        hasher.result(&mut digest);
+
+        let encoded = digest.to_base64(base64::STANDARD);
+        Ok(format!["SHA256:{}", encoded.trim_end_matches('=')])
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write![f, "{} {}", self.key_type, self.data]?;
+        if let Some(ref comment) = self.comment {
+            write![f, " {}", comment]?;
+        }
+
+        Ok(())
+    }
+}
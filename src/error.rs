@@ -10,6 +10,7 @@
 use super::diesel;
 use super::hyper;
 use super::native_tls;
+use super::openssl;
 use super::rustc_serialize;
 
 use chrono;
@@ -27,6 +28,10 @@ pub enum Error {
     /// There is a misconfiguration of the Clowder server itself.
     ConfigError(String),
 
+    /// A state-changing request didn't carry the session's CSRF token (e.g., it came from another
+    /// site or from a form rendered before the user logged in again).
+    CsrfMismatch,
+
     /// There was an error connecting to a database.
     DatabaseConnectionError(diesel::ConnectionError),
 
@@ -44,6 +49,9 @@ pub enum Error {
 
     /// The user is not permitted to perform the requested action.
     NotAuthorized(String),
+
+    /// A request would violate the user's reservation policy (for each of the given reasons).
+    PolicyViolation(Vec<String>),
 }
 
 impl Error {
@@ -53,11 +61,13 @@ impl Error {
             &Error::AuthRequired => "Authorization required",
             &Error::BadRequest(_) => "Bad request",
             &Error::ConfigError(_) => "Configuration error",
+            &Error::CsrfMismatch => "Form expired",
             &Error::DatabaseError(_) => "Database error",
             &Error::DatabaseConnectionError(_) => "Database connection error",
             &Error::InvalidData(_) => "Invalid data",
             &Error::NetError(_) => "Network error",
             &Error::NotAuthorized(_) => "Authorization error",
+            &Error::PolicyViolation(_) => "Reservation policy violation",
         }
     }
 }
@@ -80,11 +90,20 @@ impl std::fmt::Display for Error {
             &Error::AuthRequired => write![f, "Authorization required"],
             &Error::BadRequest(ref req) => write![f, "{}", req],
             &Error::ConfigError(ref msg) => write![f, "{}", msg],
+            &Error::CsrfMismatch => write![
+                f,
+                "This form was submitted from an old page (e.g., from before you last logged in) \
+                 or from another site, so nothing has been done with it. \
+                 Please go back, reload the page and try again."
+            ],
             &Error::DatabaseError(ref e) => write![f, "{:?}", e],
             &Error::DatabaseConnectionError(ref e) => write![f, "{:?}", e],
             &Error::InvalidData(ref msg) => write![f, "{}", msg],
             &Error::NetError(ref e) => write![f, "{:?}", e],
             &Error::NotAuthorized(ref action) => write![f, "Not authorized to {}", action],
+            &Error::PolicyViolation(ref reasons) => {
+                write![f, "Reservation policy violated: {}", reasons.join("; ")]
+            }
         }
     }
 }
@@ -122,8 +141,20 @@ impl From<native_tls::Error> for Error {
     }
 }
 
+impl From<openssl::error::ErrorStack> for Error {
+    fn from(err: openssl::error::ErrorStack) -> Error {
+        Error::AuthError(format!["cryptographic error: {}", err])
+    }
+}
+
 impl From<rustc_serialize::json::DecoderError> for Error {
     fn from(err: rustc_serialize::json::DecoderError) -> Error {
         Error::InvalidData(format!["JSON error: {}", err.description()])
     }
 }
+
+impl From<rustc_serialize::json::ParserError> for Error {
+    fn from(err: rustc_serialize::json::ParserError) -> Error {
+        Error::InvalidData(format!["JSON error: {}", err.description()])
+    }
+}
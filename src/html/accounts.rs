@@ -0,0 +1,699 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use std::collections::HashSet;
+use std::env;
+
+use chrono::Duration;
+use db;
+use db::models::*;
+use diesel::pg::PgConnection as Connection;
+use diesel::Connection as DieselConnection;
+use maud::*;
+use rocket::http::{Cookie, Cookies, SameSite};
+use rocket::request::{self, FlashMessage, LenientForm};
+use rocket::response::{Flash, Redirect};
+use rustc_serialize::json::ToJson;
+
+use super::auth::{self, AuthContext, ClientInfo};
+use super::bootstrap::{self, Page};
+use super::link::Link;
+use super::{forms, login, page, route_prefix, tables};
+use super::Error;
+
+/// The (encrypted) cookie that remembers an authenticated identity that has no user yet.
+static IDENTITY_COOKIE_NAME: &'static str = "clowder_identity";
+
+/// The (encrypted) cookie that remembers which invitation is being accepted.
+static INVITATION_COOKIE_NAME: &'static str = "clowder_invitation";
+
+/// How long invitations last unless the administrator says otherwise.
+const DEFAULT_INVITATION_DAYS: i64 = 7;
+
+/// Both cookies are set or read while being redirected back from an identity provider, so they
+/// can't be SameSite=Strict (Rocket's default for private cookies).
+fn lax_cookie(name: &'static str, value: String) -> Cookie<'static> {
+    Cookie::build(name, value)
+        .path("/")
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .finish()
+}
+
+///
+/// Decide what to do with an identity that a provider has vouched for but that doesn't belong to
+/// any user: if an invitation is being accepted, create the invited user; otherwise, remember the
+/// identity so that its owner can request an account.
+///
+pub fn unknown_identity(
+    provider: &str,
+    subject: &str,
+    client: &ClientInfo,
+    conn: &Connection,
+    mut cookies: Cookies,
+) -> Result<Redirect, Error> {
+    if let Some(cookie) = cookies.get_private(INVITATION_COOKIE_NAME) {
+        let invitation = Invitation::with_token(cookie.value(), conn);
+        cookies.remove_private(cookie);
+
+        if let Ok(invitation) = invitation {
+            if invitation.is_open() {
+                check_username(&invitation.username, conn)?;
+
+                let invitation_id = invitation.id;
+                let user = conn.transaction::<_, Error, _>(|| {
+                    let user = invitation.accept(provider, subject, conn)?;
+                    AuditEvent::build("invitation.accept", "invitation", invitation_id)
+                        .after(&user)
+                        .insert(&user, client.ip.clone(), conn)?;
+                    Ok(user)
+                })?;
+
+                auth::start_session(&user, client, conn, cookies)?;
+
+                return Ok(Redirect::to(format!["{}user/{}", route_prefix(), user.username]));
+            }
+        }
+    }
+
+    cookies.add_private(lax_cookie(
+        IDENTITY_COOKIE_NAME,
+        format!["{} {}", provider, subject],
+    ));
+
+    Ok(Redirect::to(format!["{}account/request", route_prefix()]))
+}
+
+/// The (provider, subject) identity that the current visitor authenticated with, if any.
+fn pending_identity(cookies: &mut Cookies) -> Result<(String, String), Error> {
+    let cookie = cookies.get_private(IDENTITY_COOKIE_NAME).ok_or_else(|| {
+        Error::BadRequest(String::from("sign in before requesting an account"))
+    })?;
+
+    let mut parts = cookie.value().splitn(2, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(provider), Some(subject)) => Ok((provider.to_string(), subject.to_string())),
+        _ => Err(Error::AuthError(String::from("invalid identity cookie"))),
+    }
+}
+
+/// Check that a username is well-formed and not already taken.
+fn check_username(username: &str, conn: &Connection) -> Result<(), Error> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.';
+
+    if username.is_empty() || !username.chars().all(valid) {
+        return Err(Error::BadRequest(format![
+            "invalid username '{}' (use letters, digits, '-', '_' and '.')",
+            username
+        ]));
+    }
+
+    if User::with_username(username, conn).is_ok() {
+        return Err(Error::BadRequest(format!["the username '{}' is taken", username]));
+    }
+
+    Ok(())
+}
+
+fn check_account_admin(auth: &AuthContext) -> Result<(), Error> {
+    if auth.user.can(Capability::AlterUsers, &auth.conn)? {
+        Ok(())
+    } else {
+        Err(Error::NotAuthorized(String::from("manage account requests and invitations")))
+    }
+}
+
+/// A page for visitors who aren't (yet) users, so it has no navigation bar.
+fn visitor_page<S: Into<String>>(title: S) -> Page {
+    Page::new(title).link_prefix(route_prefix())
+}
+
+fn role_options(c: &Connection) -> Result<Vec<forms::SelectOption>, Error> {
+    Ok(Role::all(c)?
+        .into_iter()
+        .map(|r| forms::SelectOption::new(&*r.name, &*r.name))
+        .collect())
+}
+
+#[get("/account/request")]
+pub fn request_page(mut cookies: Cookies, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let (provider, subject) = pending_identity(&mut cookies)?;
+    let conn = db::establish_connection()?;
+    let latest = AccountRequest::latest(&provider, &subject, &conn)?;
+
+    let pending = latest.as_ref().map(AccountRequest::is_pending).unwrap_or(false);
+
+    // Suggest the identity itself as a username if it would make a reasonable one.
+    let suggestion = if check_username(&subject, &conn).is_ok() {
+        subject.clone()
+    } else {
+        String::new()
+    };
+
+    Ok(visitor_page("Clowder: request an account").flash(flash).content(html! {
+        h2 { "Request an account" }
+
+        p {
+            "You have signed in as " code { (subject) } " (" (provider) "), "
+            "but that identity doesn't belong to a Clowder user."
+        }
+
+        @if let Some(ref r) = latest {
+            @if r.is_pending() {
+                (bootstrap::callout("info", "Your request is awaiting review", html! {
+                    p {
+                        "You asked for the username " code { (r.username) } " on "
+                        (r.created_at.format("%e %b %Y")) ". "
+                        "Once an administrator approves it, sign in again to use Clowder."
+                    }
+                }))
+            } @else if !r.approved {
+                (bootstrap::callout("warning", "Your last request was declined", html! {
+                    @if let Some(ref comment) = r.review_comment {
+                        p { (comment) }
+                    }
+                }))
+            }
+        }
+
+        @if !pending {
+            form action={ (route_prefix()) "account/request" } method="post" {
+                table.lefty {
+                    tr {
+                        th { "Username" }
+                        td { (forms::Input::new("username").value(suggestion).size(20)) }
+                    }
+                    tr {
+                        th { "Name" }
+                        td { (forms::Input::new("name").size(30)) }
+                    }
+                    tr {
+                        th { "Email" }
+                        td { (forms::Input::new("email").size(30)) }
+                    }
+                    tr {
+                        th { "Why do you need an account?" }
+                        td { textarea name="reason" rows="4" cols="45" {} }
+                    }
+                    tr {
+                        th /
+                        td { (forms::SubmitButton::new().label("Request account")) }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct AccountRequestForm {
+    username: String,
+    name: String,
+    email: String,
+    reason: String,
+}
+
+#[post("/account/request", data = "<form>")]
+pub fn request(
+    form: LenientForm<AccountRequestForm>,
+    mut cookies: Cookies,
+) -> Result<Flash<Redirect>, Error> {
+    let (provider, subject) = pending_identity(&mut cookies)?;
+    let conn = db::establish_connection()?;
+
+    if let Some(r) = AccountRequest::latest(&provider, &subject, &conn)? {
+        if r.is_pending() {
+            return Err(Error::BadRequest(String::from(
+                "you have already requested an account",
+            )));
+        }
+    }
+
+    let username = form.username.trim();
+    check_username(username, &conn)?;
+
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(Error::BadRequest(String::from("please tell us your name")));
+    }
+
+    let request = AccountRequest::insert(
+        provider,
+        subject,
+        username.to_string(),
+        name.to_string(),
+        form.email.trim().to_string(),
+        form.reason.trim().to_string(),
+        &conn,
+    )?;
+
+    for admin in User::all(&conn)? {
+        if admin.can(Capability::AlterUsers, &conn)? {
+            Notification::insert(
+                admin.id,
+                format!["{} ({}) has requested an account", request.name, request.username],
+                Some(String::from("accounts")),
+                &conn,
+            )?;
+        }
+    }
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}account/request", route_prefix()]),
+        "info",
+        "Your request has been sent to Clowder's administrators",
+    ))
+}
+
+#[get("/accounts")]
+pub fn accounts(auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    check_account_admin(&auth)?;
+    let conn = &auth.conn;
+
+    let requests = AccountRequest::pending(conn)?;
+
+    let mut invitations = Vec::new();
+    for i in Invitation::all(conn)? {
+        let roles = i.roles(conn)?.into_iter().map(|r| r.name).collect::<Vec<_>>();
+        let user = match i.user_id {
+            Some(id) => Some(User::get(id, conn)?),
+            None => None,
+        };
+
+        invitations.push((i, roles.join(", "), user));
+    }
+
+    let base_url = env::var("CLOWDER_URL").unwrap_or_default();
+    let invitation_link = |i: &Invitation| {
+        format![
+            "{}{}invitation/{}",
+            base_url.trim_right_matches('/'),
+            route_prefix(),
+            i.token
+        ]
+    };
+
+    Ok(page("Clowder: accounts", &auth).flash(flash).content(html! {
+        h2 { "Account requests" }
+
+        @if requests.is_empty() {
+            p { em { "There are no pending account requests." } }
+        } @else {
+            table.table.table-responsive {
+                (tables::TableHeader::new(&[
+                    "Requested", "Identity", "Username", "Name", "Email", "Reason", "",
+                ]))
+
+                tbody {
+                    @for r in &requests {
+                        tr {
+                            td { (r.created_at.format("%e %b %Y")) }
+                            td { (r.provider) ": " code { (r.subject) } }
+                            td { (r.username) }
+                            td { (r.name) }
+                            td { (r.email) }
+                            td { (r.reason) }
+                            td {
+                                form
+                                    action={ (route_prefix()) "account/request/" (r.id) "/approve" }
+                                    method="post" {
+                                    (csrf)
+                                    (forms::Select::new("roles")
+                                        .multiple(true)
+                                        .set_options(role_options(conn)?))
+                                    br /
+                                    (forms::Input::new("comment").size(20))
+                                    br /
+                                    (forms::SubmitButton::new().label("Approve"))
+                                }
+                                form
+                                    action={ (route_prefix()) "account/request/" (r.id) "/reject" }
+                                    method="post" {
+                                    (csrf)
+                                    (forms::Input::new("comment").size(20))
+                                    " "
+                                    (forms::SubmitButton::new().label("Reject"))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        h2 { "Invitations" }
+
+        p {
+            "Whoever first signs in after following an invitation's link becomes a new user "
+            "with the invitation's details and roles, so only send each link to one person."
+        }
+
+        @if !invitations.is_empty() {
+            table.table.table-responsive {
+                (tables::TableHeader::new(&[ "Username", "Name", "Roles", "Status", "" ]))
+
+                tbody {
+                    @for &(ref i, ref roles, ref user) in &invitations {
+                        tr {
+                            td { (i.username) }
+                            td { (i.name) }
+                            td { (roles) }
+                            td {
+                                @if let Some(ref u) = *user {
+                                    "accepted by " (Link::from(u))
+                                } @else if i.is_open() {
+                                    "expires " (i.expires_at.format("%e %b %Y")) ": "
+                                    code { (invitation_link(i)) }
+                                } @else {
+                                    "expired"
+                                }
+                            }
+                            td {
+                                @if user.is_none() {
+                                    form
+                                        action={ (route_prefix()) "invitation/" (i.id) "/revoke" }
+                                        method="post" {
+                                        (csrf)
+                                        (forms::SubmitButton::new().label("Revoke"))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        h3 { "Invite someone" }
+
+        form action={ (route_prefix()) "invitation/create" } method="post" {
+            (csrf)
+            table.lefty {
+                tr {
+                    th { "Username" }
+                    td { (forms::Input::new("username").size(20)) }
+                }
+                tr {
+                    th { "Name" }
+                    td { (forms::Input::new("name").size(30)) }
+                }
+                tr {
+                    th { "Email" }
+                    td { (forms::Input::new("email").size(30)) }
+                }
+                tr {
+                    th { "Roles" }
+                    td {
+                        (forms::Select::new("roles")
+                            .multiple(true)
+                            .set_options(role_options(conn)?))
+                    }
+                }
+                tr {
+                    th { "Valid for" }
+                    td {
+                        (forms::Input::new("days")
+                            .value(DEFAULT_INVITATION_DAYS.to_string())
+                            .size(3))
+                        " days"
+                    }
+                }
+                tr {
+                    th /
+                    td { (forms::SubmitButton::new().label("Create invitation")) }
+                }
+            }
+        }
+    }))
+}
+
+pub struct ReviewForm {
+    roles: HashSet<String>,
+    comment: String,
+}
+
+// TODO: use #[derive(FromForm)] once SergioBenitez/Rocket#205 is resolved
+impl<'f> request::FromForm<'f> for ReviewForm {
+    type Error = Error;
+
+    fn from_form(form_items: &mut request::FormItems<'f>, _: bool) -> Result<Self, Self::Error> {
+        let mut review = ReviewForm {
+            roles: HashSet::new(),
+            comment: String::new(),
+        };
+
+        for (key, value) in form_items.map(|i| i.key_value_decoded()) {
+            match &*key {
+                "roles" => {
+                    review.roles.insert(value);
+                }
+                "comment" => review.comment = value,
+                forms::CSRF_PARAM => {}
+                _ => {
+                    return Err(Error::InvalidData(format![
+                        "invalid form data name: '{}'",
+                        key
+                    ]));
+                }
+            }
+        }
+
+        Ok(review)
+    }
+}
+
+impl ReviewForm {
+    fn comment(&self) -> Option<String> {
+        Some(self.comment.trim().to_string()).filter(|c| !c.is_empty())
+    }
+}
+
+fn pending_request(id: i32, auth: &AuthContext) -> Result<AccountRequest, Error> {
+    check_account_admin(auth)?;
+
+    let request = AccountRequest::get(id, &auth.conn)?;
+    if !request.is_pending() {
+        return Err(Error::BadRequest(format![
+            "account request {} has already been reviewed",
+            id
+        ]));
+    }
+
+    Ok(request)
+}
+
+fn accounts_redirect<S: Into<String>>(message: S) -> Flash<Redirect> {
+    Flash::new(
+        Redirect::to(format!["{}accounts", route_prefix()]),
+        "info",
+        message.into(),
+    )
+}
+
+#[post("/account/request/<id>/approve", data = "<form>")]
+pub fn approve(
+    id: i32,
+    form: LenientForm<ReviewForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let request = pending_request(id, &auth)?;
+    check_username(&request.username, &auth.conn)?;
+
+    let user = auth.conn.transaction::<_, Error, _>(|| {
+        let user = request.approve(&auth.user, &form.roles, form.comment(), &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("account.approve", "account_request", id)
+                .after(&user.audit_snapshot(&auth.conn)?),
+        )?;
+        Ok(user)
+    })?;
+
+    Ok(accounts_redirect(format!["Created user {}", user.username]))
+}
+
+#[post("/account/request/<id>/reject", data = "<form>")]
+pub fn reject(
+    id: i32,
+    form: LenientForm<ReviewForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let request = pending_request(id, &auth)?;
+    let request = auth.conn.transaction::<_, Error, _>(|| {
+        let request = request.reject(&auth.user, form.comment(), &auth.conn)?;
+        auth.audit(AuditEvent::build("account.reject", "account_request", request.id))?;
+        Ok(request)
+    })?;
+
+    Ok(accounts_redirect(format![
+        "Rejected {}'s account request",
+        request.username
+    ]))
+}
+
+pub struct InvitationForm {
+    username: String,
+    name: String,
+    email: String,
+    roles: HashSet<String>,
+    days: String,
+}
+
+// TODO: use #[derive(FromForm)] once SergioBenitez/Rocket#205 is resolved
+impl<'f> request::FromForm<'f> for InvitationForm {
+    type Error = Error;
+
+    fn from_form(form_items: &mut request::FormItems<'f>, _: bool) -> Result<Self, Self::Error> {
+        let mut invitation = InvitationForm {
+            username: String::new(),
+            name: String::new(),
+            email: String::new(),
+            roles: HashSet::new(),
+            days: String::new(),
+        };
+
+        for (key, value) in form_items.map(|i| i.key_value_decoded()) {
+            match &*key {
+                "username" => invitation.username = value,
+                "name" => invitation.name = value,
+                "email" => invitation.email = value,
+                "roles" => {
+                    invitation.roles.insert(value);
+                }
+                "days" => invitation.days = value,
+                forms::CSRF_PARAM => {}
+                _ => {
+                    return Err(Error::InvalidData(format![
+                        "invalid form data name: '{}'",
+                        key
+                    ]));
+                }
+            }
+        }
+
+        Ok(invitation)
+    }
+}
+
+#[post("/invitation/create", data = "<form>")]
+pub fn invite(
+    form: LenientForm<InvitationForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    check_account_admin(&auth)?;
+    let conn = &auth.conn;
+
+    let username = form.username.trim();
+    check_username(username, conn)?;
+
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(Error::BadRequest(String::from("every invitation needs a name")));
+    }
+
+    let days = match form.days.trim() {
+        "" => DEFAULT_INVITATION_DAYS,
+        d => d
+            .parse::<i64>()
+            .ok()
+            .filter(|&d| d > 0)
+            .ok_or_else(|| Error::BadRequest(format!["invalid number of days: '{}'", d]))?,
+    };
+
+    let roles = form
+        .roles
+        .iter()
+        .map(|r| Role::with_name(r, conn))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let invitation = conn.transaction::<_, Error, _>(|| {
+        let invitation = Invitation::insert(
+            username.to_string(),
+            name.to_string(),
+            form.email.trim().to_string(),
+            &roles,
+            &auth.user,
+            Duration::days(days),
+            conn,
+        )?;
+
+        let role_names = roles.iter().map(|r| r.name.clone()).collect::<Vec<_>>();
+        auth.audit(
+            AuditEvent::build("invitation.create", "invitation", invitation.id).after(
+                &json_object(vec![
+                    ("username", invitation.username.to_json()),
+                    ("name", invitation.name.to_json()),
+                    ("email", invitation.email.to_json()),
+                    ("roles", role_names.to_json()),
+                    ("expires_at", invitation.expires_at.to_rfc3339().to_json()),
+                ]),
+            ),
+        )?;
+
+        Ok(invitation)
+    })?;
+
+    Ok(accounts_redirect(format![
+        "Invited {}: send them the invitation link below",
+        invitation.username
+    ]))
+}
+
+#[post("/invitation/<id>/revoke")]
+pub fn revoke(id: i32, auth: AuthContext) -> Result<Flash<Redirect>, Error> {
+    check_account_admin(&auth)?;
+
+    let invitation = Invitation::get(id, &auth.conn)?;
+    if invitation.accepted_at.is_some() {
+        return Err(Error::BadRequest(format![
+            "invitation {} has already been accepted",
+            id
+        ]));
+    }
+
+    let username = invitation.username.clone();
+    auth.conn.transaction::<_, Error, _>(|| {
+        auth.audit(AuditEvent::build("invitation.revoke", "invitation", invitation.id))?;
+        invitation.revoke(&auth.conn)?;
+        Ok(())
+    })?;
+
+    Ok(accounts_redirect(format!["Revoked {}'s invitation", username]))
+}
+
+#[get("/invitation/<token>")]
+pub fn invitation(token: String, mut cookies: Cookies) -> Result<Page, Error> {
+    let conn = db::establish_connection()?;
+    let invitation = Invitation::with_token(&token, &conn)
+        .map_err(|_| Error::BadRequest(String::from("no such invitation")))?;
+
+    if !invitation.is_open() {
+        return Err(Error::BadRequest(String::from(
+            "this invitation has expired or has already been accepted",
+        )));
+    }
+
+    cookies.add_private(lax_cookie(INVITATION_COOKIE_NAME, invitation.token.clone()));
+
+    Ok(visitor_page("Clowder: invitation").content(html! {
+        h2 { "Welcome to Clowder, " (invitation.name) }
+
+        p {
+            "You have been invited to use Clowder as " code { (invitation.username) } ". "
+            "Sign in to accept the invitation: whichever identity you sign in with will be "
+            "the one you use to log in from now on."
+        }
+
+        @if let Some(choices) = login::login_choices() {
+            (choices)
+        } @else {
+            p { em { "No way to sign in has been configured." } }
+        }
+    }))
+}
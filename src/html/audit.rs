@@ -0,0 +1,215 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use db::models::*;
+use maud::*;
+use rocket::request::LenientForm;
+use rocket::response::content;
+use rustc_serialize::json::{Json, ToJson};
+use url;
+
+use super::auth::AuthContext;
+use super::bootstrap::Page;
+use super::{forms, page, route_prefix, tables};
+use super::Error;
+
+/// The most events that the audit page will show at once (the JSON export has no limit).
+const PAGE_LIMIT: i64 = 500;
+
+/// How to filter the audit log, as submitted by the filter form (or in an export URL).
+#[derive(Debug, FromForm)]
+pub struct AuditQuery {
+    actor: Option<String>,
+    action: Option<String>,
+    target_type: Option<String>,
+    target_id: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+}
+
+impl AuditQuery {
+    fn field(f: &Option<String>) -> Option<String> {
+        f.as_ref()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    /// Interpret the query, treating empty fields as "any" and `until` as inclusive.
+    fn filter(&self) -> Result<AuditFilter, Error> {
+        let date = |f: &Option<String>| -> Result<Option<NaiveDate>, Error> {
+            match AuditQuery::field(f) {
+                Some(d) => Ok(Some(NaiveDate::parse_from_str(&d, "%Y-%m-%d")?)),
+                None => Ok(None),
+            }
+        };
+
+        let target_id = match AuditQuery::field(&self.target_id) {
+            Some(id) => Some(id.parse().map_err(|_| {
+                Error::BadRequest(format!["invalid target ID '{}'", id])
+            })?),
+            None => None,
+        };
+
+        Ok(AuditFilter {
+            actor: AuditQuery::field(&self.actor),
+            action: AuditQuery::field(&self.action),
+            target_type: AuditQuery::field(&self.target_type),
+            target_id: target_id,
+            since: date(&self.since)?.map(|d| Utc.from_utc_date(&d).and_hms(0, 0, 0)),
+            until: date(&self.until)?
+                .map(|d| Utc.from_utc_date(&d).and_hms(0, 0, 0) + Duration::days(1)),
+        })
+    }
+
+    /// The query string that reproduces this query (e.g., for the JSON export link).
+    fn query_string(&self) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        let fields = [
+            ("actor", &self.actor),
+            ("action", &self.action),
+            ("target_type", &self.target_type),
+            ("target_id", &self.target_id),
+            ("since", &self.since),
+            ("until", &self.until),
+        ];
+
+        for &(name, value) in fields.iter() {
+            if let Some(value) = AuditQuery::field(value) {
+                query.append_pair(name, &value);
+            }
+        }
+
+        query.finish()
+    }
+}
+
+fn check_audit_viewer(auth: &AuthContext) -> Result<(), Error> {
+    if auth.user.can(Capability::ViewAudit, &auth.conn)? {
+        Ok(())
+    } else {
+        Err(Error::NotAuthorized(String::from("view the audit log")))
+    }
+}
+
+/// A link to the audit history of one target (e.g., everything that happened to a reservation).
+fn target_link(e: &AuditEvent) -> Markup {
+    html! {
+        a href={
+            (route_prefix()) "audit?target_type=" (e.target_type) "&target_id=" (e.target_id)
+        } {
+            (e.target_type) " " (e.target_id)
+        }
+    }
+}
+
+fn snapshot(label: &str, json: &Option<String>) -> Markup {
+    let pretty = json
+        .as_ref()
+        .and_then(|s| Json::from_str(s).ok())
+        .map(|j| j.pretty().to_string());
+
+    html! {
+        @if let Some(p) = pretty {
+            strong { (label) }
+            pre { (p) }
+        }
+    }
+}
+
+#[get("/audit?<q..>")]
+pub fn audit(q: LenientForm<AuditQuery>, auth: AuthContext) -> Result<Page, Error> {
+    check_audit_viewer(&auth)?;
+
+    let events = AuditEvent::search(&q.filter()?, Some(PAGE_LIMIT), &auth.conn)?;
+
+    let value = |f: &Option<String>| f.clone().unwrap_or_default();
+    let mut action_options = vec![forms::SelectOption::new("", "(any)")];
+    action_options.extend(AuditEvent::actions(&auth.conn)?.into_iter().map(|a| {
+        let selected = q.action.as_ref() == Some(&a);
+        forms::SelectOption::new(a.clone(), a).selected(selected)
+    }));
+
+    Ok(page("Clowder: audit log", &auth).content(html! {
+        h2 { "Audit log" }
+
+        form action={ (route_prefix()) "audit" } method="get" {
+            table {
+                tr {
+                    th { "Actor" }
+                    td { (forms::Input::new("actor").value(value(&q.actor)).size(15)) }
+                    th { "Action" }
+                    td { (forms::Select::new("action").set_options(action_options)) }
+                }
+                tr {
+                    th { "Target" }
+                    td {
+                        (forms::Input::new("target_type").value(value(&q.target_type)).size(12))
+                        " "
+                        (forms::Input::new("target_id").value(value(&q.target_id)).size(6))
+                    }
+                    th { "Dates" }
+                    td {
+                        input type="date" name="since" value=(value(&q.since)) /
+                        " to "
+                        input type="date" name="until" value=(value(&q.until)) /
+                    }
+                }
+                tr {
+                    th /
+                    td { (forms::SubmitButton::new().label("Filter")) }
+                }
+            }
+        }
+
+        p {
+            a href={ (route_prefix()) "audit.json?" (q.query_string()) } { "Export as JSON" }
+            @if events.len() as i64 == PAGE_LIMIT {
+                " (this page only shows the " (PAGE_LIMIT) " most recent matching events)"
+            }
+        }
+
+        table.table.table-responsive {
+            (tables::TableHeader::new(&["Time", "Actor", "Action", "Target", "From", "Changes"]))
+
+            tbody {
+                @for e in &events {
+                    tr {
+                        td { (e.created_at.format("%Y-%m-%d %H:%M:%S %Z")) }
+                        td { a href={ (route_prefix()) "user/" (e.actor) } { (e.actor) } }
+                        td { code { (e.action) } }
+                        td { (target_link(e)) }
+                        td { @if let Some(ref ip) = e.ip_address { (ip) } }
+                        td {
+                            @if e.before.is_some() || e.after.is_some() {
+                                details {
+                                    summary { "Details" }
+                                    (snapshot("Before", &e.before))
+                                    (snapshot("After", &e.after))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+#[get("/audit.json?<q..>")]
+pub fn audit_json(
+    q: LenientForm<AuditQuery>,
+    auth: AuthContext,
+) -> Result<content::Json<String>, Error> {
+    check_audit_viewer(&auth)?;
+
+    AuditEvent::search(&q.filter()?, None, &auth.conn)
+        .map(|events| content::Json(events.to_json().to_string()))
+        .map_err(Error::DatabaseError)
+}
@@ -7,19 +7,48 @@
  * copied, modified, or distributed except according to those terms.
  */
 
+use chrono::{Duration, Utc};
 use db::models::*;
 use diesel::pg::PgConnection as Connection;
 use diesel::result::Error as DieselError;
-use rocket::http::Cookies;
+use openssl::memcmp;
+use rocket::fairing::AdHoc;
+use rocket::http::{Cookie, Cookies, Method};
 use rocket::request;
-use std::{env, fmt};
+use rocket::response::Redirect;
+use std::{env, fmt, str};
+use url::Url;
 
 use crate::error::Error;
-use super::github;
+use super::{accounts, forms};
 use super::rocket;
 
-/// The name of the cookie we set (with authenticated encryption) for the user's username.
-static AUTH_COOKIE_NAME: &'static str = "clowder_user";
+/// The name of the cookie we set (with authenticated encryption) for the user's session token.
+static AUTH_COOKIE_NAME: &'static str = "clowder_session";
+
+/// The CSRF token for fake authentication, which has no session (or security) to speak of.
+static FAKE_AUTH_CSRF_TOKEN: &'static str = "fake-auth";
+
+/// How often to record that a session is still in use (more often would just add database load).
+const SESSION_TOUCH_SECONDS: i64 = 60;
+
+///
+/// How long sessions may last, both in total (`CLOWDER_SESSION_HOURS`, default one week) and
+/// without being used (`CLOWDER_SESSION_IDLE_HOURS`, default one day).
+///
+pub fn session_timeouts() -> (Duration, Duration) {
+    let hours = |name: &str, default: i64| {
+        env::var(name)
+            .ok()
+            .and_then(|h| h.parse().ok())
+            .unwrap_or(default)
+    };
+
+    (
+        Duration::hours(hours("CLOWDER_SESSION_HOURS", 7 * 24)),
+        Duration::hours(hours("CLOWDER_SESSION_IDLE_HOURS", 24)),
+    )
+}
 
 ///
 /// A struct that authenticates users given a MAC'ed cookie or a debug auth bypass
@@ -36,42 +65,82 @@ impl Authenticator {
     }
 
     ///
-    /// Attempt to authenticate a Clowder user using a MAC'ed cookie, or if that fails, whatever
-    /// fallback authentication methods are permitted by local policy (e.g., fake/test auth data).
+    /// Attempt to authenticate a Clowder user using a MAC'ed session cookie, or if that fails,
+    /// whatever fallback authentication methods are permitted by local policy (e.g., fake/test
+    /// auth data).
     ///
-    fn authenticate(self, cookies: &mut Cookies) -> Result<AuthContext, Error> {
-        let user = cookies
+    fn authenticate(self, cookies: &mut Cookies, ip: Option<String>) -> Result<AuthContext, Error> {
+        let session = cookies
             .get_private(AUTH_COOKIE_NAME)
             .ok_or(Error::AuthRequired)
-            .and_then(|ref username| self.lookup_user(username.value()))
-            .or_else(|_| self.try_fake_auth())?;
+            .and_then(|ref token| self.lookup_session(token.value()));
+
+        let (user, session) = match session {
+            Ok(s) => (User::get(s.user_id, &self.conn)?, Some(s)),
+            Err(_) => (self.try_fake_auth()?, None),
+        };
+
+        let acting_as = match session.as_ref().and_then(|s| s.acting_as) {
+            Some(id) => self.acting_as(&user, id)?,
+            None => None,
+        };
+
+        let (user, impersonator) = match acting_as {
+            Some(other) => (other, Some(user)),
+            None => (user, None),
+        };
 
         Ok(AuthContext {
             conn: self.conn,
             user: user,
+            impersonator: impersonator,
+            session: session,
+            ip: ip,
         })
     }
 
     ///
-    /// Look up a known GitHub user in the Clowder user database.
+    /// Look up the user that an administrator is viewing Clowder as, as long as the administrator
+    /// is still permitted to do so.
     ///
-    fn github_user(&self, gh_username: &str) -> Result<User, Error> {
-        GithubAccount::get(&gh_username, &self.conn)
-            .map(|(_, user)| user)
-            .map_err(|_| Error::AuthError(format!["Unknown GitHub user '{}'", gh_username]))
+    fn acting_as(&self, admin: &User, id: i32) -> Result<Option<User>, Error> {
+        if admin.can(Capability::AlterUsers, &self.conn)? {
+            Ok(Some(User::get(id, &self.conn)?))
+        } else {
+            warn!["{} can no longer view Clowder as user {}", admin.username, id];
+            Ok(None)
+        }
     }
 
     ///
-    /// Attempt to look up a user (by Clowder username) in the user database.
+    /// Look up a session by its token, ending it if it has expired.
     ///
-    fn lookup_user(&self, clowder_username: &str) -> Result<User, Error> {
-        User::with_username(&clowder_username, &self.conn)
-            .map_err(|e| match e {
-                diesel::result::Error::NotFound => {
-                    Error::AuthError(format!["No such user ('{}')", clowder_username])
-                },
-                e => Error::DatabaseError(e)
-            })
+    fn lookup_session(&self, token: &str) -> Result<Session, Error> {
+        let session = Session::with_token(token, &self.conn).map_err(|e| match e {
+            DieselError::NotFound => Error::AuthRequired,
+            e => Error::DatabaseError(e),
+        })?;
+
+        let (lifetime, idle) = session_timeouts();
+        if session.is_expired(lifetime, idle) {
+            session.delete(&self.conn)?;
+            return Err(Error::AuthRequired);
+        }
+
+        if Utc::now() - session.last_seen > Duration::seconds(SESSION_TOUCH_SECONDS) {
+            Ok(session.touch(&self.conn)?)
+        } else {
+            Ok(session)
+        }
+    }
+
+    ///
+    /// Look up the Clowder user that an external identity (e.g., a GitHub username or an
+    /// OpenID Connect subject) belongs to.
+    ///
+    fn identity_user(&self, provider: &str, label: &str, subject: &str) -> Result<User, Error> {
+        Identity::user(provider, subject, &self.conn)
+            .map_err(|_| Error::AuthError(format!["Unknown {} identity '{}'", label, subject]))
     }
 
     ///
@@ -84,7 +153,7 @@ impl Authenticator {
         env::var_os("CLOWDER_FAKE_GITHUB_USERNAME")
             .and_then(|s| s.to_str().map(str::to_string))
             .ok_or(Error::AuthRequired)
-            .and_then(|username| self.github_user(&username))
+            .and_then(|username| self.identity_user("github", "GitHub", &username))
     }
 }
 
@@ -95,13 +164,128 @@ pub struct AuthContext {
     /// Database connection
     pub conn: Connection,
 
-    /// The authenticated user
+    /// The user that the request acts as: normally the authenticated user, but an administrator
+    /// can view Clowder as another user (see `impersonator`)
     pub user: User,
+
+    /// The administrator who is really behind the request, if they are viewing Clowder as `user`
+    pub impersonator: Option<User>,
+
+    /// The session that the user is logged in with (`None` with fake authentication)
+    pub session: Option<Session>,
+
+    /// The address that the request came from
+    pub ip: Option<String>,
+}
+
+impl AuthContext {
+    /// The authenticated user, who is not necessarily the user that the request acts as.
+    pub fn real_user(&self) -> &User {
+        self.impersonator.as_ref().unwrap_or(&self.user)
+    }
+
+    /// Record something that the (real) user has just done in the audit log.
+    pub fn audit(&self, event: AuditEventBuilder) -> Result<(), Error> {
+        event.insert(self.real_user(), self.ip.clone(), &self.conn)?;
+        Ok(())
+    }
+
+    /// The token that forms must carry (via `forms::csrf`) to change anything in this session.
+    pub fn csrf_token(&self) -> &str {
+        self.session
+            .as_ref()
+            .map(|s| s.csrf_token.as_str())
+            .unwrap_or(FAKE_AUTH_CSRF_TOKEN)
+    }
+
+    ///
+    /// Check that a request which might change something carries this session's CSRF token:
+    /// another site can make the user's browser submit a form to us, but it can't read the token.
+    ///
+    fn check_csrf(&self, req: &request::Request) -> Result<(), Error> {
+        match req.method() {
+            Method::Get | Method::Head | Method::Options => return Ok(()),
+            _ => {}
+        }
+
+        let expected = self.csrf_token().as_bytes();
+        let SubmittedCsrfToken(ref token) = *req.local_cache(|| SubmittedCsrfToken(None));
+        let token = token.as_ref().map(String::as_str).unwrap_or("");
+
+        if token.len() == expected.len() && memcmp::eq(token.as_bytes(), expected) {
+            Ok(())
+        } else {
+            Err(Error::CsrfMismatch)
+        }
+    }
+
+    ///
+    /// Viewing Clowder as another user is read-only: the only changes an impersonator can make
+    /// are to stop impersonating or to log out.
+    ///
+    fn check_impersonation(&self, req: &request::Request) -> Result<(), Error> {
+        if self.impersonator.is_none() {
+            return Ok(());
+        }
+
+        match req.method() {
+            Method::Get | Method::Head | Method::Options => return Ok(()),
+            _ => {}
+        }
+
+        match req.route().and_then(|r| r.name) {
+            Some("impersonate_stop") | Some("logout") => Ok(()),
+            _ => Err(Error::NotAuthorized(format![
+                "change anything while viewing Clowder as {}",
+                self.user.username
+            ])),
+        }
+    }
 }
 
+///
+/// The CSRF token at the start of a request's body, if any (see `csrf_fairing`).
+///
+struct SubmittedCsrfToken(Option<String>);
+
+///
+/// Request guards can't read request bodies, so this fairing finds the CSRF token that
+/// `forms::csrf` puts at the start of each form (within the part of the body that Rocket has
+/// already read) and leaves it in the request's local cache for `AuthContext` to check.
+///
+pub fn csrf_fairing() -> AdHoc {
+    AdHoc::on_request("CSRF token", |req, data| {
+        let peek = data.peek();
+        let first_field = peek.split(|&b| b == b'&').next().unwrap_or(peek);
+
+        let token = str::from_utf8(first_field).ok().and_then(|field| {
+            let mut parts = field.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(forms::CSRF_PARAM), Some(value)) => Some(value.to_string()),
+                _ => None,
+            }
+        });
+
+        req.local_cache(|| SubmittedCsrfToken(token));
+    })
+}
+
+///
+/// Why the request guard refused a request (as a title and an explanation), so that the 403
+/// catcher can explain it.
+///
+pub struct Refusal(pub Option<(String, String)>);
+
 impl fmt::Debug for AuthContext {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write![f, "AuthContext for User '{:?}'", self.user]
+        match self.impersonator {
+            Some(ref admin) => write![
+                f,
+                "AuthContext for User '{:?}' (impersonated by '{}')",
+                self.user, admin.username
+            ],
+            None => write![f, "AuthContext for User '{:?}'", self.user],
+        }
     }
 }
 
@@ -109,8 +293,11 @@ impl<'a, 'r> request::FromRequest<'a, 'r> for AuthContext {
     type Error = Error;
 
     fn from_request(req: &'a request::Request<'r>) -> request::Outcome<AuthContext, Self::Error> {
+        let ip = req.client_ip().map(|ip| ip.to_string());
         let auth_context = Authenticator::new()
-            .and_then(|a| a.authenticate(&mut req.cookies()));
+            .and_then(|a| a.authenticate(&mut req.cookies(), ip))
+            .and_then(|ctx| ctx.check_csrf(req).map(|()| ctx))
+            .and_then(|ctx| ctx.check_impersonation(req).map(|()| ctx));
 
         match auth_context {
             Ok(ctx) => rocket::outcome::Outcome::Success(ctx),
@@ -120,6 +307,10 @@ impl<'a, 'r> request::FromRequest<'a, 'r> for AuthContext {
                 let failure = match e {
                     Error::AuthError(_) => (rocket::http::Status::Forbidden, e),
                     Error::AuthRequired => (rocket::http::Status::Unauthorized, e),
+                    Error::CsrfMismatch | Error::NotAuthorized(_) => {
+                        req.local_cache(|| Refusal(Some((e.kind().to_string(), e.to_string()))));
+                        (rocket::http::Status::Forbidden, e)
+                    }
                     Error::DatabaseError(DieselError::NotFound) => {
                         (rocket::http::Status::Forbidden, e)
                     }
@@ -132,25 +323,107 @@ impl<'a, 'r> request::FromRequest<'a, 'r> for AuthContext {
     }
 }
 
-/// Handle a GitHub OAuth callback.
-pub fn github_callback(code: String, cookies: rocket::http::Cookies) -> Result<(), Error> {
-    let username = github::auth_callback(code)?;
+///
+/// A guard for forms that are submitted before there is a session (and so a CSRF token), e.g.,
+/// the LDAP login form: another site mustn't be able to log the user in as someone else (login
+/// CSRF), so the browser must say that the form came from one of our own pages.
+///
+pub struct SameOrigin;
+
+impl<'a, 'r> request::FromRequest<'a, 'r> for SameOrigin {
+    type Error = Error;
+
+    fn from_request(req: &'a request::Request<'r>) -> request::Outcome<SameOrigin, Error> {
+        let headers = req.headers();
+        let source = headers
+            .get_one("Origin")
+            .or_else(|| headers.get_one("Referer"))
+            .and_then(|s| Url::parse(s).ok())
+            .and_then(|u| {
+                u.host_str().map(|host| match u.port() {
+                    Some(port) => format!["{}:{}", host, port],
+                    None => host.to_string(),
+                })
+            });
+
+        match (source, headers.get_one("Host")) {
+            (Some(ref source), Some(host)) if source.eq_ignore_ascii_case(host) => {
+                rocket::outcome::Outcome::Success(SameOrigin)
+            }
+            (source, host) => {
+                warn!["Refusing form from {:?} (we are {:?})", source, host];
+
+                let e = Error::CsrfMismatch;
+                req.local_cache(|| Refusal(Some((e.kind().to_string(), e.to_string()))));
+                rocket::outcome::Outcome::Failure((rocket::http::Status::Forbidden, e))
+            }
+        }
+    }
+}
+
+///
+/// Where a request came from, which we record with any session that it starts.
+///
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
 
-    Authenticator::new()?
-        .github_user(&username)
-        .map(|user| set_user_cookie(cookies, user.username))
+impl<'a, 'r> request::FromRequest<'a, 'r> for ClientInfo {
+    type Error = ();
+
+    fn from_request(req: &'a request::Request<'r>) -> request::Outcome<ClientInfo, ()> {
+        rocket::outcome::Outcome::Success(ClientInfo {
+            ip: req.client_ip().map(|ip| ip.to_string()),
+            user_agent: req.headers().get_one("User-Agent").map(str::to_string),
+        })
+    }
 }
 
-/// Log the user out by clearing their auth cookie.
-pub fn logout<'c>(mut jar: Cookies) {
+///
+/// Log in the user that an identity provider (or LDAP) has vouched for. An identity that doesn't
+/// belong to anyone yet can be used to accept an invitation or to request an account.
+///
+pub fn identity_login(
+    provider: &str,
+    subject: &str,
+    client: &ClientInfo,
+    conn: &Connection,
+    cookies: Cookies,
+) -> Result<Redirect, Error> {
+    match Identity::user(provider, subject, conn) {
+        Ok(user) => {
+            start_session(&user, client, conn, cookies)?;
+            Ok(Redirect::to(super::route_prefix()))
+        }
+        Err(DieselError::NotFound) => {
+            accounts::unknown_identity(provider, subject, client, conn, cookies)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Log the user out by ending their session and clearing their auth cookie.
+pub fn logout(auth: AuthContext, mut jar: Cookies) -> Result<(), Error> {
+    if let Some(session) = auth.session {
+        session.delete(&auth.conn)?;
+    }
+
     jar.get_private(AUTH_COOKIE_NAME)
         .map(|c| jar.remove_private(c));
+
+    Ok(())
 }
 
-/// Generate a cookie that attests to a logged-in user's username.
-pub fn set_user_cookie<'c, S: Into<String>>(mut jar: Cookies, username: S) {
-    jar.add_private(rocket::http::Cookie::new(
-        String::from(AUTH_COOKIE_NAME),
-        username.into(),
-    ))
+/// Start a session for a user who has just logged in, and give the browser its cookie.
+pub fn start_session(
+    user: &User,
+    client: &ClientInfo,
+    conn: &Connection,
+    mut jar: Cookies,
+) -> Result<(), Error> {
+    let session = Session::insert(user, client.ip.clone(), client.user_agent.clone(), conn)?;
+    jar.add_private(Cookie::new(AUTH_COOKIE_NAME, session.token));
+
+    Ok(())
 }
@@ -11,6 +11,8 @@ use chrono;
 use maud::*;
 use rocket;
 
+use super::forms;
+
 include! { concat![env!["OUT_DIR"], "/version.rs"] }
 
 pub fn alert<S1, S2>(kind: S1, msg: S2) -> Markup
@@ -207,6 +209,8 @@ pub struct Page {
     prefix: String,
     nav: Vec<NavItem>,
     user: Option<(String, String)>,
+    csrf_token: String,
+    impersonation: Option<(String, String)>,
 }
 
 impl Page {
@@ -218,6 +222,8 @@ impl Page {
             prefix: String::from("/"),
             nav: vec![],
             user: None,
+            csrf_token: String::new(),
+            impersonation: None,
         }
     }
 
@@ -251,6 +257,18 @@ impl Page {
         self.user = Some((username.into(), display_name.into()));
         self
     }
+
+    /// The logged-in user's CSRF token, for the forms that every page has (e.g., "Log out").
+    pub fn csrf_token(mut self, token: &str) -> Self {
+        self.csrf_token = token.into();
+        self
+    }
+
+    /// The administrator (and the user) behind a page viewed as another user, if any.
+    pub fn impersonation(mut self, admin: &str, username: &str) -> Self {
+        self.impersonation = Some((admin.into(), username.into()));
+        self
+    }
 }
 
 impl<'r> rocket::response::Responder<'r> for Page {
@@ -318,8 +336,9 @@ impl Render for Page {
                                             "Profile"
                                         }
                                         div.dropdown-divider {}
-                                        a.dropdown-item href={ (self.prefix) "logout" } {
-                                            "Log out"
+                                        form action={ (self.prefix) "logout" } method="post" {
+                                            (forms::csrf(&self.csrf_token))
+                                            button.dropdown-item type="submit" { "Log out" }
                                         }
                                     }
                                 }
@@ -330,6 +349,23 @@ impl Render for Page {
                     }
 
                     div.container {
+                        @if let Some((ref admin, ref username)) = self.impersonation {
+                            div.row {
+                                div class="col-md-12" {
+                                    div.alert.alert-danger role="alert" {
+                                        form.form-inline action={ (self.prefix) "impersonate/stop" }
+                                             method="post" {
+                                            (forms::csrf(&self.csrf_token))
+                                            strong { "Viewing Clowder as " (username) }
+                                            " (you are logged in as " (admin) "; nothing can be "
+                                            "changed until you stop) "
+                                            button.btn.btn-danger.btn-sm type="submit" { "Exit" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+
                         div.row {
                             div class="col-md-12" {
                                 // Check for a flash (one-time) message:
@@ -374,18 +410,40 @@ impl Render for Page {
                     script src="https://use.fontawesome.com/ff559252db.js" {}
 
                     (PreEscaped("<script>
-                    $('input.daterange').daterangepicker({
+                    $('input.daterange').each(function () {
+                        var options = {
+                            autoApply: true,
+                            locale: {
+                                format: 'HH:mmZ D MMM YYYY'
+                            },
+                            showDropdowns: true,
+                            showToday: true,
+                            timePicker: true,
+                            timePicker24Hour: true,
+                            timePickerIncrement: 15
+                        };
+
+                        // Keep any pre-filled date range (e.g., a slot chosen from the
+                        // availability search).
+                        if (!$(this).val()) {
+                            options.startDate = moment();
+                        }
+
+                        $(this).daterangepicker(options);
+                    })
+
+                    // Single times (e.g., a new end for a reservation), in the same format.
+                    $('input.datetime').daterangepicker({
                         autoApply: true,
                         locale: {
-                            format: 'hh:mmZ D MMM YYYY'
+                            format: 'HH:mmZ D MMM YYYY'
                         },
                         showDropdowns: true,
-                        showToday: true,
-                        startDate: moment(),
+                        singleDatePicker: true,
                         timePicker: true,
                         timePicker24Hour: true,
                         timePickerIncrement: 15
-                    })
+                    });
                     </script>"))
                 }
             }
@@ -0,0 +1,207 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use chrono::{DateTime, Utc};
+use db;
+use db::models::*;
+use diesel;
+use diesel::pg::PgConnection as Connection;
+use rocket;
+use rocket::http::ContentType;
+use rocket::response::content::Content;
+
+use super::Error;
+
+/// The iCalendar format's representation of a UTC date-time.
+const ICAL_DATE_FORMAT: &'static str = "%Y%m%dT%H%M%SZ";
+
+///
+/// An iCalendar (RFC 5545) feed of reservations.
+///
+/// Every reservation is given a UID derived from its database ID, so calendar clients that
+/// re-fetch a feed will update (rather than duplicate) events whose reservations have changed.
+///
+pub struct Calendar {
+    name: String,
+    events: Vec<(Reservation, Machine, User)>,
+}
+
+impl Calendar {
+    pub fn new<S: Into<String>>(name: S, events: Vec<(Reservation, Machine, User)>) -> Calendar {
+        Calendar {
+            name: name.into(),
+            events: events,
+        }
+    }
+
+    /// Render this calendar as an iCalendar document.
+    pub fn to_ics(&self) -> String {
+        let mut lines = vec![
+            String::from("BEGIN:VCALENDAR"),
+            String::from("VERSION:2.0"),
+            String::from("PRODID:-//musec//Clowder//EN"),
+            String::from("CALSCALE:GREGORIAN"),
+            format!["X-WR-CALNAME:{}", escape_text(&self.name)],
+        ];
+
+        for &(ref r, ref m, ref u) in &self.events {
+            lines.push(String::from("BEGIN:VEVENT"));
+            lines.push(format!["UID:reservation-{}@clowder", r.id()]);
+            lines.push(format!["DTSTAMP:{}", ical_date(r.updated_at)]);
+            lines.push(format!["LAST-MODIFIED:{}", ical_date(r.updated_at)]);
+            lines.push(format!["DTSTART:{}", ical_date(r.start())]);
+            if let Some(end) = r.busy_until() {
+                lines.push(format!["DTEND:{}", ical_date(end)]);
+            }
+            lines.push(format![
+                "SUMMARY:{}",
+                escape_text(&format!["{} reserved by {}", m.name, u.username])
+            ]);
+            lines.push(format![
+                "DESCRIPTION:{}",
+                escape_text(&format![
+                    "Reservation {} of {} by {} ({}): {}",
+                    r.id(),
+                    m.name,
+                    u.name,
+                    u.username,
+                    r.purpose
+                ])
+            ]);
+            lines.push(String::from(if r.is_cancelled() {
+                "STATUS:CANCELLED"
+            } else if r.pending {
+                "STATUS:TENTATIVE"
+            } else {
+                "STATUS:CONFIRMED"
+            }));
+            lines.push(String::from("END:VEVENT"));
+        }
+
+        lines.push(String::from("END:VCALENDAR"));
+
+        lines
+            .iter()
+            .map(|l| fold(l))
+            .collect::<Vec<_>>()
+            .join("\r\n")
+            + "\r\n"
+    }
+}
+
+impl<'r> rocket::response::Responder<'r> for Calendar {
+    fn respond_to(self, req: &rocket::Request) -> rocket::response::Result<'r> {
+        Content(ContentType::new("text", "calendar"), self.to_ics()).respond_to(req)
+    }
+}
+
+fn ical_date(t: DateTime<Utc>) -> String {
+    t.format(ICAL_DATE_FORMAT).to_string()
+}
+
+/// Escape a TEXT value (RFC 5545 §3.3.11).
+fn escape_text(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line so that no physical line is longer than 75 octets (RFC 5545 §3.1).
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut len = 0;
+
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+
+        folded.push(c);
+        len += c.len_utf8();
+    }
+
+    folded
+}
+
+/// Look up the user whose calendar token this is, or `None` if there is no such token.
+fn token_user(token: &str, conn: &Connection) -> Result<Option<User>, Error> {
+    match CalendarToken::user(token, conn) {
+        Ok(user) => Ok(Some(user)),
+        Err(diesel::result::Error::NotFound) => Ok(None),
+        Err(e) => Err(Error::DatabaseError(e)),
+    }
+}
+
+/// All reservations (of machines that the token's owner can see), for lab-wide calendars.
+#[get("/calendar/<token>/all.ics")]
+pub fn all(token: String) -> Result<Option<Calendar>, Error> {
+    let conn = db::establish_connection()?;
+    let user = match token_user(&token, &conn)? {
+        Some(u) => u,
+        None => return Ok(None),
+    };
+
+    let acl = AccessControl::for_user(&user, &conn)?;
+    let events = Reservation::all(false, &conn)?
+        .into_iter()
+        .filter(|&(_, ref m, _)| acl.can_view(m))
+        .collect();
+
+    Ok(Some(Calendar::new("Clowder reservations", events)))
+}
+
+/// One machine's reservations.
+#[get("/calendar/<token>/machine/<name>")]
+pub fn machine(token: String, name: String) -> Result<Option<Calendar>, Error> {
+    let conn = db::establish_connection()?;
+    let user = match token_user(&token, &conn)? {
+        Some(u) => u,
+        None => return Ok(None),
+    };
+
+    let name = name.trim_right_matches(".ics");
+    let m = match Machine::with_name(name, &conn) {
+        Ok(m) => m,
+        Err(diesel::result::Error::NotFound) => return Ok(None),
+        Err(e) => return Err(Error::DatabaseError(e)),
+    };
+
+    if !AccessControl::for_user(&user, &conn)?.can_view(&m) {
+        return Ok(None);
+    }
+
+    let events = Reservation::for_machine(&m, &conn)?
+        .into_iter()
+        .map(|(r, u)| (r, m.clone(), u))
+        .collect();
+
+    Ok(Some(Calendar::new(format!["Clowder: {}", m.name], events)))
+}
+
+/// The reservations of the user who owns the token.
+#[get("/calendar/<token>/mine.ics")]
+pub fn mine(token: String) -> Result<Option<Calendar>, Error> {
+    let conn = db::establish_connection()?;
+    let user = match token_user(&token, &conn)? {
+        Some(u) => u,
+        None => return Ok(None),
+    };
+
+    let events = Reservation::for_user(&user, &conn)?
+        .into_iter()
+        .map(|(r, m)| (r, m, user.clone()))
+        .collect();
+
+    Ok(Some(Calendar::new(
+        format!["Clowder: {}'s reservations", user.username],
+        events,
+    )))
+}
@@ -7,45 +7,76 @@
  * copied, modified, or distributed except according to those terms.
  */
 
+use super::auth::Refusal;
 use super::bootstrap;
+use super::login;
 
-use maud;
 use maud::{html, Render};
 use rocket;
-
-use std::env;
+use rocket::http::Status;
+use rocket::response::Responder;
 
 impl Into<bootstrap::Page> for super::Error {
     fn into(self) -> bootstrap::Page {
         bootstrap::Page::new(self.kind())
-            .content(html! {
-                h1 { (self.kind()) }
-                h2 { (self) }
+            .content(match self {
+                super::Error::PolicyViolation(ref reasons) => html! {
+                    h1 { (self.kind()) }
+                    p { "This request is not permitted by your reservation policy:" }
+                    ul {
+                        @for reason in reasons {
+                            li { (reason) }
+                        }
+                    }
+                },
+                _ => html! {
+                    h1 { (self.kind()) }
+                    h2 { (self) }
+                },
             })
             .link_prefix(super::route_prefix())
     }
 }
 
-/// The error catcher for unauthorized accesses prompts for HTTP basic authentication.
+///
+/// Errors that the user can do something about are explained in an error page; any others are
+/// logged and reported as a generic 500 Internal Server Error.
+///
+impl<'r> rocket::response::Responder<'r> for super::Error {
+    fn respond_to(self, req: &rocket::Request) -> rocket::response::Result<'r> {
+        match self {
+            super::Error::PolicyViolation(_) => {
+                let page: bootstrap::Page = self.into();
+                rocket::Response::build_from(page.respond_to(req)?)
+                    .status(Status::Forbidden)
+                    .ok()
+            }
+            e => {
+                error!["{:?}", e];
+                Err(Status::InternalServerError)
+            }
+        }
+    }
+}
+
+/// The error catcher for unauthorized accesses offers every configured way of logging in.
 #[catch(401)]
 pub fn unauthorized(_req: &rocket::Request) -> bootstrap::Page {
-    const OAUTH_URL: &'static str = "https://github.com/login/oauth/authorize";
+    let content = match login::login_choices() {
+        None => {
+            error!["No identity providers configured (e.g., CLOWDER_GH_CLIENT_ID)"];
 
-    let content = match env::var("CLOWDER_GH_CLIENT_ID") {
-        Ok(ref id) => bootstrap::ModalDialog::new("login")
+            html! {
+                h1 { "Login error" }
+                p { "Internal server error: no way to log in has been configured" }
+            }
+        }
+        Some(choices) => bootstrap::ModalDialog::new("login")
             .title("Login required")
-            .body(html! {
-                p {
-                    a.btn.btn-secondary.large href={ (OAUTH_URL) "?client_id=" (id) } {
-                        i.fa.fa-github aria-hidden="true" {}
-                        (maud::PreEscaped("&nbsp;"))
-                        "Sign in with GitHub"
-                    }
-                }
-            })
+            .body(choices)
             .footer(html! {
                 p.footnote {
-                    "Contact "
+                    "New to Clowder? Sign in anyway to request an account, or contact "
                     a href="https://www.engr.mun.ca/~anderson/" { "Jonathan Anderson" }
                     " for authorization to use this system."
                 }
@@ -53,15 +84,6 @@ pub fn unauthorized(_req: &rocket::Request) -> bootstrap::Page {
             .closeable(false)
             .start_open(true)
             .render(),
-
-        Err(ref e) => {
-            error!["Error in GitHub client ID: {:?}", e];
-
-            html! {
-                h1 { "Login error" }
-                p { "Internal server error in GitHub authentication" }
-            }
-        }
     };
 
     bootstrap::Page::new("401 Unauthorized")
@@ -69,9 +91,21 @@ pub fn unauthorized(_req: &rocket::Request) -> bootstrap::Page {
         .link_prefix(super::route_prefix())
 }
 
-/// 403 forbidden means that (re-)authenticating won't help.
+///
+/// 403 forbidden means that (re-)authenticating won't help. If the request guard said why it
+/// refused the request (e.g., a form from a page left open since an earlier session), say so.
+///
 #[catch(403)]
-pub fn forbidden() -> bootstrap::Page {
+pub fn forbidden(req: &rocket::Request) -> bootstrap::Page {
+    if let Some((ref title, ref explanation)) = req.local_cache(|| Refusal(None)).0 {
+        return bootstrap::Page::new(title.as_str())
+            .content(html! {
+                h2 { (title) }
+                p { (explanation) }
+            })
+            .link_prefix(super::route_prefix());
+    }
+
     bootstrap::Page::new("403 Forbidden")
         .content(html! {
             h2 { ("403 Forbidden") }
@@ -9,6 +9,24 @@
 
 use maud::*;
 
+///
+/// The form field that carries a session's CSRF token (see `AuthContext`). Forms are parsed with
+/// `LenientForm` (or, for hand-written `FromForm` implementations, skip this field), since
+/// Rocket's strict `Form` rejects any field that the form type doesn't declare.
+///
+pub const CSRF_PARAM: &'static str = "csrf_token";
+
+///
+/// A hidden field carrying the session's CSRF token, which must be the first thing in any form
+/// that changes something: the request guard only gets to see the start of the form's body (see
+/// `auth::csrf_fairing`). Keeping the token out of URLs keeps it out of logs and `Referer` headers.
+///
+pub fn csrf(token: &str) -> Markup {
+    html! {
+        input type="hidden" name=(CSRF_PARAM) value=(token) /
+    }
+}
+
 /// An HTML <input> field
 pub struct Input {
     name: String,
@@ -16,10 +16,59 @@ use hyper_native_tls::NativeTlsClient;
 use rustc_serialize::Decodable;
 use url::Url;
 
+use super::login::{IdentityProvider, LoginState};
 use super::Error;
+use std::env;
 use std::io::Read;
 
 const ACCESS_TOKEN_URL: &'static str = "https://github.com/login/oauth/access_token";
+const AUTHORIZE_URL: &'static str = "https://github.com/login/oauth/authorize";
+
+///
+/// GitHub as an identity provider: users are identified by their GitHub usernames.
+///
+/// GitHub will redirect users to the callback URL registered for the OAuth application
+/// (`/gh-callback`), so we don't pass our own `redirect_uri`.
+///
+pub struct GitHub {
+    client_id: String,
+}
+
+impl GitHub {
+    /// Configure GitHub login if `CLOWDER_GH_CLIENT_ID` is set.
+    pub fn from_env() -> Option<GitHub> {
+        env::var("CLOWDER_GH_CLIENT_ID")
+            .ok()
+            .map(|client_id| GitHub { client_id })
+    }
+}
+
+impl IdentityProvider for GitHub {
+    fn id(&self) -> &str {
+        "github"
+    }
+
+    fn label(&self) -> &str {
+        "GitHub"
+    }
+
+    fn icon(&self) -> &str {
+        "fa-github"
+    }
+
+    fn authorization_url(&self, login: &LoginState) -> Result<String, Error> {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("client_id", &self.client_id)
+            .append_pair("state", &login.state)
+            .finish();
+
+        Ok(format!["{}?{}", AUTHORIZE_URL, query])
+    }
+
+    fn authenticate(&self, code: &str, _login: &LoginState) -> Result<String, Error> {
+        auth_callback(code.to_string())
+    }
+}
 
 ///
 /// Retrieve a GitHub user's username based on an OAuth code passed to a GitHub callback.
@@ -44,6 +93,14 @@ pub fn auth_callback(auth_code: String) -> Result<String, Error> {
         .map(|u| u.username().to_string())
 }
 
+///
+/// Retrieve the SSH public keys that a GitHub user has uploaded (which GitHub publishes to
+/// anyone, so this doesn't need the user to authorize us).
+///
+pub fn public_keys(username: &str) -> Result<Vec<String>, Error> {
+    OAuthClient::new(crate::getenv("CLOWDER_GH_CLIENT_ID")?)?.public_keys(username)
+}
+
 /// A GitHub OAuth client.
 struct OAuthClient {
     /// Client ID: identifies the OAuth application.
@@ -81,6 +138,13 @@ impl UserData {
     }
 }
 
+/// An SSH public key that a GitHub user has uploaded, retrieved from the GitHub API.
+#[derive(Clone, Debug, RustcDecodable)]
+struct KeyData {
+    /// The key in OpenSSH format (with no comment).
+    key: String,
+}
+
 impl OAuthClient {
     pub fn new<S: Into<String>>(id: S) -> Result<OAuthClient, Error> {
         let tls_connector = hyper::net::HttpsConnector::new(NativeTlsClient::new()?);
@@ -120,6 +184,17 @@ impl OAuthClient {
             .expect("UNREACHABLE: self.user was just set; should not be None")
     }
 
+    pub fn public_keys(&self, username: &str) -> Result<Vec<String>, Error> {
+        let mut url = Url::parse("https://api.github.com/users").expect("bad GitHub URI");
+        url.path_segments_mut()
+            .expect("GitHub URI cannot be a base")
+            .push(username)
+            .push("keys");
+
+        let keys: Vec<KeyData> = self.get(url, None)?;
+        Ok(keys.into_iter().map(|k| k.key).collect())
+    }
+
     fn access_token(&mut self) -> Result<&str, Error> {
         if let Some(ref token) = self.token {
             Ok(token)
@@ -134,15 +209,25 @@ impl OAuthClient {
         U: hyper::client::IntoUrl,
     {
         let access_token = self.access_token()?.to_string();
+        self.get(url, Some(access_token))
+    }
 
-        let mut response = self
+    /// Query the GitHub API, on behalf of a user if given an access token.
+    fn get<T, U>(&self, url: U, access_token: Option<String>) -> Result<T, Error>
+    where
+        T: Decodable,
+        U: hyper::client::IntoUrl,
+    {
+        let mut request = self
             .http
             .get(url)
-            .header(header::Authorization(header::Bearer {
-                token: access_token,
-            }))
-            .header(header::UserAgent(String::from("musec/clowder")))
-            .send()?;
+            .header(header::UserAgent(String::from("musec/clowder")));
+
+        if let Some(token) = access_token {
+            request = request.header(header::Authorization(header::Bearer { token: token }));
+        }
+
+        let mut response = request.send()?;
 
         let body = response_str(&mut response)?;
 
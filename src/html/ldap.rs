@@ -0,0 +1,253 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use db;
+use db::models::*;
+use diesel::Connection as DieselConnection;
+use ldap3::{ldap_escape, LdapConn, Scope, SearchEntry};
+use maud::*;
+use rocket::http::Cookies;
+use rocket::request::Form;
+use rocket::response::Redirect;
+use std::{env, io};
+
+use super::auth::{self, ClientInfo, SameOrigin};
+use super::bootstrap::Page;
+use super::route_prefix;
+use super::Error;
+
+/// The identity provider name that LDAP identities are stored under.
+pub const PROVIDER: &'static str = "ldap";
+
+///
+/// How to find users (and their groups) in an LDAP directory, configured from the environment
+/// (or `.env`):
+///
+/// `CLOWDER_LDAP_URL`
+/// : the directory server (e.g., `ldap://localhost:389`); LDAP login is disabled if unset
+///
+/// `CLOWDER_LDAP_BASE`, `CLOWDER_LDAP_FILTER`
+/// : where to search for users and how to find them: `{username}` in the filter is replaced by
+///   the (escaped) username (default: `(uid={username})`)
+///
+/// `CLOWDER_LDAP_USERNAME_ATTR`
+/// : the attribute that holds a user's canonical username, which identifies them to Clowder
+///   however they typed it when logging in (default: `uid`)
+///
+/// `CLOWDER_LDAP_BIND_DN`, `CLOWDER_LDAP_BIND_PASSWORD`
+/// : credentials to search the directory with (default: search anonymously)
+///
+/// `CLOWDER_LDAP_GROUP_BASE`, `CLOWDER_LDAP_GROUP_FILTER`, `CLOWDER_LDAP_GROUP_ATTR`
+/// : where to search for a user's groups, how to find them (`{dn}` and `{username}` are
+///   replaced) and which attribute names them (defaults: the user search base,
+///   `(|(member={dn})(memberUid={username}))` and `cn`)
+///
+struct Directory {
+    url: String,
+    base: String,
+    filter: String,
+    username_attr: String,
+    bind: Option<(String, String)>,
+    group_base: String,
+    group_filter: String,
+    group_attr: String,
+}
+
+/// A user whose password the directory has vouched for (with the directory's spelling of their
+/// username).
+struct DirectoryUser {
+    username: String,
+    groups: Vec<String>,
+}
+
+impl Directory {
+    fn from_env() -> Result<Directory, Error> {
+        let base = crate::getenv("CLOWDER_LDAP_BASE")?;
+        let var = |name: &str, default: &str| env::var(name).unwrap_or(default.to_string());
+
+        let bind = match env::var("CLOWDER_LDAP_BIND_DN") {
+            Ok(dn) => Some((dn, crate::getenv("CLOWDER_LDAP_BIND_PASSWORD")?)),
+            Err(_) => None,
+        };
+
+        Ok(Directory {
+            url: crate::getenv("CLOWDER_LDAP_URL")?,
+            filter: var("CLOWDER_LDAP_FILTER", "(uid={username})"),
+            username_attr: var("CLOWDER_LDAP_USERNAME_ATTR", "uid"),
+            bind,
+            group_base: var("CLOWDER_LDAP_GROUP_BASE", &base),
+            group_filter: var(
+                "CLOWDER_LDAP_GROUP_FILTER",
+                "(|(member={dn})(memberUid={username}))",
+            ),
+            group_attr: var("CLOWDER_LDAP_GROUP_ATTR", "cn"),
+            base,
+        })
+    }
+
+    ///
+    /// Check a username and password against the directory by finding the user's entry and
+    /// binding as it, then look up the groups that the user belongs to.
+    ///
+    fn authenticate(&self, username: &str, password: &str) -> Result<DirectoryUser, Error> {
+        let rejected = || Error::AuthError(String::from("invalid username or password"));
+
+        // An empty password would be an unauthenticated bind, which "succeeds" (RFC 4513, 5.1.2).
+        if username.is_empty() || password.is_empty() {
+            return Err(rejected());
+        }
+
+        let ldap = LdapConn::new(&self.url).map_err(ldap_error)?;
+
+        if let Some((ref dn, ref pw)) = self.bind {
+            ldap.simple_bind(dn, pw)
+                .and_then(|r| r.success())
+                .map_err(ldap_error)?;
+        }
+
+        let filter = self.filter.replace("{username}", &ldap_escape(username));
+        let (entries, _) = ldap
+            .search(&self.base, Scope::Subtree, &filter, vec![self.username_attr.as_str()])
+            .and_then(|r| r.success())
+            .map_err(ldap_error)?;
+
+        if entries.len() != 1 {
+            warn!["LDAP search for '{}' found {} entries", filter, entries.len()];
+            return Err(rejected());
+        }
+
+        let mut entry = SearchEntry::construct(entries.into_iter().next().unwrap());
+        let dn = entry.dn;
+
+        // Directories typically match usernames case-insensitively, but identities don't.
+        let username = match entry
+            .attrs
+            .remove(&self.username_attr)
+            .and_then(|values| values.into_iter().next())
+        {
+            Some(u) => u,
+            None => {
+                warn!["LDAP entry '{}' has no {} attribute", dn, self.username_attr];
+                return Err(rejected());
+            }
+        };
+
+        ldap.simple_bind(&dn, password)
+            .and_then(|r| r.success())
+            .map_err(|_| rejected())?;
+
+        let group_filter = self
+            .group_filter
+            .replace("{dn}", &ldap_escape(dn.as_str()))
+            .replace("{username}", &ldap_escape(username.as_str()));
+
+        let (entries, _) = ldap
+            .search(
+                &self.group_base,
+                Scope::Subtree,
+                &group_filter,
+                vec![self.group_attr.as_str()],
+            )
+            .and_then(|r| r.success())
+            .map_err(ldap_error)?;
+
+        let groups = entries
+            .into_iter()
+            .map(SearchEntry::construct)
+            .filter_map(|mut e| e.attrs.remove(&self.group_attr))
+            .flatten()
+            .collect();
+
+        let _ = ldap.unbind();
+
+        Ok(DirectoryUser { username, groups })
+    }
+}
+
+fn ldap_error(err: io::Error) -> Error {
+    Error::AuthError(format!["LDAP error: {}", err])
+}
+
+/// Is LDAP login enabled?
+pub fn configured() -> bool {
+    env::var("CLOWDER_LDAP_URL").is_ok()
+}
+
+/// The form that users log in with, for the login dialog.
+pub fn login_form() -> Markup {
+    html! {
+        form action={ (route_prefix()) "login/ldap" } method="post" {
+            div.form-group {
+                input.form-control type="text" name="username" placeholder="Username" /
+            }
+            div.form-group {
+                input.form-control type="password" name="password" placeholder="Password" /
+            }
+            button.btn.btn-primary type="submit" {
+                i.fa.fa-sign-in aria-hidden="true" {}
+                (PreEscaped("&nbsp;"))
+                "Sign in"
+            }
+        }
+    }
+}
+
+#[derive(FromForm)]
+pub struct LdapLoginForm {
+    username: String,
+    password: String,
+}
+
+///
+/// Log in with an LDAP username and password. There's no session to take a CSRF token from yet,
+/// so the form must come from one of our own pages instead (see `SameOrigin`); the other login
+/// providers are protected by matching their callbacks' `state` to the login in progress.
+///
+#[post("/login/ldap", data = "<form>")]
+pub fn login(
+    form: Form<LdapLoginForm>,
+    _origin: SameOrigin,
+    client: ClientInfo,
+    cookies: Cookies,
+) -> Result<Redirect, Page> {
+    ldap_login(form.username.trim(), &form.password, &client, cookies).map_err(Into::into)
+}
+
+///
+/// Log in a user whose password the directory accepts, first bringing their roles into line with
+/// their LDAP groups.
+///
+fn ldap_login(
+    username: &str,
+    password: &str,
+    client: &ClientInfo,
+    cookies: Cookies,
+) -> Result<Redirect, Error> {
+    let directory_user = Directory::from_env()?.authenticate(username, password)?;
+
+    let conn = db::establish_connection()?;
+    if let Ok(user) = Identity::user(PROVIDER, &directory_user.username, &conn) {
+        conn.transaction::<_, Error, _>(|| {
+            let before = user.audit_snapshot(&conn)?;
+            LdapGroupRole::sync_roles(&user, &directory_user.groups, &conn)?;
+
+            let after = user.audit_snapshot(&conn)?;
+            if after != before {
+                AuditEvent::build("user.roles", "user", user.id)
+                    .before(&before)
+                    .after(&after)
+                    .insert(&user, client.ip.clone(), &conn)?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    auth::identity_login(PROVIDER, &directory_user.username, client, &conn, cookies)
+}
@@ -51,6 +51,15 @@ impl<'a> From<&'a Reservation> for Link {
     }
 }
 
+impl<'a> From<&'a Team> for Link {
+    fn from(t: &Team) -> Link {
+        Link {
+            url: format!["{}team/{}", super::route_prefix(), t.name],
+            text: t.name.clone(),
+        }
+    }
+}
+
 impl<'a> From<&'a User> for Link {
     fn from(u: &User) -> Link {
         Link {
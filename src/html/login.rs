@@ -0,0 +1,242 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use db;
+use db::models::random_token;
+use maud::*;
+use rocket::http::{Cookie, Cookies, SameSite};
+use rocket::response::Redirect;
+use std::env;
+
+use super::auth::ClientInfo;
+use super::bootstrap::Page;
+use super::{auth, github, ldap, oidc, route_prefix};
+use super::Error;
+
+/// The name of the (encrypted) cookie that remembers a login in progress.
+static LOGIN_COOKIE_NAME: &'static str = "clowder_login";
+
+///
+/// Something that can vouch for a user's identity (e.g., GitHub or an OpenID Connect provider).
+///
+/// Logging in is a two-step process: we send the user to the provider's authorization URL, and
+/// the provider sends them back to us with a code that we exchange for the user's identity.
+///
+pub trait IdentityProvider {
+    /// A short name for this provider, used in URLs and stored with users' identities.
+    fn id(&self) -> &str;
+
+    /// The name of this provider, as shown to users.
+    fn label(&self) -> &str;
+
+    /// The Font Awesome icon to show alongside this provider's label.
+    fn icon(&self) -> &str {
+        "fa-sign-in"
+    }
+
+    /// Where to send a user who wants to log in with this provider.
+    fn authorization_url(&self, login: &LoginState) -> Result<String, Error>;
+
+    /// Exchange an authorization code for the provider's identifier for the user.
+    fn authenticate(&self, code: &str, login: &LoginState) -> Result<String, Error>;
+}
+
+/// Find all of the identity providers that are configured in the environment (or `.env`).
+pub fn providers() -> Vec<Box<dyn IdentityProvider>> {
+    let mut providers: Vec<Box<dyn IdentityProvider>> = Vec::new();
+
+    if let Some(gh) = github::GitHub::from_env() {
+        providers.push(Box::new(gh));
+    }
+
+    if let Ok(ids) = env::var("CLOWDER_OIDC_PROVIDERS") {
+        for id in ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+            match oidc::OidcProvider::from_env(id) {
+                Ok(p) => providers.push(Box::new(p)),
+                Err(e) => error!["Invalid OpenID Connect provider '{}': {}", id, e],
+            }
+        }
+    }
+
+    providers
+}
+
+///
+/// Every kind of identity that a user can log in with, as (provider ID, label) pairs: the
+/// identity providers above, plus LDAP if it is configured.
+///
+pub fn identity_sources() -> Vec<(String, String)> {
+    let mut sources = providers()
+        .iter()
+        .map(|p| (p.id().to_string(), p.label().to_string()))
+        .collect::<Vec<_>>();
+
+    if ldap::configured() {
+        sources.push((ldap::PROVIDER.to_string(), String::from("LDAP")));
+    }
+
+    sources
+}
+
+///
+/// Buttons for logging in with each identity provider (and an LDAP login form, if LDAP is
+/// configured), or `None` if there is no way to log in at all.
+///
+pub fn login_choices() -> Option<Markup> {
+    let providers = providers();
+    let ldap = ldap::configured();
+
+    if providers.is_empty() && !ldap {
+        return None;
+    }
+
+    Some(html! {
+        @for p in &providers {
+            p {
+                a.btn.btn-secondary.large href={ (route_prefix()) "login/" (p.id()) } {
+                    i class={ "fa " (p.icon()) } aria-hidden="true" {}
+                    (PreEscaped("&nbsp;"))
+                    "Sign in with " (p.label())
+                }
+            }
+        }
+
+        @if ldap {
+            @if !providers.is_empty() {
+                hr /
+            }
+            (ldap::login_form())
+        }
+    })
+}
+
+fn provider(id: &str) -> Result<Box<dyn IdentityProvider>, Error> {
+    providers()
+        .into_iter()
+        .find(|p| p.id() == id)
+        .ok_or_else(|| Error::BadRequest(format!["no such login provider: '{}'", id]))
+}
+
+///
+/// What we need to remember between sending a user to an identity provider and their return:
+/// an unguessable `state` to match the response to the request, a `nonce` to bind the resulting
+/// ID token to this login and a PKCE code verifier.
+///
+pub struct LoginState {
+    pub provider: String,
+    pub state: String,
+    pub nonce: String,
+    pub verifier: String,
+}
+
+impl LoginState {
+    fn new(provider: &str) -> LoginState {
+        LoginState {
+            provider: provider.to_string(),
+            state: random_token(32),
+            nonce: random_token(32),
+            verifier: random_token(64),
+        }
+    }
+
+    fn save(&self, cookies: &mut Cookies) {
+        let value = format!["{} {} {} {}", self.provider, self.state, self.nonce, self.verifier];
+
+        // The provider redirects back to us from another site, so a SameSite=Strict cookie
+        // (Rocket's default for private cookies) would never come back.
+        cookies.add_private(
+            Cookie::build(LOGIN_COOKIE_NAME, value)
+                .path("/")
+                .same_site(SameSite::Lax)
+                .http_only(true)
+                .finish(),
+        );
+    }
+
+    /// Retrieve (and forget) the state of the login in progress.
+    fn take(cookies: &mut Cookies) -> Result<LoginState, Error> {
+        let cookie = cookies
+            .get_private(LOGIN_COOKIE_NAME)
+            .ok_or_else(|| Error::AuthError(String::from("no login in progress")))?;
+
+        let login = {
+            let parts = cookie.value().split(' ').collect::<Vec<_>>();
+            if parts.len() != 4 {
+                return Err(Error::AuthError(String::from("invalid login cookie")));
+            }
+
+            LoginState {
+                provider: parts[0].to_string(),
+                state: parts[1].to_string(),
+                nonce: parts[2].to_string(),
+                verifier: parts[3].to_string(),
+            }
+        };
+
+        cookies.remove_private(cookie);
+        Ok(login)
+    }
+}
+
+#[get("/login/<id>")]
+pub fn login(id: String, mut cookies: Cookies) -> Result<Redirect, Page> {
+    let login = LoginState::new(&id);
+
+    provider(&id)
+        .and_then(|p| p.authorization_url(&login))
+        .map(|url| {
+            login.save(&mut cookies);
+            Redirect::to(url)
+        })
+        .map_err(Into::into)
+}
+
+#[get("/login/<id>/callback?<code>&<state>")]
+pub fn callback(
+    id: String,
+    code: String,
+    state: Option<String>,
+    client: ClientInfo,
+    cookies: Cookies,
+) -> Result<Redirect, Page> {
+    finish(&id, &code, state, &client, cookies).map_err(Into::into)
+}
+
+/// GitHub OAuth applications are configured with this (older) callback URL.
+#[get("/gh-callback?<code>&<state>")]
+pub fn github_callback(
+    code: String,
+    state: Option<String>,
+    client: ClientInfo,
+    cookies: Cookies,
+) -> Result<Redirect, Page> {
+    callback(String::from("github"), code, state, client, cookies)
+}
+
+/// Finish logging in: check the provider's response against the login in progress and, if the
+/// provider vouches for an identity, log in as (or request an account for) that identity.
+fn finish(
+    id: &str,
+    code: &str,
+    state: Option<String>,
+    client: &ClientInfo,
+    mut cookies: Cookies,
+) -> Result<Redirect, Error> {
+    let login = LoginState::take(&mut cookies)?;
+    if login.provider != id || state.as_ref() != Some(&login.state) {
+        return Err(Error::AuthError(String::from(
+            "login response does not match the login in progress",
+        )));
+    }
+
+    let p = provider(id)?;
+    let subject = p.authenticate(code, &login)?;
+
+    auth::identity_login(p.id(), &subject, client, &db::establish_connection()?, cookies)
+}
@@ -0,0 +1,33 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use maud::*;
+use pulldown_cmark::{html, Event, Parser};
+
+///
+/// User-supplied Markdown text, rendered as HTML.
+///
+/// Any raw HTML in the source is shown as text rather than being passed through, so users
+/// can't inject markup (or scripts) into the pages that display their notes.
+///
+pub struct Markdown<'a>(pub &'a str);
+
+impl<'a> Render for Markdown<'a> {
+    fn render(&self) -> Markup {
+        let events = Parser::new(self.0).map(|e| match e {
+            Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+            e => e,
+        });
+
+        let mut rendered = String::new();
+        html::push_html(&mut rendered, events);
+
+        PreEscaped(rendered)
+    }
+}
@@ -7,51 +7,95 @@
  * copied, modified, or distributed except according to those terms.
  */
 
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 use super::rocket;
-use chrono::{DateTime, Utc};
+use chrono;
+use chrono::{DateTime, FixedOffset, NaiveDate, Offset, Utc};
 use chrono_humanize::HumanTime;
+use chrono_tz::Tz;
 use db;
 use db::models::*;
+use db::recurrence::{self, Frequency, Recurrence};
+use db::ssh::PublicKey;
+use diesel;
+use diesel::pg::PgConnection as Connection;
+use diesel::Connection as DieselConnection;
 use hyper;
 use marksman_escape::Escape;
 use maud::*;
-use rocket::request::{FlashMessage, Form};
-use rocket::response::{Flash, Redirect};
+use rocket::request::{FlashMessage, LenientForm};
+use rocket::response::{content, Flash, Redirect};
 use rocket::{http, request, Catcher, Route};
 use rustc_serialize;
+use rustc_serialize::json::{Json, ToJson};
 use url;
 
 // We do, in fact, use FromFrom, but only in a rocket-codegen derivation.
 #[allow(unused_imports)]
 use rocket::request::FromForm;
 
+mod accounts;
+mod audit;
 mod auth;
 mod bootstrap;
+mod calendar;
 mod error;
 mod forms;
 mod github;
+mod ldap;
 mod link;
+mod login;
+mod markdown;
+mod oidc;
+mod pools;
+mod roles;
 mod static_files;
 mod tables;
+mod teams;
+mod timeline;
+mod waitlist;
 
 use crate::error::Error;
 use self::auth::AuthContext;
+pub use self::auth::csrf_fairing;
 use self::bootstrap::Page;
 use self::link::Link;
+use self::markdown::Markdown;
 use std::env;
 
 /// All of the routes that we can handle.
 pub fn all_routes() -> Vec<Route> {
     routes! {
         index,
-        github_callback, logout,
-        machine, machine_create, machines,
-        reservation, reservation_create_page, reservation_create,
+        accounts::accounts, accounts::approve, accounts::invitation, accounts::invite,
+        accounts::reject, accounts::request, accounts::request_page, accounts::revoke,
+        approvals, audit::audit, audit::audit_json, availability, availability_json,
+        calendar::all, calendar::machine, calendar::mine, calendar_regenerate,
+        impersonate_stop,
+        ldap::login, login::callback, login::github_callback, login::login, logout,
+        machine, machine_approval, machine_create, machines,
+        notification_dismiss,
+        pools::add_machine, pools::add_machine_rule, pools::add_pool_rule, pools::create,
+        pools::delete_machine_rule, pools::delete_pool_rule, pools::pool, pools::pools,
+        pools::remove_machine,
+        reservation, reservation_approve, reservation_authorized_keys, reservation_boot,
+        reservation_cancel, reservation_details, reservation_reject, reservation_team,
+        reservation_transfer,
+        reservation_create_page, reservation_create,
+        reservation_spec, reservation_spec_page,
+        reservation_group, reservation_group_create, reservation_group_end,
+        reservation_group_extend, reservation_group_page,
+        reservation_series, reservation_series_boot, reservation_series_cancel,
         reservation_end, reservation_end_confirm, reservations,
+        roles::add_ldap_group, roles::create, roles::delete, roles::delete_ldap_group, roles::role,
+        roles::roles, roles::update,
         static_files::static_css, static_files::static_images, static_files::static_js,
-        user, user_update, users,
+        teams::add_member, teams::create, teams::remove_member, teams::team, teams::teams,
+        timeline,
+        user, user_identity_add, user_identity_delete, user_impersonate, user_session_revoke,
+        user_ssh_key_add, user_ssh_key_delete, user_ssh_key_github, user_update, users,
+        waitlist::accept, waitlist::join, waitlist::waitlist, waitlist::withdraw,
     }
 }
 
@@ -96,28 +140,75 @@ where
     let mut nav_links = vec![
         bootstrap::NavItem::link(prefix("machines"), "Machines"),
         bootstrap::NavItem::link(prefix("reservations"), "Reservations"),
+        bootstrap::NavItem::link(prefix("teams"), "Teams"),
+        bootstrap::NavItem::link(prefix("timeline"), "Timeline"),
+        bootstrap::NavItem::link(prefix("waitlist"), "Waitlist"),
     ];
 
-    if let Ok(true) = user.can_alter_users(&auth.conn) {
+    let capabilities = user.capabilities(&auth.conn).unwrap_or_default();
+
+    if capabilities.contains(&Capability::AlterUsers) {
         nav_links.push(bootstrap::NavItem::link(prefix("users"), "Users"));
+        nav_links.push(bootstrap::NavItem::link(prefix("roles"), "Roles"));
+        nav_links.push(bootstrap::NavItem::link(prefix("accounts"), "Accounts"));
+    }
+
+    if capabilities.contains(&Capability::ApproveReservations) {
+        nav_links.push(bootstrap::NavItem::link(prefix("approvals"), "Approvals"));
+    }
+
+    if capabilities.contains(&Capability::ViewAudit) {
+        nav_links.push(bootstrap::NavItem::link(prefix("audit"), "Audit"));
     }
 
-    Page::new(title.into())
+    let page = Page::new(title.into())
         .link_prefix(route_prefix)
         .nav(nav_links)
         .user(&user.username, &user.name)
+        .csrf_token(auth.csrf_token());
+
+    match auth.impersonator {
+        Some(ref admin) => page.impersonation(&admin.username, &user.username),
+        None => page,
+    }
 }
 
 #[get("/")]
-fn index(auth: AuthContext) -> Result<Page, Error> {
-    let machines = FullMachine::all(&auth.conn)?;
+fn index(auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
+    let machines = FullMachine::all(&auth.conn)?
+        .into_iter()
+        .filter(|m| acl.can_view(m.machine()))
+        .collect::<Vec<_>>();
+
+    // Pass on lapsed waitlist offers before showing anyone their (new) offers.
+    WaitlistEntry::expire_offers(&auth.conn)?;
+    let notifications = Notification::unread(&auth.user, &auth.conn)?;
 
     let reservations = Reservation::all(true, &auth.conn)?
         .into_iter()
+        .filter(|&(_, ref m, _)| acl.can_view(m))
         .map(|(r, m, u)| (r, Some(m), Some(u)))
         .collect();
 
-    Ok(page("Clowder", &auth).content(html! {
+    Ok(page("Clowder", &auth).flash(flash).content(html! {
+        @for n in &notifications {
+            (bootstrap::callout("info", n.created_at.format(DATE_FORMAT).to_string(), html! {
+                p {
+                    (n.message)
+                    @if let Some(ref link) = n.link {
+                        " " a href={ (route_prefix()) (link) } { "Details" }
+                    }
+                }
+                form action={ (route_prefix()) "notification/" (n.id) "/dismiss" }
+                     method="post" {
+                    (csrf)
+                    (forms::SubmitButton::new().label("Dismiss"))
+                }
+            }))
+        }
+
         div.row {
             div class="col-md-6" {
                 h4 { "Machine inventory" }
@@ -143,30 +234,77 @@ fn index(auth: AuthContext) -> Result<Page, Error> {
     }))
 }
 
-#[get("/gh-callback?<code>")]
-fn github_callback(code: String, cookies: http::Cookies) -> Result<Page, Redirect> {
-    match auth::github_callback(code, cookies) {
-        Ok(_) => Err(Redirect::to(route_prefix())),
-        Err(e) => Ok(e.into()),
+#[post("/notification/<id>/dismiss")]
+fn notification_dismiss(id: i32, auth: AuthContext) -> Result<Redirect, Error> {
+    let n = Notification::get(id, &auth.conn)?;
+    if n.user_id != auth.user.id {
+        return Err(Error::NotAuthorized(format!["dismiss notification {}", id]));
     }
+
+    n.mark_read(&auth.conn)?;
+    Ok(Redirect::to(route_prefix()))
 }
 
-#[get("/logout")]
-fn logout(_auth: AuthContext, cookies: http::Cookies) -> Redirect {
-    auth::logout(cookies);
-    Redirect::to(route_prefix().to_string())
+#[post("/logout")]
+fn logout(auth: AuthContext, cookies: http::Cookies) -> Result<Redirect, Error> {
+    auth::logout(auth, cookies)?;
+    Ok(Redirect::to(route_prefix().to_string()))
+}
+
+#[post("/calendar/regenerate")]
+fn calendar_regenerate(auth: AuthContext) -> Result<Flash<Redirect>, Error> {
+    let replaced = auth.conn.transaction::<_, Error, _>(|| {
+        let replaced = CalendarToken::for_user(&auth.user, &auth.conn)?.is_some();
+        CalendarToken::regenerate(&auth.user, &auth.conn)?;
+        auth.audit(AuditEvent::build("calendar.regenerate", "user", auth.user.id))?;
+        Ok(replaced)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}user/{}", route_prefix(), auth.user.username]),
+        "info",
+        if replaced {
+            "Replaced calendar feed links; the old links no longer work"
+        } else {
+            "Created calendar feed links"
+        },
+    ))
 }
 
 #[get("/machine/<machine_name>")]
-fn machine(machine_name: String, auth: AuthContext) -> Result<Page, Error> {
+fn machine(
+    machine_name: String,
+    auth: AuthContext,
+    flash: Option<FlashMessage>,
+) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
     let conn = &auth.conn;
+    let acl = AccessControl::for_user(&auth.user, conn)?;
 
     let m = FullMachine::with_name(&machine_name, conn)?;
+    let access = acl.access(m.machine());
+    if !access.view {
+        return Err(Error::NotAuthorized(format!["view machine {}", m.name()]));
+    }
+
+    let pool = match m.machine().pool_id {
+        Some(p) => Some(Pool::get(p, conn)?),
+        None => None,
+    };
+    let rule_action = format!["{}machine/{}/rule", route_prefix(), m.name()];
+    let rules = pools::rule_table(
+        &m.machine().access_rules(conn)?,
+        if access.administer { Some(rule_action.as_str()) } else { None },
+        &csrf,
+        conn,
+    )?;
+
+    let devices = m.machine().devices(conn)?;
     let disks = m.machine().disks(conn)?;
     let nics = m.machine().nics(conn)?;
 
     Ok(
-        page(format!["Clowder: {}", m.name()], &auth).content(html! {
+        page(format!["Clowder: {}", m.name()], &auth).flash(flash).content(html! {
             div.row { h2 { (m.name()) } }
 
             div.row {
@@ -175,14 +313,20 @@ fn machine(machine_name: String, auth: AuthContext) -> Result<Page, Error> {
                         dt { "Processor(s)" }
                         dd {
                             ul {
-                                li {
-                                    (Link::from(m.processor()))
-                                    ": "
-                                    (Link::from(m.microarchitecture())) " " (m.architecture().name)
-                                    ", "
-                                    (m.cores()) " cores, " (m.freq_ghz()) " GHz"
+                                @for ref s in m.sockets() {
+                                    li {
+                                        (s.count) " × "
+                                        (Link::from(&s.processor))
+                                        ": "
+                                        (Link::from(&s.microarch)) " " (s.arch.name)
+                                        ", "
+                                        (s.processor.cores) " cores, "
+                                        (s.processor.threads) " threads, "
+                                        (s.processor.freq_ghz) " GHz"
+                                    }
                                 }
                             }
+                            (m.cores()) " cores, " (m.threads()) " threads in total"
                         }
 
                         dt { "Memory" }
@@ -205,13 +349,67 @@ fn machine(machine_name: String, auth: AuthContext) -> Result<Page, Error> {
                                 }
                             }
                         }
+
+                        @if !devices.is_empty() {
+                            dt { "Devices" }
+                            dd {
+                                ul {
+                                    @for ref device in devices {
+                                        li { (device.short_description()) }
+                                    }
+                                }
+                            }
+                        }
+
+                        dt { "Reservations" }
+                        dd {
+                            @if m.machine().requires_approval {
+                                "Require approval"
+                            } @else {
+                                "Take effect immediately"
+                            }
+
+                            @if access.administer {
+                                form action={ (route_prefix()) "machine/" (m.name()) "/approval" }
+                                     method="post" {
+                                    (csrf)
+                                    input type="hidden" name="required"
+                                        value=(!m.machine().requires_approval) /
+                                    (forms::SubmitButton::new().label(
+                                        if m.machine().requires_approval {
+                                            "Stop requiring approval"
+                                        } else {
+                                            "Require approval"
+                                        }
+                                    ))
+                                }
+                            }
+                        }
                     }
 
+                    @if access.reserve {
+                        p {
+                            a href={ (route_prefix()) "reservation/create/?machine=" (m.name()) } {
+                                "Reserve this machine"
+                            }
+                        }
+                    }
+
+                    h4 { "Access" }
                     p {
-                        a href={ (route_prefix()) "reservation/create/?machine=" (m.name()) } {
-                            "Reserve this machine"
+                        @if let Some(ref p) = pool {
+                            "In pool " a href={ (route_prefix()) "pool/" (p.name) } { (p.name) }
+                            ", whose access rules also apply."
+                        } @else {
+                            "Not in any "
+                            a href={ (route_prefix()) "pools" } { "pool" }
+                            "."
                         }
                     }
+                    (rules)
+                    @if access.administer {
+                        (pools::rule_form(&rule_action, &csrf))
+                    }
                 }
 
                 div class="col-md-5" {
@@ -240,43 +438,121 @@ fn machine(machine_name: String, auth: AuthContext) -> Result<Page, Error> {
     )
 }
 
+#[derive(Debug, FromForm)]
+struct ApprovalRequirementForm {
+    required: bool,
+}
+
+#[post("/machine/<machine_name>/approval", data = "<form>")]
+fn machine_approval(
+    machine_name: String,
+    form: LenientForm<ApprovalRequirementForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let m = Machine::with_name(&machine_name, &auth.conn)?;
+    if !AccessControl::for_user(&auth.user, &auth.conn)?.can_administer(&m) {
+        return Err(Error::NotAuthorized(format!["administer machine {}", m.name]));
+    }
+
+    let before = m.clone();
+    let m = auth.conn.transaction::<_, Error, _>(|| {
+        let m = m.set_requires_approval(form.required, &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("machine.approval", "machine", m.id)
+                .before(&before)
+                .after(&m),
+        )?;
+        Ok(m)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}machine/{}", route_prefix(), m.name]),
+        "info",
+        if m.requires_approval {
+            format!["New reservations of {} now require approval", m.name]
+        } else {
+            format!["New reservations of {} no longer require approval", m.name]
+        },
+    ))
+}
+
 #[derive(Debug, FromForm)]
 struct NewMachineForm {
     name: String,
     processor: i32,
+    sockets: i32,
     memory_gb: i32,
+    requires_approval: bool,
 }
 
 #[post("/machine/create", data = "<form>")]
-fn machine_create(form: Form<NewMachineForm>, auth: AuthContext) -> Result<Redirect, Error> {
-    MachineBuilder::new(form.name.clone())
-        .processor(&Processor::get(form.processor, &auth.conn)?)
-        .memory_gb(form.memory_gb)
-        .insert(&auth.conn)
-        .map(|m| Redirect::to(format!["{}machine/{}", route_prefix(), m.name]))
-        .map_err(Error::DatabaseError)
+fn machine_create(form: LenientForm<NewMachineForm>, auth: AuthContext) -> Result<Redirect, Error> {
+    if form.sockets < 1 {
+        return Err(Error::BadRequest(String::from(
+            "a machine's processor must occupy at least one socket",
+        )));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        let m = MachineBuilder::new(form.name.clone())
+            .processor(&Processor::get(form.processor, &auth.conn)?, form.sockets)
+            .memory_gb(form.memory_gb)
+            .requires_approval(form.requires_approval)
+            .insert(&auth.conn)?;
+        auth.audit(AuditEvent::build("machine.create", "machine", m.id).after(&m))?;
+        Ok(Redirect::to(format!["{}machine/{}", route_prefix(), m.name]))
+    })
 }
 
-#[get("/machines")]
-fn machines(auth: AuthContext) -> Result<Page, Error> {
-    let machine_creator = auth.user.can_create_machines(&auth.conn)?;
+#[get("/machines?<q>")]
+fn machines(q: Option<String>, auth: AuthContext) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let machine_creator = auth.user.can(Capability::CreateMachines, &auth.conn)?;
     let processor_options = Processor::all(&auth.conn)?
         .iter()
         .map(|p| forms::SelectOption::new(p.id.to_string(), p.name.clone()))
         .collect::<Vec<_>>();
 
-    FullMachine::all(&auth.conn)
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
+    let query = q.unwrap_or(String::new());
+    let inventory = if query.is_empty() {
+        FullMachine::all(&auth.conn)
+    } else {
+        FullMachine::search(&query, &auth.conn)
+    };
+
+    inventory
         .map_err(Error::DatabaseError)
+        .map(|machines| {
+            machines
+                .into_iter()
+                .filter(|m| acl.can_view(m.machine()))
+                .collect::<Vec<_>>()
+        })
         .map(|machines| tables::MachineTable::new(machines))
         .map(|table| {
             html! {
                 h2 { "Current inventory" }
+
+                p {
+                    "Access to machines is controlled by "
+                    a href={ (route_prefix()) "pools" } { "machine pools" }
+                    "."
+                }
+
+                form action={ (route_prefix()) "machines" } method="get" {
+                    (forms::Input::new("q").value(query.clone()).size(30))
+                    " "
+                    (forms::SubmitButton::new().label("Search"))
+                }
+
                 (table)
 
                 @if machine_creator {
                     h2 { "Add new machine" }
 
                     form action={ (route_prefix()) "machine/create" } method="post" {
+                        (csrf)
                         table {
                             tr {
                                 th { "Name" }
@@ -289,10 +565,21 @@ fn machines(auth: AuthContext) -> Result<Page, Error> {
                                                    .set_options(processor_options))
                                 }
                             }
+                            tr {
+                                th { "Sockets" }
+                                td { (forms::Input::new("sockets").value("1").size(3)) }
+                            }
                             tr {
                                 th { "Memory" }
                                 td { (forms::Input::new("memory_gb")) " GiB" }
                             }
+                            tr {
+                                th { "Approval" }
+                                td {
+                                    input type="checkbox" name="requires_approval" /
+                                    " Reservations require approval"
+                                }
+                            }
                             tr {
                                 th /
                                 td { (forms::SubmitButton::new().label("Add to inventory")) }
@@ -307,12 +594,76 @@ fn machines(auth: AuthContext) -> Result<Page, Error> {
 
 #[get("/reservation/<id>")]
 fn reservation(id: i32, auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
     let (r, machine, user) = Reservation::get(id, &auth.conn)?;
 
+    // Reservation holders can always see their own reservations, even if they have since lost
+    // access to the machine.
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
+    if r.user_id != auth.user.id && !acl.can_view(&machine) {
+        return Err(Error::NotAuthorized(format!["view reservation {}", id]));
+    }
+
+    let may_manage = r.may_manage(&auth.user, &auth.conn)?;
+
     let can_end = match (r.scheduled_start, r.actual_end) {
-        (s, None) if s <= Utc::now() => true,
+        (s, None) if s <= Utc::now() => may_manage && !r.is_cancelled(),
         (_, _) => false,
     };
+    let can_cancel = may_manage && r.scheduled_start > Utc::now() && !r.is_closed();
+    let can_edit = may_manage && !r.is_closed();
+
+    let team = match r.team_id {
+        Some(t) => Some(Team::get(t, &auth.conn)?),
+        None => None,
+    };
+    let team_options = if can_edit {
+        team_options(&auth.user, team.as_ref(), &auth.conn)?
+    } else {
+        Vec::new()
+    };
+
+    let cancelled_by = match r.cancelled_by {
+        Some(uid) => Some(User::get(uid, &auth.conn)?),
+        None => None,
+    };
+    let reviewer = match r.reviewed_by {
+        Some(uid) => Some(User::get(uid, &auth.conn)?),
+        None => None,
+    };
+    let boot_served = r.boot_config().is_some();
+
+    let pxe = r.pxe_path.clone().unwrap_or_default();
+    let nfs = r.nfs_root.clone().unwrap_or_default();
+    let authorized_keys = format![
+        "{}{}reservation/keys/{}",
+        env::var("CLOWDER_URL").unwrap_or_default().trim_right_matches('/'),
+        route_prefix(),
+        r.keys_token
+    ];
+    let can_transfer = !r.is_closed() && may_transfer(&r, &auth)?;
+    let transfer_options = if can_transfer {
+        User::all(&auth.conn)?
+            .into_iter()
+            .filter(|u| u.id != r.user_id)
+            .map(|u| forms::SelectOption::new(u.username, u.name))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut previous_owners = Vec::new();
+    for t in r.transfers(&auth.conn)? {
+        previous_owners.push((
+            User::get(t.from_user_id, &auth.conn)?,
+            User::get(t.transferred_by, &auth.conn)?,
+            t.transferred_at,
+        ));
+    }
+
+    let purpose = r.purpose.clone();
+    let notes = r.notes.clone().unwrap_or_default();
+    let project = r.project.clone().unwrap_or_default();
 
     Ok(page(format!["Clowder: reservation {}", r.id], &auth)
         .flash(flash)
@@ -321,7 +672,48 @@ fn reservation(id: i32, auth: AuthContext, flash: Option<FlashMessage>) -> Resul
 
             table.lefty {
                 tr { th { "User" }       td { (Link::from(&user)) } }
+                @if !previous_owners.is_empty() {
+                    tr {
+                        th { "Previous owners" }
+                        td {
+                            ul.list-unstyled {
+                                @for &(ref from, ref by, when) in &previous_owners {
+                                    li {
+                                        (Link::from(from)) " (until " (when)
+                                        @if by.id != from.id {
+                                            ", transferred by " (Link::from(by))
+                                        }
+                                        ")"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                @if let Some(ref t) = team {
+                    tr { th { "Team" } td { (Link::from(t)) } }
+                }
                 tr { th { "Machine" }    td { (Link::from(&machine)) } }
+                tr { th { "Purpose" }    td { (r.purpose) } }
+                @if let Some(ref project) = r.project {
+                    tr { th { "Project" } td { (project) } }
+                }
+                @if let Some(group) = r.group_id {
+                    tr {
+                        th { "Group" }
+                        td {
+                            a href={ (route_prefix()) "reservation/group/" (group) } { (group) }
+                        }
+                    }
+                }
+                @if let Some(series) = r.series_id {
+                    tr {
+                        th { "Series" }
+                        td {
+                            a href={ (route_prefix()) "reservation/series/" (series) } { (series) }
+                        }
+                    }
+                }
                 tr { th { "Starts" }     td { (r.scheduled_start) } }
                 tr {
                     th { "Ends" }
@@ -341,6 +733,31 @@ fn reservation(id: i32, auth: AuthContext, flash: Option<FlashMessage>) -> Resul
                         })
                     }
                 }
+                @if r.pending && !r.is_cancelled() {
+                    tr { th { "Status" } td { strong { "Awaiting approval" } } }
+                }
+                @if let (Some(when), false) = (r.reviewed_at, r.is_cancelled()) {
+                    tr {
+                        th { "Approved" }
+                        td {
+                            (when)
+                            @if let Some(ref u) = reviewer { " by " (Link::from(u)) }
+                            @if let Some(ref comment) = r.review_comment { ": " (comment) }
+                        }
+                    }
+                }
+                @if let Some(when) = r.cancelled_at {
+                    tr {
+                        th { "Cancelled" }
+                        td {
+                            (when)
+                            @if let Some(ref u) = cancelled_by { " by " (Link::from(u)) }
+                        }
+                    }
+                    @if let Some(ref reason) = r.cancel_reason {
+                        tr { th { "Reason" } td { (reason) } }
+                    }
+                }
                 tr {
                     th { "NFS root" }
                     td {
@@ -359,6 +776,26 @@ fn reservation(id: i32, auth: AuthContext, flash: Option<FlashMessage>) -> Resul
                         })
                     }
                 }
+                @if !boot_served && can_edit {
+                    tr {
+                        th {}
+                        td { em { "Boot configuration will not be served until approved." } }
+                    }
+                }
+                @if boot_served && may_manage {
+                    tr {
+                        th { "SSH keys" }
+                        td {
+                            a href=(authorized_keys) { code { (authorized_keys) } }
+                            br /
+                            small {
+                                "Once the reservation starts, the booted system can fetch its "
+                                "users' SSH keys from here (e.g., into "
+                                code { "~/.ssh/authorized_keys" } ")."
+                            }
+                        }
+                    }
+                }
                 @if can_end {
                     tr {
                         th {}
@@ -369,92 +806,1518 @@ fn reservation(id: i32, auth: AuthContext, flash: Option<FlashMessage>) -> Resul
                         }
                     }
                 }
+                @if can_cancel {
+                    tr {
+                        th {}
+                        td {
+                            form action={ (route_prefix()) "reservation/" (r.id) "/cancel" }
+                                 method="post" {
+                                (csrf)
+                                "Reason: "
+                                (forms::Input::new("reason").size(30))
+                                " "
+                                (forms::SubmitButton::new().label(
+                                    if r.series_id.is_some() {
+                                        "Cancel this occurrence"
+                                    } else {
+                                        "Cancel reservation"
+                                    }
+                                ))
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if !notes.is_empty() {
+                h4 { "Notes" }
+                div.notes { (Markdown(&notes)) }
+            }
+
+            @if can_edit {
+                h4 { "Boot configuration" }
+                form action={ (route_prefix()) "reservation/" (r.id) "/boot" }
+                     method="post" {
+                    (csrf)
+                    table.lefty {
+                        tr {
+                            th { "PXE path" }
+                            td { (forms::Input::new("pxe").value(pxe).size(45)) }
+                        }
+                        tr {
+                            th { "NFS root" }
+                            td { (forms::Input::new("nfs").value(nfs).size(45)) }
+                        }
+                        tr {
+                            th /
+                            td { (forms::SubmitButton::new().label("Update")) }
+                        }
+                    }
+                }
+            }
+
+            @if can_edit {
+                h4 { "Team" }
+                form action={ (route_prefix()) "reservation/" (r.id) "/team" }
+                     method="post" {
+                    (csrf)
+                    "Share this reservation with "
+                    (forms::Select::new("team").set_options(team_options))
+                    " "
+                    (forms::SubmitButton::new().label("Share"))
+                }
+            }
+
+            @if can_transfer {
+                h4 { "Transfer" }
+                form action={ (route_prefix()) "reservation/" (r.id) "/transfer" }
+                     method="post" {
+                    (csrf)
+                    "Give this reservation to "
+                    (forms::Select::new("user").set_options(transfer_options))
+                    " "
+                    (forms::SubmitButton::new().label("Transfer"))
+                }
+            }
+
+            @if may_manage {
+                h4 { "Description" }
+                form action={ (route_prefix()) "reservation/" (r.id) "/details" }
+                     method="post" {
+                    (csrf)
+                    table.lefty {
+                        tr {
+                            th { "Purpose" }
+                            td { (forms::Input::new("purpose").value(purpose).size(45)) }
+                        }
+                        tr {
+                            th { "Project" }
+                            td { (forms::Input::new("project").value(project).size(15)) }
+                        }
+                        tr {
+                            th { "Notes" }
+                            td { textarea name="notes" rows="6" cols="45" { (notes) } }
+                        }
+                        tr {
+                            th /
+                            td { (forms::SubmitButton::new().label("Update description")) }
+                        }
+                    }
+                }
             }
         }))
 }
 
-#[derive(Debug, FromForm)]
-struct ReservationForm {
-    user: String,
-    machine: String,
-    dates: String,
-    pxe: String,
-    nfs: String,
+/// A reservation may be transferred by its owner or by a user administrator.
+fn may_transfer(r: &Reservation, auth: &AuthContext) -> Result<bool, Error> {
+    Ok(r.user_id == auth.user.id || auth.user.can(Capability::AlterUsers, &auth.conn)?)
 }
 
-#[post("/reservation/create", data = "<res>")]
-fn reservation_create(res: Form<ReservationForm>, auth: AuthContext) -> Result<Redirect, Error> {
-    let user = User::with_username(&res.user, &auth.conn)?;
-    let machine = Machine::with_name(&res.machine, &auth.conn)?;
+/// Check that a user's access to a machine (via its pool or its own access rules) lets them
+/// reserve it.
+fn check_may_reserve(user: &User, m: &Machine, c: &Connection) -> Result<(), Error> {
+    if AccessControl::for_user(user, c)?.can_reserve(m) {
+        Ok(())
+    } else {
+        Err(Error::NotAuthorized(format![
+            "reserve {} on behalf of {}",
+            m.name, user.username
+        ]))
+    }
+}
 
-    let dates: Vec<&str> = res.dates.split(" - ").collect();
-    if dates.len() != 2 {
-        return Err(Error::BadRequest(format![
-            "expected two dates, not '{:?}'",
-            dates
-        ]));
+/// Check that the current user may end, extend, cancel or reconfigure a reservation.
+fn check_may_manage(r: &Reservation, auth: &AuthContext) -> Result<(), Error> {
+    if r.may_manage(&auth.user, &auth.conn)? {
+        Ok(())
+    } else {
+        Err(Error::NotAuthorized(format!["manage reservation {}", r.id]))
     }
+}
 
-    let start = DateTime::parse_from_str(dates[0], "%H:%M%:z %e %b %Y")?;
-    let end = DateTime::parse_from_str(dates[1], "%H:%M%:z %e %b %Y")?;
+/// Options for choosing which of a user's teams (if any) to share a reservation with.
+fn team_options(
+    user: &User,
+    current: Option<&Team>,
+    c: &Connection,
+) -> Result<Vec<forms::SelectOption>, Error> {
+    let mut options = vec![forms::SelectOption::new("", "(no team)")];
+    options.extend(user.teams(c)?.into_iter().map(|t| {
+        let selected = current.map(|c| c.id == t.id).unwrap_or(false);
+        forms::SelectOption::new(t.name.clone(), t.name).selected(selected)
+    }));
+
+    Ok(options)
+}
 
-    let mut rb = ReservationBuilder::new(&user, &machine, start.with_timezone(&Utc));
-    rb.end(end.with_timezone(&Utc));
+///
+/// Look up the team named in a reservation form (if any), checking that the current user is
+/// allowed to share reservations with it.
+///
+fn chosen_team(name: &str, auth: &AuthContext) -> Result<Option<Team>, Error> {
+    if name.is_empty() {
+        return Ok(None);
+    }
 
-    if res.pxe.len() > 0 {
-        rb.pxe(res.pxe.clone());
+    let team = Team::with_name(name, &auth.conn)?;
+    if !team.has_member(&auth.user, &auth.conn)?
+        && !auth.user.can(Capability::AlterUsers, &auth.conn)?
+    {
+        return Err(Error::NotAuthorized(format![
+            "share reservations with team {}",
+            team.name
+        ]));
     }
-    if res.pxe.len() > 0 {
-        rb.nfs(res.nfs.clone());
+
+    Ok(Some(team))
+}
+
+#[derive(Debug, FromForm)]
+struct TeamForm {
+    team: String,
+}
+
+#[post("/reservation/<id>/team", data = "<form>")]
+fn reservation_team(
+    id: i32,
+    form: LenientForm<TeamForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let (r, _, _) = Reservation::get(id, &auth.conn)?;
+    check_may_manage(&r, &auth)?;
+    if r.is_closed() {
+        return Err(Error::BadRequest(format![
+            "reservation {} has already ended or been cancelled",
+            id
+        ]));
     }
 
-    rb.insert(&auth.conn)
-        .map(|r| Redirect::to(format!["{}reservation/{}", route_prefix(), r.id]))
-        .map_err(Error::DatabaseError)
+    let team = chosen_team(&form.team, &auth)?;
+    let before = r.to_json();
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        let r = r.set_team(team.as_ref(), &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("reservation.team", "reservation", r.id)
+                .before(&before)
+                .after(&r),
+        )?;
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/{}", route_prefix(), id]),
+        "info",
+        match team {
+            Some(t) => format!["Shared reservation {} with team {}", id, t.name],
+            None => format!["Reservation {} is no longer shared with a team", id],
+        },
+    ))
 }
 
-#[get("/reservation/create?<machine>")]
-fn reservation_create_page(machine: Option<String>, auth: AuthContext) -> Result<Page, Error> {
-    let users = User::all(&auth.conn)?;
-    let user_options = users
-        .iter()
-        .map(|ref u| {
-            forms::SelectOption::new(u.username.clone(), u.name.clone())
-                .selected(u.username == auth.user.username)
-        })
-        .collect::<Vec<_>>();
+#[derive(Debug, FromForm)]
+struct TransferForm {
+    user: String,
+}
 
-    let machines = Machine::all(&auth.conn)?;
-    let machine_options = machines
-        .iter()
-        .map(|ref m| {
-            forms::SelectOption::new(m.name.clone(), m.name.clone()).selected(
-                if let &Some(ref name) = &machine {
-                    name == &m.name
-                } else {
-                    false
-                },
-            )
-        })
-        .collect::<Vec<_>>();
+#[post("/reservation/<id>/transfer", data = "<form>")]
+fn reservation_transfer(
+    id: i32,
+    form: LenientForm<TransferForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let (r, machine, from) = Reservation::get(id, conn)?;
 
-    Ok(page("Create reservation", &auth).content(html! {
-        h2 { "Reserve a machine" }
+    if !may_transfer(&r, &auth)? {
+        return Err(Error::NotAuthorized(format!["transfer reservation {}", id]));
+    }
+    if r.is_closed() {
+        return Err(Error::BadRequest(format![
+            "reservation {} has already ended or been cancelled",
+            id
+        ]));
+    }
 
-        form action="." method="post" {
-            table {
-                tr {
-                    th { "User" }
-                    td { (forms::Select::new("user").set_options(user_options)) }
-                }
-                tr {
-                    th { "Machine" }
+    let to = User::with_username(&form.user, conn)?;
+    if to.id == from.id {
+        return Err(Error::BadRequest(format![
+            "reservation {} already belongs to {}",
+            id, to.username
+        ]));
+    }
+
+    let before = r.to_json();
+    let r = conn.transaction::<_, Error, _>(|| {
+        // The recipient must be able to make this reservation themselves.
+        if !AccessControl::for_user(&to, conn)?.can_reserve(&machine) {
+            return Err(Error::NotAuthorized(format![
+                "{} may not reserve {}",
+                to.username, machine.name
+            ]));
+        }
+        to.reservation_policy(conn)?
+            .check_replacing(&to, &[(r.start(), r.busy_until())], &[r.id], conn)?;
+
+        let r = r.transfer(&to, &auth.user, conn)?;
+        let link = Some(format!["reservation/{}", r.id]);
+
+        Notification::insert(
+            from.id,
+            format![
+                "Reservation {} of {} was transferred to {} by {}",
+                r.id, machine.name, to.username, auth.user.username
+            ],
+            link.clone(),
+            conn,
+        )?;
+        Notification::insert(
+            to.id,
+            format![
+                "{} transferred reservation {} of {} to you",
+                auth.user.username, r.id, machine.name
+            ],
+            link,
+            conn,
+        )?;
+
+        auth.audit(
+            AuditEvent::build("reservation.transfer", "reservation", r.id)
+                .before(&before)
+                .after(&r),
+        )?;
+
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/{}", route_prefix(), r.id]),
+        "info",
+        format!["Transferred reservation {} to {}", r.id, to.username],
+    ))
+}
+
+#[derive(Debug, FromForm)]
+struct DetailsForm {
+    purpose: String,
+    notes: String,
+    project: String,
+}
+
+#[post("/reservation/<id>/details", data = "<form>")]
+fn reservation_details(
+    id: i32,
+    form: LenientForm<DetailsForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let details = ReservationDetails::parse(&form.purpose, &form.notes, &form.project)?;
+    let (r, _, _) = Reservation::get(id, &auth.conn)?;
+    check_may_manage(&r, &auth)?;
+
+    let before = r.to_json();
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        let r = r.set_details(details.purpose, details.notes, details.project, &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("reservation.details", "reservation", r.id)
+                .before(&before)
+                .after(&r),
+        )?;
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/{}", route_prefix(), id]),
+        "info",
+        format!["Updated description of reservation {}", id],
+    ))
+}
+
+#[derive(Debug, FromForm)]
+struct BootPathForm {
+    pxe: String,
+    nfs: String,
+}
+
+impl BootPathForm {
+    fn pxe(&self) -> Option<String> {
+        Some(self.pxe.trim().to_string()).filter(|s| !s.is_empty())
+    }
+
+    fn nfs(&self) -> Option<String> {
+        Some(self.nfs.trim().to_string()).filter(|s| !s.is_empty())
+    }
+}
+
+#[post("/reservation/<id>/boot", data = "<form>")]
+fn reservation_boot(
+    id: i32,
+    form: LenientForm<BootPathForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let (r, _, _) = Reservation::get(id, &auth.conn)?;
+    check_may_manage(&r, &auth)?;
+    if r.is_closed() {
+        return Err(Error::BadRequest(format![
+            "reservation {} has already ended or been cancelled",
+            id
+        ]));
+    }
+
+    let before = r.to_json();
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        let r = r.set_boot_paths(form.pxe(), form.nfs(), &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("reservation.boot", "reservation", r.id)
+                .before(&before)
+                .after(&r),
+        )?;
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/{}", route_prefix(), id]),
+        "info",
+        format!["Updated boot configuration for reservation {}", id],
+    ))
+}
+
+///
+/// The SSH keys that may log in to a reservation's machine, as an `authorized_keys` file that the
+/// booted system can fetch. This is served from the start of the reservation for as long as its
+/// boot configuration is, without authentication but at a URL containing a secret token (like the
+/// calendar feeds), so that nobody can find out who is using which machine by trying every ID.
+///
+#[get("/reservation/keys/<token>")]
+fn reservation_authorized_keys(token: String) -> Result<Option<content::Plain<String>>, Error> {
+    let conn = db::establish_connection()?;
+    let r = match Reservation::with_keys_token(&token, &conn) {
+        Ok(r) => r,
+        Err(diesel::result::Error::NotFound) => return Ok(None),
+        Err(e) => return Err(Error::DatabaseError(e)),
+    };
+
+    if r.boot_config().is_none() || r.scheduled_start > Utc::now() {
+        return Ok(None);
+    }
+
+    let keys = SshKey::for_reservation(&r, &conn)?
+        .iter()
+        .map(|k| k.authorized_keys_line() + "\n")
+        .collect::<String>();
+
+    Ok(Some(content::Plain(keys)))
+}
+
+#[derive(Debug, FromForm)]
+struct CancelForm {
+    reason: String,
+}
+
+impl CancelForm {
+    fn reason(&self) -> Option<String> {
+        Some(self.reason.trim().to_string()).filter(|s| !s.is_empty())
+    }
+}
+
+#[post("/reservation/<id>/cancel", data = "<form>")]
+fn reservation_cancel(
+    id: i32,
+    form: LenientForm<CancelForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let (r, _, _) = Reservation::get(id, &auth.conn)?;
+    check_may_manage(&r, &auth)?;
+    if r.is_closed() {
+        return Err(Error::BadRequest(format![
+            "reservation {} has already ended or been cancelled",
+            id
+        ]));
+    }
+    if r.scheduled_start <= Utc::now() {
+        return Err(Error::BadRequest(format![
+            "reservation {} has already started; end it instead",
+            id
+        ]));
+    }
+
+    let series = r.series_id;
+    let before = r.to_json();
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        let r = r.cancel(&auth.user, form.reason(), &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("reservation.cancel", "reservation", r.id)
+                .before(&before)
+                .after(&r),
+        )?;
+        Ok(r)
+    })?;
+
+    let target = match series {
+        Some(s) => format!["{}reservation/series/{}", route_prefix(), s],
+        None => format!["{}reservations", route_prefix()],
+    };
+
+    Ok(Flash::new(
+        Redirect::to(target),
+        "info",
+        format!["Cancelled reservation {}", id],
+    ))
+}
+
+/// The format used by the `daterange` picker for each end of a date range.
+const DATE_FORMAT: &'static str = "%H:%M%:z %e %b %Y";
+
+/// Parse a date range (as submitted by a `daterange` input) into start and end times.
+fn parse_daterange(range: &str) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    parse_local_daterange(range)
+        .map(|(start, end)| (start.with_timezone(&Utc), end.with_timezone(&Utc)))
+}
+
+///
+/// Parse a date range (as submitted by a `daterange` input) into start and end times in the
+/// time zone that the user submitted them in.
+///
+fn parse_local_daterange(
+    range: &str,
+) -> Result<(DateTime<FixedOffset>, DateTime<FixedOffset>), Error> {
+    let dates: Vec<&str> = range.split(" - ").collect();
+    if dates.len() != 2 {
+        return Err(Error::BadRequest(format![
+            "expected two dates, not '{:?}'",
+            dates
+        ]));
+    }
+
+    let start = DateTime::parse_from_str(dates[0], DATE_FORMAT)?;
+    let end = DateTime::parse_from_str(dates[1], DATE_FORMAT)?;
+
+    Ok((start, end))
+}
+
+///
+/// The time zone that Clowder's users are (mostly) in: `CLOWDER_TIMEZONE`, an IANA zone name such
+/// as `America/St_Johns` (default: UTC).
+///
+fn local_timezone() -> Result<Tz, Error> {
+    match env::var("CLOWDER_TIMEZONE") {
+        Ok(name) => name
+            .parse()
+            .map_err(|e| Error::ConfigError(format!["invalid CLOWDER_TIMEZONE: {}", e])),
+        Err(_) => Ok(Tz::UTC),
+    }
+}
+
+///
+/// The start times of a recurring reservation's occurrences. If the first one was entered in
+/// the local time zone, the others keep its local time across daylight saving changes;
+/// times entered with some other UTC offset keep that offset.
+///
+fn occurrence_starts(
+    recurrence: &Recurrence,
+    first: DateTime<FixedOffset>,
+) -> Result<Vec<DateTime<Utc>>, Error> {
+    let local = first.with_timezone(&local_timezone()?);
+
+    let starts = if local.offset().fix() == *first.offset() {
+        recurrence.occurrences(local)?.iter().map(|s| s.with_timezone(&Utc)).collect()
+    } else {
+        recurrence.occurrences(first)?.iter().map(|s| s.with_timezone(&Utc)).collect()
+    };
+
+    Ok(starts)
+}
+
+///
+/// Parse a single time, as submitted by a `datetime` input (i.e., in `DATE_FORMAT`) or typed in
+/// ISO 8601 / RFC 3339 form (e.g., `2026-10-31T17:00:00Z`).
+///
+fn parse_time(time: &str) -> Result<DateTime<Utc>, Error> {
+    let time = time.trim();
+
+    DateTime::parse_from_str(time, DATE_FORMAT)
+        .or_else(|_| DateTime::parse_from_rfc3339(time))
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| {
+            Error::BadRequest(format![
+                "invalid time '{}' (expected e.g. '17:00+00:00 31 Oct 2026' or \
+                 '2026-10-31T17:00:00Z')",
+                time
+            ])
+        })
+}
+
+/// Parse an optional integer form field, where an empty string means "no value".
+fn optional_int(name: &str, value: &str) -> Result<Option<i32>, Error> {
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+
+    value
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|e| Error::BadRequest(format!["invalid {} '{}': {}", name, value, e]))
+}
+
+///
+/// A description of what a reservation is for, as submitted in any of the reservation forms.
+///
+struct ReservationDetails {
+    purpose: String,
+    notes: Option<String>,
+    project: Option<String>,
+}
+
+impl ReservationDetails {
+    fn parse(purpose: &str, notes: &str, project: &str) -> Result<ReservationDetails, Error> {
+        let optional = |s: &str| Some(s.trim().to_string()).filter(|s| !s.is_empty());
+
+        let purpose = purpose.trim();
+        if purpose.is_empty() {
+            return Err(Error::BadRequest(String::from(
+                "every reservation needs a purpose",
+            )));
+        }
+
+        Ok(ReservationDetails {
+            purpose: purpose.to_string(),
+            notes: optional(notes),
+            project: optional(project),
+        })
+    }
+
+    fn apply(&self, rb: &mut ReservationBuilder) {
+        rb.purpose(self.purpose.clone());
+
+        if let Some(ref notes) = self.notes {
+            rb.notes(notes.clone());
+        }
+        if let Some(ref project) = self.project {
+            rb.project(project.clone());
+        }
+    }
+}
+
+/// Form rows for describing a reservation (see `ReservationDetails`).
+fn purpose_form_rows() -> Markup {
+    html! {
+        tr {
+            th { "Purpose" }
+            td { (forms::Input::new("purpose").size(45)) }
+        }
+        tr {
+            th { "Project" }
+            td { (forms::Input::new("project").size(15)) " (project or grant code, if any)" }
+        }
+        tr {
+            th { "Notes" }
+            td {
+                textarea name="notes" rows="4" cols="45" {}
+                br /
+                small { "Markdown is supported." }
+            }
+        }
+    }
+}
+
+struct ReservationForm {
+    user: String,
+    machine: String,
+    dates: String,
+    pxe: String,
+    nfs: String,
+    purpose: String,
+    notes: String,
+    project: String,
+    team: String,
+
+    /// How often the reservation recurs (empty for a one-off reservation).
+    repeat: String,
+    days: Vec<String>,
+    count: String,
+    until: String,
+}
+
+// TODO: use #[derive(FromForm)] once SergioBenitez/Rocket#205 is resolved
+impl<'f> request::FromForm<'f> for ReservationForm {
+    type Error = Error;
+
+    fn from_form(form_items: &mut request::FormItems<'f>, _: bool) -> Result<Self, Self::Error> {
+        let mut form = ReservationForm {
+            user: String::new(),
+            machine: String::new(),
+            dates: String::new(),
+            pxe: String::new(),
+            nfs: String::new(),
+            purpose: String::new(),
+            notes: String::new(),
+            project: String::new(),
+            team: String::new(),
+            repeat: String::new(),
+            days: Vec::new(),
+            count: String::new(),
+            until: String::new(),
+        };
+
+        for (key, value) in form_items.map(|i| i.key_value_decoded()) {
+            match &*key {
+                "user" => form.user = value,
+                "machine" => form.machine = value,
+                "dates" => form.dates = value,
+                "pxe" => form.pxe = value,
+                "nfs" => form.nfs = value,
+                "purpose" => form.purpose = value,
+                "notes" => form.notes = value,
+                "project" => form.project = value,
+                "team" => form.team = value,
+                "repeat" => form.repeat = value,
+                "days" => form.days.push(value),
+                "count" => form.count = value,
+                "until" => form.until = value,
+                forms::CSRF_PARAM => {}
+                _ => {
+                    return Err(Error::InvalidData(format![
+                        "invalid form data name: '{}'",
+                        key
+                    ]));
+                }
+            }
+        }
+
+        Ok(form)
+    }
+}
+
+impl ReservationForm {
+    /// The recurrence rule requested by the user (if any).
+    fn recurrence(&self) -> Result<Option<Recurrence>, Error> {
+        let freq = match &*self.repeat {
+            "" => return Ok(None),
+            "daily" => Frequency::Daily,
+            "weekly" => Frequency::Weekly,
+            other => {
+                return Err(Error::BadRequest(format!["invalid repetition '{}'", other]));
+            }
+        };
+
+        let by_day = self
+            .days
+            .iter()
+            .map(|d| recurrence::parse_weekday(d))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let until = match self.until.trim() {
+            "" => None,
+            date => Some(NaiveDate::parse_from_str(date, "%Y-%m-%d")?),
+        };
+
+        let r = Recurrence {
+            freq: freq,
+            by_day: by_day,
+            count: optional_int("occurrence count", &self.count)?.map(|n| n.max(0) as usize),
+            until: until,
+        };
+
+        r.check()?;
+        Ok(Some(r))
+    }
+}
+
+#[post("/reservation/create", data = "<res>")]
+fn reservation_create(
+    res: LenientForm<ReservationForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let user = User::with_username(&res.user, &auth.conn)?;
+    if user.id != auth.user.id && !auth.user.can(Capability::ReserveForOthers, &auth.conn)? {
+        return Err(Error::NotAuthorized(format![
+            "reserve machines on behalf of {}",
+            user.username
+        ]));
+    }
+
+    let machine = Machine::with_name(&res.machine, &auth.conn)?;
+    check_may_reserve(&user, &machine, &auth.conn)?;
+
+    let details = ReservationDetails::parse(&res.purpose, &res.notes, &res.project)?;
+    let team = chosen_team(&res.team, &auth)?;
+
+    if let Some(recurrence) = res.recurrence()? {
+        return reservation_create_series(
+            &res,
+            &details,
+            &recurrence,
+            &user,
+            &machine,
+            team.as_ref(),
+            &auth,
+        );
+    }
+
+    let (start, end) = parse_daterange(&res.dates)?;
+
+    let mut rb = ReservationBuilder::new(&user, &machine, start);
+    rb.end(end);
+    details.apply(&mut rb);
+
+    if let Some(ref t) = team {
+        rb.team(t);
+    }
+
+    if res.pxe.len() > 0 {
+        rb.pxe(res.pxe.clone());
+    }
+    if res.pxe.len() > 0 {
+        rb.nfs(res.nfs.clone());
+    }
+
+    // Lock the machine so that nobody else can reserve it between our checks and the insert.
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        Machine::lock(&[&machine], &auth.conn)?;
+
+        let conflicts = Reservation::overlapping(&machine, start, end, &auth.conn)?;
+        if let Some(c) = conflicts.first() {
+            return Err(Error::BadRequest(format![
+                "{} is already reserved from {} (reservation {}); see {}availability?machine={} \
+                 for free slots, or join the waitlist at {}waitlist?machine={}",
+                machine.name,
+                c.start(),
+                c.id(),
+                route_prefix(),
+                machine.name,
+                route_prefix(),
+                machine.name
+            ]));
+        }
+
+        user.reservation_policy(&auth.conn)?
+            .check(&user, &[(start, Some(end))], &auth.conn)?;
+
+        let r = rb.insert(&auth.conn)?;
+        auth.audit(AuditEvent::build("reservation.create", "reservation", r.id).after(&r))?;
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/{}", route_prefix(), r.id]),
+        "info",
+        if r.pending {
+            format!["Requested reservation {} (awaiting approval)", r.id]
+        } else {
+            format!["Created reservation {}", r.id]
+        },
+    ))
+}
+
+///
+/// Create a recurring reservation, materialising each occurrence that doesn't conflict with an
+/// existing reservation (and reporting those that do).
+///
+fn reservation_create_series(
+    res: &ReservationForm,
+    details: &ReservationDetails,
+    recurrence: &Recurrence,
+    user: &User,
+    machine: &Machine,
+    team: Option<&Team>,
+    auth: &AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let (first_start, first_end) = parse_local_daterange(&res.dates)?;
+    let duration = first_end.signed_duration_since(first_start);
+    let starts = occurrence_starts(recurrence, first_start)?;
+
+    let (series, conflicts) = conn.transaction::<_, Error, _>(|| {
+        Machine::lock(&[machine], conn)?;
+
+        let mut planned = Vec::new();
+        let mut conflicts = Vec::new();
+
+        for &start in &starts {
+            let end = start + duration;
+
+            let existing = Reservation::overlapping(machine, start, end, conn)?;
+            if let Some(c) = existing.first() {
+                conflicts.push(format!["{} (reservation {})", start, c.id()]);
+            } else {
+                planned.push((start, Some(end)));
+            }
+        }
+
+        if planned.is_empty() {
+            return Err(Error::BadRequest(format![
+                "every occurrence conflicts with an existing reservation: {}",
+                conflicts.join(", ")
+            ]));
+        }
+
+        user.reservation_policy(conn)?.check(user, &planned, conn)?;
+
+        let series = ReservationSeries::insert(user, machine, recurrence, conn)?;
+
+        for &(start, _) in &planned {
+            let mut rb = ReservationBuilder::new(user, machine, start);
+            rb.end(start + duration).series(&series);
+            details.apply(&mut rb);
+
+            if let Some(t) = team {
+                rb.team(t);
+            }
+
+            if res.pxe.len() > 0 {
+                rb.pxe(res.pxe.clone());
+            }
+            if res.nfs.len() > 0 {
+                rb.nfs(res.nfs.clone());
+            }
+
+            let r = rb.insert(conn)?;
+            auth.audit(AuditEvent::build("reservation.create", "reservation", r.id).after(&r))?;
+        }
+
+        Ok((series, conflicts))
+    })?;
+
+    let (kind, message) = if conflicts.is_empty() {
+        ("info", format!["Created recurring reservation {}", series.id])
+    } else {
+        (
+            "warning",
+            format![
+                "Created recurring reservation {}, but skipped {} conflicting occurrence(s): {}",
+                series.id,
+                conflicts.len(),
+                conflicts.join("; ")
+            ],
+        )
+    };
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/series/{}", route_prefix(), series.id]),
+        kind,
+        message,
+    ))
+}
+
+#[get("/reservation/create?<machine>&<dates>")]
+fn reservation_create_page(
+    machine: Option<String>,
+    dates: Option<String>,
+    auth: AuthContext,
+) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+
+    // Only users with the right role may reserve machines on someone else's behalf.
+    let users = if auth.user.can(Capability::ReserveForOthers, &auth.conn)? {
+        User::all(&auth.conn)?
+    } else {
+        vec![auth.user.clone()]
+    };
+    let user_options = users
+        .iter()
+        .map(|ref u| {
+            forms::SelectOption::new(u.username.clone(), u.name.clone())
+                .selected(u.username == auth.user.username)
+        })
+        .collect::<Vec<_>>();
+
+    // Only offer the machines that the user may reserve.
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
+    let machines = Machine::all(&auth.conn)?;
+    let machine_options = machines
+        .iter()
+        .filter(|m| acl.can_reserve(m))
+        .map(|ref m| {
+            forms::SelectOption::new(m.name.clone(), m.name.clone()).selected(
+                if let &Some(ref name) = &machine {
+                    name == &m.name
+                } else {
+                    false
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let team_options = team_options(&auth.user, None, &auth.conn)?;
+
+    let repeat_options = vec![
+        forms::SelectOption::new("", "Never"),
+        forms::SelectOption::new("daily", "Daily"),
+        forms::SelectOption::new("weekly", "Weekly"),
+    ];
+
+    let day_options = [
+        ("MO", "Monday"),
+        ("TU", "Tuesday"),
+        ("WE", "Wednesday"),
+        ("TH", "Thursday"),
+        ("FR", "Friday"),
+        ("SA", "Saturday"),
+        ("SU", "Sunday"),
+    ]
+    .iter()
+    .map(|&d| forms::SelectOption::from(d))
+    .collect();
+
+    Ok(page("Create reservation", &auth).content(html! {
+        h2 { "Reserve a machine" }
+
+        p {
+            "Don't mind which machine you get? "
+            a href={ (route_prefix()) "reservation/spec" } { "Reserve by specification" }
+            " instead. Need several machines at once? "
+            a href={ (route_prefix()) "reservation/group" } { "Reserve a group" }
+            "."
+        }
+
+        form action={ (route_prefix()) "reservation/create" } method="post" {
+            (csrf)
+            table {
+                tr {
+                    th { "User" }
+                    td { (forms::Select::new("user").set_options(user_options)) }
+                }
+                tr {
+                    th { "Machine" }
                     td { (forms::Select::new("machine").set_options(machine_options)) }
                 }
+                tr {
+                    th { "Dates" }
+                    td {
+                        (forms::Input::new("dates")
+                                      .class("daterange")
+                                      .value(dates.unwrap_or(String::new()))
+                                      .size(45))
+                        " "
+                        a href={ (route_prefix()) "availability" } { "Find a free slot" }
+                    }
+                }
+                (purpose_form_rows())
+                tr {
+                    th { "Team" }
+                    td { (forms::Select::new("team").set_options(team_options)) }
+                }
+                tr {
+                    th { "PXE loader" }
+                    td { (forms::Input::new("pxe").size(45)) }
+                }
+                tr {
+                    th { "NFS root" }
+                    td { (forms::Input::new("nfs").size(45)) }
+                }
+                tr {
+                    th { "Repeat" }
+                    td {
+                        (forms::Select::new("repeat").set_options(repeat_options))
+                        " on "
+                        (forms::Select::new("days").multiple(true).set_options(day_options))
+                    }
+                }
+                tr {
+                    th { "Repeat until" }
+                    td {
+                        (forms::Input::new("count").size(3)) " occurrences, or until "
+                        (forms::Input::new("until").size(10)) " (YYYY-MM-DD)"
+                    }
+                }
+                tr {
+                    th /
+                    td { (forms::SubmitButton::new().label("Reserve")) }
+                }
+            }
+        }
+    }))
+}
+
+///
+/// Build a machine specification from form fields, where empty fields are unconstrained.
+///
+fn parse_spec(
+    memory_gb: &str,
+    cores: &str,
+    arch: &str,
+    microarch: &str,
+    tags: &str,
+    disk: &str,
+    conn: &Connection,
+) -> Result<MachineSpec, Error> {
+    Ok(MachineSpec {
+        min_memory_gb: optional_int("memory", memory_gb)?,
+        min_cores: optional_int("core count", cores)?,
+        arch: match optional_int("architecture", arch)? {
+            Some(id) => Some(Architecture::get(id, conn)?),
+            None => None,
+        },
+        min_microarch: match optional_int("microarchitecture", microarch)? {
+            Some(id) => Some(Microarchitecture::get(id, conn)?),
+            None => None,
+        },
+        tags: tags
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(str::to_string)
+            .collect(),
+        ssd: match disk {
+            "ssd" => Some(true),
+            "hdd" => Some(false),
+            _ => None,
+        },
+    })
+}
+
+/// Form rows for specifying the kind of machine to reserve (see `parse_spec`).
+fn spec_form_rows(conn: &Connection) -> Result<Markup, Error> {
+    let any = || forms::SelectOption::new("", "(any)");
+
+    let mut arch_options = vec![any()];
+    arch_options.extend(
+        Architecture::all(conn)?
+            .into_iter()
+            .map(|a| forms::SelectOption::new(a.id.to_string(), a.name)),
+    );
+
+    let mut microarch_options = vec![any()];
+    microarch_options.extend(
+        Microarchitecture::all(conn)?
+            .into_iter()
+            .map(|u| forms::SelectOption::new(u.id.to_string(), format!["{} or newer", u.name])),
+    );
+
+    let disk_options = vec![
+        any(),
+        forms::SelectOption::new("ssd", "SSD"),
+        forms::SelectOption::new("hdd", "non-SSD"),
+    ];
+
+    Ok(html! {
+        tr {
+            th { "Memory" }
+            td { "at least " (forms::Input::new("memory_gb").size(5)) " GiB" }
+        }
+        tr {
+            th { "Cores" }
+            td { "at least " (forms::Input::new("cores").size(5)) }
+        }
+        tr {
+            th { "Architecture" }
+            td { (forms::Select::new("arch").set_options(arch_options)) }
+        }
+        tr {
+            th { "Microarchitecture" }
+            td { (forms::Select::new("microarch").set_options(microarch_options)) }
+        }
+        tr {
+            th { "Tags" }
+            td { (forms::Input::new("tags").size(45)) }
+        }
+        tr {
+            th { "Disk" }
+            td { (forms::Select::new("disk").set_options(disk_options)) }
+        }
+    })
+}
+
+#[derive(Debug, FromForm)]
+struct SpecReservationForm {
+    memory_gb: String,
+    cores: String,
+    arch: String,
+    microarch: String,
+    tags: String,
+    disk: String,
+    dates: String,
+    pxe: String,
+    nfs: String,
+    purpose: String,
+    notes: String,
+    project: String,
+}
+
+///
+/// Reserve the smallest machine that satisfies a specification, showing which machine was chosen
+/// (if any) and why each of the others was not.
+///
+#[post("/reservation/spec", data = "<form>")]
+fn reservation_spec(
+    form: LenientForm<SpecReservationForm>,
+    auth: AuthContext,
+) -> Result<Page, Error> {
+    let conn = &auth.conn;
+    let details = ReservationDetails::parse(&form.purpose, &form.notes, &form.project)?;
+
+    let spec = parse_spec(
+        &form.memory_gb,
+        &form.cores,
+        &form.arch,
+        &form.microarch,
+        &form.tags,
+        &form.disk,
+        conn,
+    )?;
+
+    let (start, end) = parse_daterange(&form.dates)?;
+    let acl = AccessControl::for_user(&auth.user, conn)?;
+    let candidates = spec
+        .evaluate(start, end, conn)?
+        .into_iter()
+        .filter(|&(ref m, _)| acl.can_view(m.machine()))
+        .map(|(m, mut reasons)| {
+            if !acl.can_reserve(m.machine()) {
+                reasons.push(String::from("not permitted to reserve"));
+            }
+            (m, reasons)
+        })
+        .collect::<Vec<_>>();
+
+    let suitable = candidates
+        .iter()
+        .filter(|&&(_, ref reasons)| reasons.is_empty())
+        .map(|&(ref m, _)| m.machine())
+        .collect::<Vec<_>>();
+
+    // Someone else may have reserved a suitable machine since we looked, so check again (in
+    // order) once nobody else can.
+    let (reservation, taken) = conn.transaction::<_, Error, _>(|| {
+        Machine::lock(&suitable, conn)?;
+
+        let mut chosen = None;
+        let mut taken = HashSet::new();
+        for m in &suitable {
+            if Reservation::overlapping(m, start, end, conn)?.is_empty() {
+                chosen = Some(*m);
+                break;
+            }
+
+            taken.insert(m.id);
+        }
+
+        let chosen = match chosen {
+            Some(m) => m,
+            None => return Ok((None, taken)),
+        };
+
+        auth.user
+            .reservation_policy(conn)?
+            .check(&auth.user, &[(start, Some(end))], conn)?;
+
+        let mut rb = ReservationBuilder::new(&auth.user, chosen, start);
+        rb.end(end);
+        details.apply(&mut rb);
+
+        if form.pxe.len() > 0 {
+            rb.pxe(form.pxe.clone());
+        }
+        if form.nfs.len() > 0 {
+            rb.nfs(form.nfs.clone());
+        }
+
+        let r = rb.insert(conn)?;
+        auth.audit(AuditEvent::build("reservation.create", "reservation", r.id).after(&r))?;
+
+        Ok((Some((r, chosen.clone())), taken))
+    })?;
+
+    let chosen_id = reservation.as_ref().map(|&(_, ref m)| m.id);
+
+    Ok(page("Clowder: reserve by specification", &auth).content(html! {
+        h2 { "Reserve by specification" }
+
+        @if let Some((ref r, ref machine)) = reservation {
+            (bootstrap::callout("success", "Machine reserved", html! {
+                p {
+                    "Reserved " (Link::from(machine)) " as reservation " (Link::from(r))
+                    ": it is the smallest machine that satisfies the specification. "
+                    "The other machines are listed below with the reasons they weren't chosen."
+                }
+            }))
+        } @else {
+            (bootstrap::callout("warning", "No machine available", html! {
+                p { "No machine satisfies this specification over the requested dates." }
+            }))
+        }
+
+        table.table.table-responsive {
+            (tables::TableHeader::new(&[ "Machine", "Memory", "Cores", "Outcome" ]))
+
+            tbody {
+                @for &(ref m, ref reasons) in &candidates {
+                    tr {
+                        td { (Link::from(m.machine())) }
+                        td.numeric { (m.memory_gb()) " GiB" }
+                        td.numeric { (m.cores()) }
+                        td {
+                            @if chosen_id == Some(m.machine().id) {
+                                strong { "Chosen" }
+                            } @else if taken.contains(&m.machine().id) {
+                                "Reserved by someone else in the meantime"
+                            } @else if reasons.is_empty() {
+                                "Suitable, but a smaller machine was chosen"
+                            } @else {
+                                ul.list-unstyled {
+                                    @for reason in reasons {
+                                        li { (reason) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+#[get("/reservation/spec")]
+fn reservation_spec_page(auth: AuthContext) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let spec_rows = spec_form_rows(&auth.conn)?;
+
+    Ok(page("Reserve by specification", &auth).content(html! {
+        h2 { "Reserve any machine matching a specification" }
+
+        form action={ (route_prefix()) "reservation/spec" } method="post" {
+            (csrf)
+            table {
+                (spec_rows)
+                tr {
+                    th { "Dates" }
+                    td { (forms::Input::new("dates").class("daterange").size(45)) }
+                }
+                (purpose_form_rows())
+                tr {
+                    th { "PXE loader" }
+                    td { (forms::Input::new("pxe").size(45)) }
+                }
+                tr {
+                    th { "NFS root" }
+                    td { (forms::Input::new("nfs").size(45)) }
+                }
+                tr {
+                    th /
+                    td { (forms::SubmitButton::new().label("Find and reserve")) }
+                }
+            }
+        }
+    }))
+}
+
+/// A request to reserve several machines (named and/or matching a specification) at once.
+struct GroupReservationForm {
+    machines: BTreeSet<String>,
+    count: String,
+    memory_gb: String,
+    cores: String,
+    arch: String,
+    microarch: String,
+    tags: String,
+    disk: String,
+    dates: String,
+    pxe: String,
+    nfs: String,
+    purpose: String,
+    notes: String,
+    project: String,
+}
+
+// TODO: use #[derive(FromForm)] once SergioBenitez/Rocket#205 is resolved
+impl<'f> request::FromForm<'f> for GroupReservationForm {
+    type Error = Error;
+
+    fn from_form(form_items: &mut request::FormItems<'f>, _: bool) -> Result<Self, Self::Error> {
+        let mut form = GroupReservationForm {
+            machines: BTreeSet::new(),
+            count: String::new(),
+            memory_gb: String::new(),
+            cores: String::new(),
+            arch: String::new(),
+            microarch: String::new(),
+            tags: String::new(),
+            disk: String::new(),
+            dates: String::new(),
+            pxe: String::new(),
+            nfs: String::new(),
+            purpose: String::new(),
+            notes: String::new(),
+            project: String::new(),
+        };
+
+        for (key, value) in form_items.map(|i| i.key_value_decoded()) {
+            match &*key {
+                "machines" => {
+                    form.machines.insert(value);
+                }
+                "count" => form.count = value,
+                "memory_gb" => form.memory_gb = value,
+                "cores" => form.cores = value,
+                "arch" => form.arch = value,
+                "microarch" => form.microarch = value,
+                "tags" => form.tags = value,
+                "disk" => form.disk = value,
+                "dates" => form.dates = value,
+                "pxe" => form.pxe = value,
+                "nfs" => form.nfs = value,
+                "purpose" => form.purpose = value,
+                "notes" => form.notes = value,
+                "project" => form.project = value,
+                forms::CSRF_PARAM => {}
+                _ => {
+                    return Err(Error::InvalidData(format![
+                        "invalid form data name: '{}'",
+                        key
+                    ]));
+                }
+            }
+        }
+
+        Ok(form)
+    }
+}
+
+#[post("/reservation/group/create", data = "<form>")]
+fn reservation_group_create(
+    form: LenientForm<GroupReservationForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let (start, end) = parse_daterange(&form.dates)?;
+    let details = ReservationDetails::parse(&form.purpose, &form.notes, &form.project)?;
+
+    let mut chosen = form
+        .machines
+        .iter()
+        .map(|name| Machine::with_name(name, conn))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let acl = AccessControl::for_user(&auth.user, conn)?;
+    if let Some(m) = chosen.iter().find(|m| !acl.can_reserve(m)) {
+        return Err(Error::NotAuthorized(format!["reserve {}", m.name]));
+    }
+
+    let count = optional_int("machine count", &form.count)?.unwrap_or(0) as usize;
+    if count > 0 {
+        let spec = parse_spec(
+            &form.memory_gb,
+            &form.cores,
+            &form.arch,
+            &form.microarch,
+            &form.tags,
+            &form.disk,
+            conn,
+        )?;
+
+        let suitable = spec
+            .evaluate(start, end, conn)?
+            .into_iter()
+            .filter(|&(ref m, ref reasons)| {
+                reasons.is_empty()
+                    && acl.can_reserve(m.machine())
+                    && !chosen.iter().any(|c| c.id == m.machine().id)
+            })
+            .map(|(m, _)| m.into_machine())
+            .take(count)
+            .collect::<Vec<_>>();
+
+        if suitable.len() < count {
+            return Err(Error::BadRequest(format![
+                "only {} available machine(s) match the specification (need {})",
+                suitable.len(),
+                count
+            ]));
+        }
+
+        chosen.extend(suitable);
+    }
+
+    if chosen.is_empty() {
+        return Err(Error::BadRequest(String::from("no machines requested")));
+    }
+
+    // Either every machine is reserved or none of them are.
+    let group = conn.transaction::<_, Error, _>(|| {
+        Machine::lock(&chosen.iter().collect::<Vec<_>>(), conn)?;
+
+        auth.user
+            .reservation_policy(conn)?
+            .check(&auth.user, &vec![(start, Some(end)); chosen.len()], conn)?;
+
+        for m in &chosen {
+            let conflicts = Reservation::overlapping(m, start, end, conn)?;
+            if !conflicts.is_empty() {
+                return Err(Error::BadRequest(format![
+                    "{} is already reserved (reservation {})",
+                    m.name,
+                    conflicts.iter().map(|r| r.id().to_string()).collect::<Vec<_>>().join(", ")
+                ]));
+            }
+        }
+
+        let group = ReservationGroup::insert(&auth.user, conn)?;
+
+        for m in &chosen {
+            let mut rb = ReservationBuilder::new(&auth.user, m, start);
+            rb.end(end).group(&group);
+            details.apply(&mut rb);
+
+            if form.pxe.len() > 0 {
+                rb.pxe(form.pxe.clone());
+            }
+            if form.nfs.len() > 0 {
+                rb.nfs(form.nfs.clone());
+            }
+
+            let r = rb.insert(conn)?;
+            auth.audit(AuditEvent::build("reservation.create", "reservation", r.id).after(&r))?;
+        }
+
+        Ok(group)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/group/{}", route_prefix(), group.id]),
+        "info",
+        format!["Reserved {} machines", chosen.len()],
+    ))
+}
+
+#[get("/reservation/group")]
+fn reservation_group_page(auth: AuthContext) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
+    let machine_options = Machine::all(&auth.conn)?
+        .into_iter()
+        .filter(|m| acl.can_reserve(m))
+        .map(|m| forms::SelectOption::new(m.name.clone(), m.name))
+        .collect::<Vec<_>>();
+
+    let spec_rows = spec_form_rows(&auth.conn)?;
+
+    Ok(page("Reserve several machines", &auth).content(html! {
+        h2 { "Reserve several machines at once" }
+
+        p {
+            "Choose machines by name, ask for a number of machines matching a specification, "
+            "or both. If any machine is unavailable, nothing will be reserved."
+        }
+
+        form action={ (route_prefix()) "reservation/group/create" } method="post" {
+            (csrf)
+            table {
+                tr {
+                    th { "Machines" }
+                    td {
+                        (forms::Select::new("machines").multiple(true).set_options(machine_options))
+                    }
+                }
+                tr {
+                    th { "Matching machines" }
+                    td { (forms::Input::new("count").size(3)) " more machine(s) with:" }
+                }
+                (spec_rows)
                 tr {
                     th { "Dates" }
                     td { (forms::Input::new("dates").class("daterange").size(45)) }
                 }
+                (purpose_form_rows())
                 tr {
                     th { "PXE loader" }
                     td { (forms::Input::new("pxe").size(45)) }
@@ -465,7 +2328,703 @@ fn reservation_create_page(machine: Option<String>, auth: AuthContext) -> Result
                 }
                 tr {
                     th /
-                    td { (forms::SubmitButton::new().label("Reserve")) }
+                    td { (forms::SubmitButton::new().label("Reserve all")) }
+                }
+            }
+        }
+    }))
+}
+
+#[get("/reservation/group/<id>")]
+fn reservation_group(
+    id: i32,
+    auth: AuthContext,
+    flash: Option<FlashMessage>,
+) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let (group, user) = ReservationGroup::get(id, &auth.conn)?;
+
+    let members = group
+        .members(&auth.conn)?
+        .into_iter()
+        .map(|(r, m)| (r, Some(m), None))
+        .collect::<Vec<_>>();
+
+    let active = members.iter().any(|&(ref r, _, _)| !r.is_closed());
+    let latest_end = members
+        .iter()
+        .filter_map(|&(ref r, _, _)| r.finish())
+        .max()
+        .map(|t| t.format(DATE_FORMAT).to_string())
+        .unwrap_or_default();
+
+    Ok(page(format!["Clowder: reservation group {}", group.id], &auth)
+        .flash(flash)
+        .content(html! {
+            h2 { "Reservation group " (group.id) }
+
+            table.lefty {
+                tr { th { "User" }       td { (Link::from(&user)) } }
+                tr { th { "Created" }    td { (group.created_at) } }
+                tr { th { "Machines" }   td { (members.len()) } }
+            }
+
+            (tables::ReservationTable::new(members).show_user(false))
+
+            @if active {
+                form action={ (route_prefix()) "reservation/group/" (group.id) "/extend" }
+                     method="post" {
+                    (csrf)
+                    "New end: "
+                    (forms::Input::new("end").class("datetime").value(latest_end).size(25))
+                    " "
+                    (forms::SubmitButton::new().label("Extend all"))
+                }
+
+                form action={ (route_prefix()) "reservation/group/" (group.id) "/end" }
+                     method="post" {
+                    (csrf)
+                    (forms::SubmitButton::new().label("End all"))
+                }
+            }
+        }))
+}
+
+#[derive(Debug, FromForm)]
+struct GroupExtendForm {
+    end: String,
+}
+
+#[post("/reservation/group/<id>/extend", data = "<form>")]
+fn reservation_group_extend(
+    id: i32,
+    form: LenientForm<GroupExtendForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let (group, _) = ReservationGroup::get(id, conn)?;
+    let new_end = parse_time(&form.end)?;
+
+    conn.transaction::<_, Error, _>(|| {
+        let members = group
+            .members(conn)?
+            .into_iter()
+            .filter(|&(ref r, _)| !r.is_closed())
+            .collect::<Vec<_>>();
+        Machine::lock(&members.iter().map(|&(_, ref m)| m).collect::<Vec<_>>(), conn)?;
+
+        for &(ref r, ref m) in &members {
+            check_may_manage(r, &auth)?;
+
+            let old_end = match r.finish() {
+                Some(end) => end,
+                None => {
+                    return Err(Error::BadRequest(format![
+                        "reservation {} has no end, so it can't be extended",
+                        r.id()
+                    ]));
+                }
+            };
+
+            if new_end <= old_end || new_end <= r.start() {
+                return Err(Error::BadRequest(format![
+                    "reservation {} already lasts until {}, so it can't be extended to {}",
+                    r.id(),
+                    old_end,
+                    new_end
+                ]));
+            }
+
+            let conflicts = Reservation::overlapping(m, old_end, new_end, conn)?;
+            if let Some(c) = conflicts.iter().find(|c| c.id() != r.id()) {
+                return Err(Error::BadRequest(format![
+                    "{} is already reserved from {} (reservation {})",
+                    m.name,
+                    c.start(),
+                    c.id()
+                ]));
+            }
+        }
+
+        // Extending reservations mustn't be a way around their owners' reservation policies
+        // (members of the group may have been transferred to other users).
+        let owners = members.iter().map(|&(ref r, _)| r.user_id).collect::<BTreeSet<_>>();
+        for owner in owners {
+            let owner = User::get(owner, conn)?;
+            let (windows, ids): (Vec<_>, Vec<_>) = members
+                .iter()
+                .filter(|&&(ref r, _)| r.user_id == owner.id)
+                .map(|&(ref r, _)| ((r.start(), Some(new_end)), r.id()))
+                .unzip();
+
+            owner
+                .reservation_policy(conn)?
+                .check_replacing(&owner, &windows, &ids, conn)?;
+        }
+
+        for (r, _) in members {
+            let before = r.to_json();
+            let r = r.extend(new_end, conn)?;
+            auth.audit(
+                AuditEvent::build("reservation.extend", "reservation", r.id)
+                    .before(&before)
+                    .after(&r),
+            )?;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/group/{}", route_prefix(), group.id]),
+        "info",
+        format!["Extended reservation group {} until {}", group.id, new_end],
+    ))
+}
+
+#[post("/reservation/group/<id>/end")]
+fn reservation_group_end(id: i32, auth: AuthContext) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let (group, _) = ReservationGroup::get(id, conn)?;
+
+    conn.transaction::<_, Error, _>(|| {
+        let now = Utc::now();
+        for (r, _) in group.members(conn)? {
+            if r.is_closed() {
+                continue;
+            }
+            check_may_manage(&r, &auth)?;
+
+            let before = r.to_json();
+            let (action, r) = if r.scheduled_start > now {
+                ("reservation.cancel", r.cancel(&auth.user, None, conn)?)
+            } else {
+                ("reservation.end", r.end(conn)?)
+            };
+
+            auth.audit(
+                AuditEvent::build(action, "reservation", r.id)
+                    .before(&before)
+                    .after(&r),
+            )?;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/group/{}", route_prefix(), group.id]),
+        "info",
+        format!["Ended reservation group {}", group.id],
+    ))
+}
+
+#[get("/approvals")]
+fn approvals(auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    if !auth.user.can(Capability::ApproveReservations, &auth.conn)? {
+        return Err(Error::NotAuthorized(String::from("approve reservations")));
+    }
+
+    let pending = Reservation::pending_approval(&auth.conn)?;
+
+    Ok(page("Clowder: approvals", &auth).flash(flash).content(html! {
+        h2 { "Reservations awaiting approval" }
+
+        @if pending.is_empty() {
+            p { "There are no reservations awaiting approval." }
+        } @else {
+            table.table.table-responsive {
+                (tables::TableHeader::new(&[ "#", "Machine", "User", "Starts", "Ends", "" ]))
+
+                tbody {
+                    @for &(ref r, ref m, ref u) in &pending {
+                        tr {
+                            td { (Link::from(r)) }
+                            td { (Link::from(m)) }
+                            td { (Link::from(u)) }
+                            td { (r.scheduled_start) }
+                            td {
+                                @if let Some(end) = r.scheduled_end { (end) }
+                            }
+                            td {
+                                form action={ (route_prefix()) "reservation/" (r.id) "/approve" }
+                                     method="post" {
+                                    (csrf)
+                                    (forms::Input::new("comment").size(25))
+                                    " "
+                                    (forms::SubmitButton::new().label("Approve"))
+                                }
+                                form action={ (route_prefix()) "reservation/" (r.id) "/reject" }
+                                     method="post" {
+                                    (csrf)
+                                    (forms::Input::new("comment").size(25))
+                                    " "
+                                    (forms::SubmitButton::new().label("Reject"))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+#[derive(Debug, FromForm)]
+struct ReviewForm {
+    comment: String,
+}
+
+impl ReviewForm {
+    fn comment(&self) -> Option<String> {
+        Some(self.comment.trim().to_string()).filter(|s| !s.is_empty())
+    }
+}
+
+/// Look up a reservation that is awaiting approval by the current user.
+fn reviewable(id: i32, auth: &AuthContext) -> Result<Reservation, Error> {
+    if !auth.user.can(Capability::ApproveReservations, &auth.conn)? {
+        return Err(Error::NotAuthorized(String::from("approve reservations")));
+    }
+
+    let (r, _, _) = Reservation::get(id, &auth.conn)?;
+    if !r.pending || r.is_cancelled() {
+        return Err(Error::BadRequest(format![
+            "reservation {} is not awaiting approval",
+            id
+        ]));
+    }
+
+    Ok(r)
+}
+
+#[post("/reservation/<id>/approve", data = "<form>")]
+fn reservation_approve(
+    id: i32,
+    form: LenientForm<ReviewForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let r = reviewable(id, &auth)?;
+    let before = r.to_json();
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        let r = r.approve(&auth.user, form.comment(), &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("reservation.approve", "reservation", r.id)
+                .before(&before)
+                .after(&r),
+        )?;
+
+        Notification::insert(
+            r.user_id,
+            format!["{} approved reservation {}", auth.user.username, r.id],
+            Some(format!["reservation/{}", r.id]),
+            &auth.conn,
+        )?;
+
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}approvals", route_prefix()]),
+        "info",
+        format!["Approved reservation {}", r.id],
+    ))
+}
+
+#[post("/reservation/<id>/reject", data = "<form>")]
+fn reservation_reject(
+    id: i32,
+    form: LenientForm<ReviewForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let r = reviewable(id, &auth)?;
+    let before = r.to_json();
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        let r = r.reject(&auth.user, form.comment(), &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("reservation.reject", "reservation", r.id)
+                .before(&before)
+                .after(&r),
+        )?;
+
+        Notification::insert(
+            r.user_id,
+            format!["{} rejected reservation {}", auth.user.username, r.id],
+            Some(format!["reservation/{}", r.id]),
+            &auth.conn,
+        )?;
+
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}approvals", route_prefix()]),
+        "info",
+        format!["Rejected reservation {}", r.id],
+    ))
+}
+
+#[get("/reservation/series/<id>")]
+fn reservation_series(
+    id: i32,
+    auth: AuthContext,
+    flash: Option<FlashMessage>,
+) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let (series, machine, user) = ReservationSeries::get(id, &auth.conn)?;
+    let recurrence = series.recurrence()?;
+
+    let now = Utc::now();
+    let members = series.members(&auth.conn)?;
+    let upcoming = members
+        .iter()
+        .filter(|r| r.scheduled_start > now && !r.is_closed())
+        .count();
+
+    let members = members
+        .into_iter()
+        .map(|r| (r, None, None))
+        .collect::<Vec<_>>();
+
+    Ok(page(format!["Clowder: reservation series {}", series.id], &auth)
+        .flash(flash)
+        .content(html! {
+            h2 { "Reservation series " (series.id) }
+
+            table.lefty {
+                tr { th { "User" }        td { (Link::from(&user)) } }
+                tr { th { "Machine" }     td { (Link::from(&machine)) } }
+                tr { th { "Repeats" }     td { code { (recurrence) } } }
+                tr { th { "Created" }     td { (series.created_at) } }
+                tr { th { "Occurrences" } td { (members.len()) " (" (upcoming) " upcoming)" } }
+            }
+
+            (tables::ReservationTable::new(members).show_machine(false).show_user(false))
+
+            @if upcoming > 0 {
+                h4 { "Boot configuration for upcoming occurrences" }
+                form action={ (route_prefix()) "reservation/series/" (series.id) "/boot" }
+                     method="post" {
+                    (csrf)
+                    table.lefty {
+                        tr {
+                            th { "PXE path" }
+                            td { (forms::Input::new("pxe").size(45)) }
+                        }
+                        tr {
+                            th { "NFS root" }
+                            td { (forms::Input::new("nfs").size(45)) }
+                        }
+                        tr {
+                            th /
+                            td { (forms::SubmitButton::new().label("Update all upcoming")) }
+                        }
+                    }
+                }
+
+                form action={ (route_prefix()) "reservation/series/" (series.id) "/cancel" }
+                     method="post" {
+                    (csrf)
+                    "Reason: "
+                    (forms::Input::new("reason").size(30))
+                    " "
+                    (forms::SubmitButton::new().label("Cancel all upcoming"))
+                }
+            }
+        }))
+}
+
+#[post("/reservation/series/<id>/boot", data = "<form>")]
+fn reservation_series_boot(
+    id: i32,
+    form: LenientForm<BootPathForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let (series, _, _) = ReservationSeries::get(id, conn)?;
+    let now = Utc::now();
+
+    let updated = conn.transaction::<_, Error, _>(|| {
+        let mut updated = 0;
+        for r in series.members(conn)? {
+            if r.scheduled_start > now && !r.is_closed() {
+                check_may_manage(&r, &auth)?;
+                let before = r.to_json();
+                let r = r.set_boot_paths(form.pxe(), form.nfs(), conn)?;
+                auth.audit(
+                    AuditEvent::build("reservation.boot", "reservation", r.id)
+                        .before(&before)
+                        .after(&r),
+                )?;
+                updated += 1;
+            }
+        }
+
+        Ok(updated)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/series/{}", route_prefix(), series.id]),
+        "info",
+        format!["Updated boot configuration for {} upcoming occurrence(s)", updated],
+    ))
+}
+
+#[post("/reservation/series/<id>/cancel", data = "<form>")]
+fn reservation_series_cancel(
+    id: i32,
+    form: LenientForm<CancelForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let (series, _, _) = ReservationSeries::get(id, conn)?;
+    let now = Utc::now();
+
+    let cancelled = conn.transaction::<_, Error, _>(|| {
+        let mut cancelled = 0;
+        for r in series.members(conn)? {
+            if r.scheduled_start > now && !r.is_closed() {
+                check_may_manage(&r, &auth)?;
+                let before = r.to_json();
+                let r = r.cancel(&auth.user, form.reason(), conn)?;
+                auth.audit(
+                    AuditEvent::build("reservation.cancel", "reservation", r.id)
+                        .before(&before)
+                        .after(&r),
+                )?;
+                cancelled += 1;
+            }
+        }
+
+        Ok(cancelled)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/series/{}", route_prefix(), series.id]),
+        "info",
+        format!["Cancelled {} upcoming occurrence(s)", cancelled],
+    ))
+}
+
+/// Format a time window in the form expected by a `daterange` input.
+fn format_daterange(start: DateTime<Utc>, end: DateTime<Utc>) -> String {
+    format!["{} - {}", start.format(DATE_FORMAT), end.format(DATE_FORMAT)]
+}
+
+/// A request for free time slots on a particular machine or on any machine matching a spec.
+#[derive(Debug, FromForm)]
+struct AvailabilityQuery {
+    machine: Option<String>,
+    hours: Option<String>,
+    memory_gb: Option<String>,
+    cores: Option<String>,
+    arch: Option<String>,
+    microarch: Option<String>,
+    tags: Option<String>,
+    disk: Option<String>,
+}
+
+/// A free time slot, as reported by the availability endpoint.
+struct Slot {
+    machine: String,
+
+    /// When the machine becomes free.
+    start: DateTime<Utc>,
+
+    /// When a reservation of the requested duration (starting at `start`) would end.
+    end: DateTime<Utc>,
+
+    /// When the machine is next reserved (if ever).
+    free_until: Option<DateTime<Utc>>,
+}
+
+impl Slot {
+    /// A link to the reservation form, pre-filled with this slot.
+    fn reservation_link(&self) -> String {
+        let dates = format_daterange(self.start, self.end);
+
+        format![
+            "{}reservation/create/?machine={}&dates={}",
+            route_prefix(),
+            url::form_urlencoded::byte_serialize(self.machine.as_bytes()).collect::<String>(),
+            url::form_urlencoded::byte_serialize(dates.as_bytes()).collect::<String>()
+        ]
+    }
+}
+
+impl ToJson for Slot {
+    fn to_json(&self) -> Json {
+        let mut obj = BTreeMap::new();
+        obj.insert(String::from("machine"), self.machine.to_json());
+        obj.insert(String::from("start"), self.start.to_rfc3339().to_json());
+        obj.insert(String::from("end"), self.end.to_rfc3339().to_json());
+        obj.insert(
+            String::from("free_until"),
+            self.free_until.map(|t| t.to_rfc3339()).to_json(),
+        );
+        obj.insert(
+            String::from("dates"),
+            format_daterange(self.start, self.end).to_json(),
+        );
+
+        Json::Object(obj)
+    }
+}
+
+///
+/// Find the earliest free slots (of the requested duration) on the requested machine or, if no
+/// machine is named, the earliest slot on each machine that matches the requested spec (and that
+/// the user may reserve).
+///
+fn find_slots(
+    q: &AvailabilityQuery,
+    acl: &AccessControl,
+    conn: &Connection,
+) -> Result<Vec<Slot>, Error> {
+    const MAX_SLOTS: usize = 10;
+
+    let field = |f: &Option<String>| f.clone().unwrap_or(String::new());
+
+    let hours = optional_int("duration", &field(&q.hours))?.unwrap_or(1);
+    if hours <= 0 {
+        return Err(Error::BadRequest(format!["invalid duration: {} hours", hours]));
+    }
+
+    let duration = chrono::Duration::hours(hours as i64);
+    let now = Utc::now();
+
+    let (machines, per_machine) = match q.machine {
+        Some(ref name) if !name.is_empty() => {
+            let m = Machine::with_name(name, conn)?;
+            if !acl.can_view(&m) {
+                return Err(Error::NotAuthorized(format!["view machine {}", m.name]));
+            }
+
+            (vec![m], MAX_SLOTS)
+        }
+        _ => {
+            let spec = parse_spec(
+                &field(&q.memory_gb),
+                &field(&q.cores),
+                &field(&q.arch),
+                &field(&q.microarch),
+                &field(&q.tags),
+                &field(&q.disk),
+                conn,
+            )?;
+
+            let mut suitable = Vec::new();
+            for m in FullMachine::all(conn)? {
+                if acl.can_reserve(m.machine()) && spec.rejections(&m, conn)?.is_empty() {
+                    suitable.push(m.into_machine());
+                }
+            }
+
+            (suitable, 1)
+        }
+    };
+
+    let mut slots = Vec::new();
+    for m in machines {
+        for (start, end) in Reservation::free_windows(&m, now, duration, per_machine, conn)? {
+            slots.push(Slot {
+                machine: m.name.clone(),
+                start: start,
+                end: start + duration,
+                free_until: end,
+            });
+        }
+    }
+
+    slots.sort_by_key(|s| s.start);
+    slots.truncate(MAX_SLOTS);
+
+    Ok(slots)
+}
+
+#[get("/availability.json?<q..>")]
+fn availability_json(
+    q: LenientForm<AvailabilityQuery>,
+    auth: AuthContext,
+) -> Result<content::Json<String>, Error> {
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
+    find_slots(&q, &acl, &auth.conn).map(|slots| content::Json(slots.to_json().to_string()))
+}
+
+#[get("/availability?<q..>")]
+fn availability(
+    q: LenientForm<AvailabilityQuery>,
+    auth: AuthContext,
+) -> Result<Page, Error> {
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
+    let searched = q.machine.is_some() || q.hours.is_some();
+    let slots = if searched {
+        find_slots(&q, &acl, &auth.conn)?
+    } else {
+        Vec::new()
+    };
+
+    let visible = Machine::all(&auth.conn)?.into_iter().filter(|m| acl.can_view(m));
+    let mut machine_options = vec![forms::SelectOption::new("", "(any machine matching:)")];
+    machine_options.extend(visible.map(|m| {
+        let selected = q.machine.as_ref() == Some(&m.name);
+        forms::SelectOption::new(m.name.clone(), m.name).selected(selected)
+    }));
+
+    let spec_rows = spec_form_rows(&auth.conn)?;
+
+    Ok(page("Clowder: availability", &auth).content(html! {
+        h2 { "Find a free slot" }
+
+        form action={ (route_prefix()) "availability" } method="get" {
+            table {
+                tr {
+                    th { "Duration" }
+                    td {
+                        (forms::Input::new("hours")
+                                      .value(q.hours.clone().unwrap_or(String::from("1")))
+                                      .size(5))
+                        " hours"
+                    }
+                }
+                tr {
+                    th { "Machine" }
+                    td { (forms::Select::new("machine").set_options(machine_options)) }
+                }
+                (spec_rows)
+                tr {
+                    th /
+                    td { (forms::SubmitButton::new().label("Search")) }
+                }
+            }
+        }
+
+        @if searched {
+            h3 { "Earliest free slots" }
+
+            @if slots.is_empty() {
+                p { "No machine is free for that long." }
+            } @else {
+                table.table.table-responsive {
+                    (tables::TableHeader::new(&[ "Machine", "Free from", "Free until", "" ]))
+
+                    tbody {
+                        @for slot in &slots {
+                            tr {
+                                td { (slot.machine) }
+                                td { (slot.start) " (" (HumanTime::from(slot.start)) ")" }
+                                td {
+                                    @if let Some(t) = slot.free_until {
+                                        (t)
+                                    } @else {
+                                        "(no later reservations)"
+                                    }
+                                }
+                                td { a href=(slot.reservation_link()) { "Reserve" } }
+                            }
+                        }
+                    }
                 }
             }
         }
@@ -474,7 +3033,10 @@ fn reservation_create_page(machine: Option<String>, auth: AuthContext) -> Result
 
 #[get("/reservation/end/<id>")]
 fn reservation_end(id: i32, auth: AuthContext) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
     let (r, machine, user) = Reservation::get(id, &auth.conn)?;
+    check_may_manage(&r, &auth)?;
+    check_endable(&r)?;
 
     Ok(
         page(format!["Clowder: end reservation {}", r.id], &auth).content(html! {
@@ -514,7 +3076,8 @@ fn reservation_end(id: i32, auth: AuthContext) -> Result<Page, Error> {
                         }
                         tr {
                             td colspan="2" {
-                                form action={ "confirm/" (r.id) } method="get" {
+                                form action={ "confirm/" (r.id) } method="post" {
+                                    (csrf)
                                     input type="submit" value="End reservation" /
                                 }
                             }
@@ -525,34 +3088,127 @@ fn reservation_end(id: i32, auth: AuthContext) -> Result<Page, Error> {
     )
 }
 
-#[get("/reservation/end/confirm/<res_id>")]
+#[post("/reservation/end/confirm/<res_id>")]
 fn reservation_end_confirm(res_id: i32, auth: AuthContext) -> Result<Flash<Redirect>, Error> {
-    Reservation::get(res_id, &auth.conn)
-        .and_then(|(r, _, _)| r.end(&auth.conn))
-        .map_err(Error::DatabaseError)
-        .map(|r| {
-            Flash::new(
-                Redirect::to(format!["{}reservation/{}", route_prefix(), r.id()]),
-                "info",
-                format!["Ended reservation {}", r.id()],
-            )
-        })
+    let (r, _, _) = Reservation::get(res_id, &auth.conn)?;
+    check_may_manage(&r, &auth)?;
+    check_endable(&r)?;
+
+    let before = r.to_json();
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        let r = r.end(&auth.conn)?;
+        auth.audit(
+            AuditEvent::build("reservation.end", "reservation", r.id)
+                .before(&before)
+                .after(&r),
+        )?;
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/{}", route_prefix(), r.id()]),
+        "info",
+        format!["Ended reservation {}", r.id()],
+    ))
+}
+
+/// Only reservations that have started (and haven't already been closed) can be ended.
+fn check_endable(r: &Reservation) -> Result<(), Error> {
+    if r.is_closed() {
+        Err(Error::BadRequest(format![
+            "reservation {} has already ended or been cancelled",
+            r.id()
+        ]))
+    } else if r.scheduled_start > Utc::now() {
+        Err(Error::BadRequest(format![
+            "reservation {} hasn't started yet; cancel it instead",
+            r.id()
+        ]))
+    } else {
+        Ok(())
+    }
 }
 
 #[get("/reservations")]
 fn reservations(auth: AuthContext) -> Result<Page, Error> {
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
     let reservations = Reservation::all(false, &auth.conn)?
         .into_iter()
+        .filter(|&(_, ref m, _)| acl.can_view(m))
         .map(|(r, m, u)| (r, Some(m), Some(u)))
         .collect();
 
     Ok(page("Clowder: Reservations", &auth).content(tables::ReservationTable::new(reservations)))
 }
 
+#[get("/timeline?<zoom>&<start>")]
+fn timeline(zoom: Option<String>, start: Option<String>, auth: AuthContext) -> Result<Page, Error> {
+    let zoom = match zoom {
+        Some(ref z) => timeline::Zoom::parse(z)
+            .ok_or(Error::BadRequest(format!["invalid zoom level '{}'", z]))?,
+        None => timeline::Zoom::Week,
+    };
+
+    let start_date = match start {
+        Some(ref s) => NaiveDate::parse_from_str(s, "%Y-%m-%d")?,
+        None => Utc::today().naive_utc(),
+    };
+
+    let start = DateTime::<Utc>::from_utc(start_date.and_hms(0, 0, 0), Utc);
+    let end = start + zoom.span();
+
+    let link = |z: timeline::Zoom, d: NaiveDate| {
+        format![
+            "{}timeline?zoom={}&start={}",
+            route_prefix(),
+            z.name(),
+            d.format("%Y-%m-%d")
+        ]
+    };
+
+    let acl = AccessControl::for_user(&auth.user, &auth.conn)?;
+    let machines = Machine::all(&auth.conn)?
+        .into_iter()
+        .filter(|m| acl.can_view(m))
+        .collect();
+    let reservations = Reservation::during(start, end, &auth.conn)?
+        .into_iter()
+        .filter(|&(_, ref m, _)| acl.can_view(m))
+        .collect();
+
+    let chart = timeline::Timeline::new(zoom, start)
+        .machines(machines)
+        .reservations(reservations);
+
+    Ok(page("Clowder: timeline", &auth).content(html! {
+        h2 { "Reservation timeline" }
+
+        p {
+            a href=(link(zoom, start_date - zoom.span())) { "« Earlier" }
+            " | "
+            a href=(link(zoom, Utc::today().naive_utc())) { "Today" }
+            " | "
+            a href=(link(zoom, start_date + zoom.span())) { "Later »" }
+            " — "
+            @for z in timeline::Zoom::all().iter() {
+                @if *z == zoom {
+                    strong { (z.name()) }
+                } @else {
+                    a href=(link(*z, start_date)) { (z.name()) }
+                }
+                " "
+            }
+        }
+
+        (chart)
+    }))
+}
+
 #[get("/user/<name>")]
-fn user(name: String, auth: AuthContext) -> Result<Page, Error> {
+fn user(name: String, auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
     let user = User::with_username(&name, &auth.conn)?;
-    let superuser = auth.user.can_alter_users(&auth.conn)?;
+    let superuser = auth.user.can(Capability::AlterUsers, &auth.conn)?;
 
     let name = user.name.as_str();
     let myself = user.id == auth.user.id;
@@ -570,12 +3226,71 @@ fn user(name: String, auth: AuthContext) -> Result<Page, Error> {
         .map(|(r, m)| (r, Some(m), None))
         .collect();
 
-    Ok(page(name, &auth).content(html! {
+    let policy = user.reservation_policy(&auth.conn)?;
+    let default_policy = user.roles(&auth.conn)?.is_empty();
+    let limit = |l: Option<i32>, unit: &str| match l {
+        Some(n) => format!["{} {}", n, unit],
+        None => String::from("unlimited"),
+    };
+
+    let calendar_token = if myself {
+        CalendarToken::for_user(&user, &auth.conn)?
+    } else {
+        None
+    };
+
+    let identities = if writable {
+        Identity::for_user(&user, &auth.conn)?
+    } else {
+        Vec::new()
+    };
+
+    let ssh_keys = if writable {
+        SshKey::for_user(&user, &auth.conn)?
+    } else {
+        Vec::new()
+    };
+    let github_import = github::GitHub::from_env().is_some()
+        && identities.iter().any(|i| i.provider == "github");
+
+    let (lifetime, idle) = auth::session_timeouts();
+    let sessions = if writable {
+        Session::for_user(&user, &auth.conn)?
+            .into_iter()
+            .filter(|s| !s.is_expired(lifetime, idle))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let current_session = auth.session.as_ref().map(|s| s.id);
+
+    let sources = login::identity_sources();
+    let provider_label = |id: &str| {
+        sources
+            .iter()
+            .find(|&&(ref source, _)| source == id)
+            .map(|&(_, ref label)| label.clone())
+            .unwrap_or(id.to_string())
+    };
+
+    let can_impersonate = superuser && !myself && auth.session.is_some();
+
+    Ok(page(name, &auth).flash(flash).content(html! {
         h2 { (name) }
 
+        @if can_impersonate {
+            form action={ (route_prefix()) "user/" (user.username) "/impersonate" }
+                 method="post" {
+                (csrf)
+                (forms::SubmitButton::new().label("View Clowder as this user"))
+            }
+        }
+
         div.row {
             div class="col-md-6" {
-                form action={ (route_prefix()) "user/update/" (user.username) } method="post" {
+                form action={ (route_prefix()) "user/update/" (user.username) }
+                     method="post" {
+                    (csrf)
                     table.table.table-responsive {
                         tbody {
                             tr { th { "Username" } td { (user.username) } }
@@ -625,6 +3340,32 @@ fn user(name: String, auth: AuthContext) -> Result<Page, Error> {
                                     }
                                 }
                             }
+                            tr {
+                                th { "Reservation limits" }
+                                td {
+                                    ul.list-unstyled {
+                                        li { "Length: " (limit(policy.max_hours, "hours")) }
+                                        li {
+                                            "Advance booking: "
+                                            (limit(policy.max_advance_days, "days"))
+                                        }
+                                        li {
+                                            "Concurrent: "
+                                            (limit(policy.max_concurrent, "reservations"))
+                                        }
+                                        li {
+                                            "Weekly: "
+                                            (limit(policy.max_weekly_hours, "machine-hours"))
+                                        }
+                                    }
+                                    @if default_policy {
+                                        small.text-muted {
+                                            "These are the default limits for users without any \
+                                             roles."
+                                        }
+                                    }
+                                }
+                            }
                             tr {
                                 td colspan="2" {
                                     input.btn.btn-block.btn-warning type="submit"
@@ -638,17 +3379,460 @@ fn user(name: String, auth: AuthContext) -> Result<Page, Error> {
 
             div class="col-md-6" {
                 (tables::ReservationTable::new(reservations).show_user(false))
+
+                @if myself {
+                    h4 { "Calendar feeds" }
+
+                    p {
+                        "Subscribe to these iCalendar feeds from your calendar application. "
+                        "Anyone with these links can see the reservations in them."
+                    }
+                }
+
+                @if let Some(ref token) = calendar_token {
+
+                    ul {
+                        li {
+                            a href={ (route_prefix()) "calendar/" (token.token) "/mine.ics" } {
+                                "My reservations"
+                            }
+                        }
+                        li {
+                            a href={ (route_prefix()) "calendar/" (token.token) "/all.ics" } {
+                                "All reservations"
+                            }
+                        }
+                        li {
+                            "One machine's reservations: "
+                            code { (route_prefix()) "calendar/" (token.token) "/machine/NAME.ics" }
+                        }
+                    }
+
+                    form action={ (route_prefix()) "calendar/regenerate" } method="post" {
+                        (csrf)
+                        (forms::SubmitButton::new().label("Replace feed links"))
+                    }
+                } @else if myself {
+                    form action={ (route_prefix()) "calendar/regenerate" } method="post" {
+                        (csrf)
+                        (forms::SubmitButton::new().label("Create feed links"))
+                    }
+                }
+
+                @if writable {
+                    h4 { "SSH keys" }
+
+                    p {
+                        "These keys can log in to machines reserved by (or shared with) "
+                        (name) ", via each reservation's " code { "authorized_keys" } " link."
+                    }
+
+                    table.table.table-responsive {
+                        (tables::TableHeader::new(&[
+                            "Type", "Fingerprint", "Comment", "Added", "",
+                        ]))
+
+                        tbody {
+                            @for k in &ssh_keys {
+                                tr {
+                                    td { (k.key_type) }
+                                    td { code { (k.fingerprint) } }
+                                    td { @if let Some(ref comment) = k.comment { (comment) } }
+                                    td { (k.created_at.format(DATE_FORMAT)) }
+                                    td {
+                                        form action={
+                                            (route_prefix()) "user/" (user.username)
+                                                "/ssh-key/" (k.id) "/delete"
+                                        } method="post" {
+                                            (csrf)
+                                            (forms::SubmitButton::new().label("Remove"))
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    form action={ (route_prefix()) "user/" (user.username) "/ssh-key" }
+                        method="post" {
+                        (csrf)
+                        p {
+                            textarea name="keys" rows="3" cols="60"
+                                placeholder="ssh-ed25519 AAAA... you@laptop" {}
+                        }
+                        p { (forms::SubmitButton::new().label("Add keys")) }
+                    }
+
+                    @if github_import {
+                        form action={ (route_prefix()) "user/" (user.username) "/ssh-key/github" }
+                             method="post" {
+                            (csrf)
+                            (forms::SubmitButton::new().label("Import keys from GitHub"))
+                        }
+                    }
+
+                    h4 { "Logins" }
+
+                    table.table.table-responsive {
+                        (tables::TableHeader::new(&[ "Provider", "Identity", "Since", "" ]))
+
+                        tbody {
+                            @for i in &identities {
+                                tr {
+                                    td { (provider_label(&i.provider)) }
+                                    td { code { (i.subject) } }
+                                    td { (i.created_at.format(DATE_FORMAT)) }
+                                    td {
+                                        @if superuser {
+                                            form action={
+                                                (route_prefix()) "user/" (user.username)
+                                                    "/identity/" (i.id) "/delete"
+                                            } method="post" {
+                                                (csrf)
+                                                (forms::SubmitButton::new().label("Remove"))
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    @if superuser {
+                        form action={ (route_prefix()) "user/" (user.username) "/identity" }
+                            method="post" {
+                            (csrf)
+                            (forms::Select::new("provider").set_options(
+                                sources
+                                    .iter()
+                                    .map(|&(ref id, ref label)| {
+                                        forms::SelectOption::new(&**id, &**label)
+                                    })
+                                    .collect()
+                            ))
+                            " "
+                            (forms::Input::new("subject").size(20))
+                            " "
+                            (forms::SubmitButton::new().label("Add login"))
+                        }
+                    }
+
+                    h4 { "Sessions" }
+
+                    table.table.table-responsive {
+                        (tables::TableHeader::new(&[
+                            "Logged in", "Last seen", "From", "Browser", "",
+                        ]))
+
+                        tbody {
+                            @for s in &sessions {
+                                tr {
+                                    td { (s.created_at.format(DATE_FORMAT)) }
+                                    td { (HumanTime::from(s.last_seen)) }
+                                    td {
+                                        @if let Some(ref ip) = s.ip_address { (ip) }
+                                    }
+                                    td {
+                                        @if let Some(ref agent) = s.user_agent {
+                                            small { (agent) }
+                                        }
+                                    }
+                                    td {
+                                        @if current_session == Some(s.id) {
+                                            em { "this session" }
+                                        } @else {
+                                            form action={
+                                                (route_prefix()) "user/" (user.username)
+                                                    "/session/" (s.id) "/revoke"
+                                            } method="post" {
+                                                (csrf)
+                                                (forms::SubmitButton::new().label("Log out"))
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
             }
         }
     }))
 }
 
+#[post("/user/<name>/session/<id>/revoke")]
+fn user_session_revoke(
+    name: String,
+    id: i32,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let user = User::with_username(&name, &auth.conn)?;
+    if user.id != auth.user.id && !auth.user.can(Capability::AlterUsers, &auth.conn)? {
+        return Err(Error::NotAuthorized(format!["end {}'s sessions", user.username]));
+    }
+
+    let session = Session::get(id, &auth.conn)?;
+    if session.user_id != user.id {
+        return Err(Error::BadRequest(format!["session {} does not belong to {}", id, name]));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        auth.audit(AuditEvent::build("session.revoke", "session", session.id).before(&session))?;
+        session.delete(&auth.conn)?;
+        Ok(())
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}user/{}", route_prefix(), user.username]),
+        "info",
+        "Logged out the session",
+    ))
+}
+
+/// Check that the current user may manage another user's logins.
+fn check_identity_admin(auth: &AuthContext) -> Result<(), Error> {
+    if auth.user.can(Capability::AlterUsers, &auth.conn)? {
+        Ok(())
+    } else {
+        Err(Error::NotAuthorized(String::from("manage users' logins")))
+    }
+}
+
+#[derive(Debug, FromForm)]
+struct IdentityForm {
+    provider: String,
+    subject: String,
+}
+
+#[post("/user/<name>/identity", data = "<form>")]
+fn user_identity_add(
+    name: String,
+    form: LenientForm<IdentityForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    check_identity_admin(&auth)?;
+
+    let user = User::with_username(&name, &auth.conn)?;
+    let subject = form.subject.trim();
+    if subject.is_empty() {
+        return Err(Error::BadRequest(String::from("no identity given")));
+    }
+
+    if !login::identity_sources().iter().any(|&(ref id, _)| *id == form.provider) {
+        return Err(Error::BadRequest(format!["no such login provider: '{}'", form.provider]));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        let identity =
+            Identity::insert(&user, form.provider.clone(), subject.to_string(), &auth.conn)?;
+        auth.audit(AuditEvent::build("identity.add", "user", user.id).after(&identity))
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}user/{}", route_prefix(), user.username]),
+        "info",
+        format!["{} can now log in as {} ({})", user.username, subject, form.provider],
+    ))
+}
+
+#[post("/user/<name>/identity/<id>/delete")]
+fn user_identity_delete(
+    name: String,
+    id: i32,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    check_identity_admin(&auth)?;
+
+    let user = User::with_username(&name, &auth.conn)?;
+    let identity = Identity::get(id, &auth.conn)?;
+    if identity.user_id != user.id {
+        return Err(Error::BadRequest(format!["login {} does not belong to {}", id, name]));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        auth.audit(AuditEvent::build("identity.delete", "user", user.id).before(&identity))?;
+        identity.delete(&auth.conn)?;
+        Ok(())
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}user/{}", route_prefix(), user.username]),
+        "info",
+        format!["Removed a login from {}", user.username],
+    ))
+}
+
+/// Check that the current user may change a user's SSH keys (their own, or anyone's if an admin).
+fn check_ssh_key_owner(user: &User, auth: &AuthContext) -> Result<(), Error> {
+    if user.id == auth.user.id || auth.user.can(Capability::AlterUsers, &auth.conn)? {
+        Ok(())
+    } else {
+        Err(Error::NotAuthorized(format!["change {}'s SSH keys", user.username]))
+    }
+}
+
+///
+/// Store SSH public keys for a user, skipping any that they already have.
+/// Returns how many keys were new.
+///
+fn add_ssh_keys<'a, I>(user: &User, keys: I, auth: &AuthContext) -> Result<usize, Error>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let keys = keys
+        .into_iter()
+        .map(str::trim)
+        .filter(|k| !k.is_empty() && !k.starts_with('#'))
+        .map(PublicKey::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    auth.conn.transaction(|| {
+        let mut added = 0;
+        for key in &keys {
+            if let Some(k) = SshKey::insert(user, key, &auth.conn)? {
+                auth.audit(AuditEvent::build("ssh_key.add", "user", user.id).after(&k))?;
+                added += 1;
+            }
+        }
+
+        Ok(added)
+    })
+}
+
+#[derive(Debug, FromForm)]
+struct SshKeyForm {
+    keys: String,
+}
+
+#[post("/user/<name>/ssh-key", data = "<form>")]
+fn user_ssh_key_add(
+    name: String,
+    form: LenientForm<SshKeyForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let user = User::with_username(&name, &auth.conn)?;
+    check_ssh_key_owner(&user, &auth)?;
+
+    let added = add_ssh_keys(&user, form.keys.lines(), &auth)?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}user/{}", route_prefix(), user.username]),
+        "info",
+        format!["Added {} SSH key(s)", added],
+    ))
+}
+
+///
+/// Import the SSH keys that a user has uploaded to GitHub, for each GitHub account that they
+/// log in with.
+///
+#[post("/user/<name>/ssh-key/github")]
+fn user_ssh_key_github(name: String, auth: AuthContext) -> Result<Flash<Redirect>, Error> {
+    let user = User::with_username(&name, &auth.conn)?;
+    check_ssh_key_owner(&user, &auth)?;
+
+    let accounts = Identity::for_user(&user, &auth.conn)?
+        .into_iter()
+        .filter(|i| i.provider == "github")
+        .map(|i| i.subject)
+        .collect::<Vec<_>>();
+
+    if accounts.is_empty() {
+        return Err(Error::BadRequest(format!["{} has no GitHub login", user.username]));
+    }
+
+    let mut keys = Vec::new();
+    for account in &accounts {
+        keys.extend(github::public_keys(account)?);
+    }
+
+    let added = add_ssh_keys(&user, keys.iter().map(String::as_str), &auth)?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}user/{}", route_prefix(), user.username]),
+        "info",
+        format!["Imported {} new SSH key(s) from GitHub ({})", added, accounts.join(", ")],
+    ))
+}
+
+#[post("/user/<name>/ssh-key/<id>/delete")]
+fn user_ssh_key_delete(
+    name: String,
+    id: i32,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let user = User::with_username(&name, &auth.conn)?;
+    check_ssh_key_owner(&user, &auth)?;
+
+    let key = SshKey::get(id, &auth.conn)?;
+    if key.user_id != user.id {
+        return Err(Error::BadRequest(format!["SSH key {} does not belong to {}", id, name]));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        auth.audit(AuditEvent::build("ssh_key.delete", "user", user.id).before(&key))?;
+        key.delete(&auth.conn)?;
+        Ok(())
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}user/{}", route_prefix(), user.username]),
+        "info",
+        format!["Removed an SSH key from {}", user.username],
+    ))
+}
+
+///
+/// Start viewing Clowder as another user (e.g., to see why they can't reserve a machine).
+/// Until the administrator stops, every page says so and nothing can be changed.
+///
+#[post("/user/<name>/impersonate")]
+fn user_impersonate(name: String, auth: AuthContext) -> Result<Redirect, Error> {
+    if !auth.user.can(Capability::AlterUsers, &auth.conn)? {
+        return Err(Error::NotAuthorized(String::from("view Clowder as another user")));
+    }
+
+    let session_id = auth.session.as_ref().map(|s| s.id).ok_or_else(|| {
+        Error::BadRequest(String::from("can't view Clowder as another user without a session"))
+    })?;
+
+    let user = User::with_username(&name, &auth.conn)?;
+    if user.id == auth.user.id {
+        return Err(Error::BadRequest(String::from("can't view Clowder as yourself")));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        Session::get(session_id, &auth.conn)?.act_as(Some(&user), &auth.conn)?;
+        auth.audit(AuditEvent::build("impersonation.start", "user", user.id))
+    })?;
+    info!["{} is now viewing Clowder as {}", auth.user.username, user.username];
+
+    Ok(Redirect::to(route_prefix()))
+}
+
+#[post("/impersonate/stop")]
+fn impersonate_stop(auth: AuthContext) -> Result<Redirect, Error> {
+    let session_id = match (&auth.impersonator, &auth.session) {
+        (&Some(_), &Some(ref session)) => session.id,
+        _ => return Ok(Redirect::to(route_prefix())),
+    };
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        Session::get(session_id, &auth.conn)?.act_as(None, &auth.conn)?;
+        auth.audit(AuditEvent::build("impersonation.stop", "user", auth.user.id))
+    })?;
+    info!["{} is no longer viewing Clowder as {}", auth.real_user().username, auth.user.username];
+
+    Ok(Redirect::to(format!["{}user/{}", route_prefix(), auth.user.username]))
+}
+
 #[get("/users")]
 fn users(auth: AuthContext) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
     let conn = &auth.conn;
 
-    let can_view = auth.user.can_alter_users(conn).unwrap_or(false);
-    let can_edit = auth.user.can_alter_users(conn).unwrap_or(false);
+    let can_view = auth.user.can(Capability::AlterUsers, conn).unwrap_or(false);
+    let can_edit = auth.user.can(Capability::AlterUsers, conn).unwrap_or(false);
 
     if !can_view {
         return Err(Error::NotAuthorized(format![
@@ -677,7 +3861,9 @@ fn users(auth: AuthContext) -> Result<Page, Error> {
 
             tbody {
                 @for ref user in &users {
-                    form action={ (route_prefix()) "user/update/" (user.username) } method="post" {
+                    form action={ (route_prefix()) "user/update/" (user.username) }
+                         method="post" {
+                        (csrf)
                         tr {
                             th { (user.id) }
                             td { (user.username.clone()) }
@@ -739,6 +3925,7 @@ impl<'f> request::FromForm<'f> for UserUpdate {
                 "roles" => {
                     update.roles.insert(value);
                 }
+                forms::CSRF_PARAM => {}
                 _ => {
                     return Err(Error::InvalidData(format![
                         "invalid form data name: '{}'",
@@ -756,14 +3943,14 @@ impl<'f> request::FromForm<'f> for UserUpdate {
 fn user_update(
     who: String,
     auth: AuthContext,
-    form: Form<UserUpdate>,
+    form: LenientForm<UserUpdate>,
 ) -> Result<Flash<Redirect>, Error> {
     let conn = &auth.conn;
 
     let mut user = User::with_username(&who, conn)
         .map_err(|err| Error::BadRequest(format!["No such user: '{}' ({})", who, err]))?;
 
-    let superuser = auth.user.can_alter_users(conn).unwrap_or(false);
+    let superuser = auth.user.can(Capability::AlterUsers, conn).unwrap_or(false);
 
     if !(user.id == auth.user.id || superuser) {
         return Err(Error::NotAuthorized(String::from(
@@ -771,21 +3958,37 @@ fn user_update(
         )));
     }
 
-    // Has the user requested a name change?
-    if user.name != form.name {
-        user = user.change_name(form.name.clone(), conn)?;
-    }
+    let user = conn.transaction::<_, Error, _>(|| {
+        let before = user.audit_snapshot(conn)?;
 
-    // Only admin users can (currently) modify email addresses, since they are almost akin to
-    // login credentials. We will revisit this in the future.
-    if superuser {
-        user.set_emails(&form.emails, conn)?;
-    }
+        // Has the user requested a name change?
+        if user.name != form.name {
+            user = user.change_name(form.name.clone(), conn)?;
+        }
 
-    // Only admin users can change users' roles.
-    if superuser {
-        user.set_roles(&form.roles, conn)?;
-    }
+        // Only admin users can (currently) modify email addresses, since they are almost akin to
+        // login credentials. We will revisit this in the future.
+        if superuser {
+            user.set_emails(&form.emails, conn)?;
+        }
+
+        // Only admin users can change users' roles.
+        if superuser {
+            user.set_roles(&form.roles, conn)?;
+            roles::check_administered(&auth)?;
+        }
+
+        let after = user.audit_snapshot(conn)?;
+        if after != before {
+            auth.audit(
+                AuditEvent::build("user.update", "user", user.id)
+                    .before(&before)
+                    .after(&after),
+            )?;
+        }
+
+        Ok(user)
+    })?;
 
     Ok(Flash::new(
         Redirect::to(format!["{}user/{}", route_prefix(), user.username]),
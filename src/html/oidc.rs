@@ -0,0 +1,358 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use super::hyper;
+use super::rustc_serialize;
+use super::url;
+
+use chrono::Utc;
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use hyper::header;
+use hyper_native_tls::NativeTlsClient;
+use openssl::bn::BigNum;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+use rustc_serialize::base64::{self, FromBase64, ToBase64};
+use rustc_serialize::json::Json;
+use rustc_serialize::Decodable;
+use std::env;
+use std::io::Read;
+
+use super::login::{IdentityProvider, LoginState};
+use super::route_prefix;
+use super::Error;
+
+/// How much clock skew (in seconds) we tolerate between ourselves and an identity provider.
+const CLOCK_LEEWAY: i64 = 60;
+
+///
+/// A generic OpenID Connect identity provider, configured from the environment (or `.env`):
+///
+/// `CLOWDER_OIDC_<ID>_ISSUER`
+/// : the issuer URL, from which we fetch the provider's discovery document
+///
+/// `CLOWDER_OIDC_<ID>_CLIENT_ID`, `CLOWDER_OIDC_<ID>_CLIENT_SECRET`
+/// : the credentials that the provider issued to Clowder (the secret is optional for public
+///   clients, which rely on PKCE alone)
+///
+/// `CLOWDER_OIDC_<ID>_LABEL`
+/// : the provider's name as shown on the login dialog (defaults to the ID)
+///
+/// Users are identified by the provider's `sub` (subject) claim.
+///
+pub struct OidcProvider {
+    id: String,
+    label: String,
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+}
+
+/// The parts of an OpenID Connect discovery document that we use.
+#[derive(Debug, RustcDecodable)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+impl OidcProvider {
+    pub fn from_env(id: &str) -> Result<OidcProvider, Error> {
+        let var = |name: &str| {
+            format!["CLOWDER_OIDC_{}_{}", id.to_uppercase().replace('-', "_"), name]
+        };
+
+        Ok(OidcProvider {
+            id: id.to_string(),
+            label: env::var(var("LABEL")).unwrap_or(id.to_string()),
+            issuer: crate::getenv(&var("ISSUER"))?
+                .trim_right_matches('/')
+                .to_string(),
+            client_id: crate::getenv(&var("CLIENT_ID"))?,
+            client_secret: env::var(var("CLIENT_SECRET")).ok(),
+        })
+    }
+
+    /// Where the provider should send users back to (`CLOWDER_URL` is our public base URL).
+    fn redirect_uri(&self) -> Result<String, Error> {
+        let base = crate::getenv("CLOWDER_URL")?;
+
+        Ok(format![
+            "{}{}login/{}/callback",
+            base.trim_right_matches('/'),
+            route_prefix(),
+            self.id
+        ])
+    }
+
+    fn discover(&self, http: &hyper::Client) -> Result<Discovery, Error> {
+        let url = format!["{}/.well-known/openid-configuration", self.issuer];
+        let discovery: Discovery = get_json(http, &url)?;
+
+        // OpenID Connect Discovery 1.0, section 4.3: the issuer must match exactly.
+        if discovery.issuer.trim_right_matches('/') != self.issuer {
+            return Err(Error::AuthError(format![
+                "{} claims to be issuer '{}'",
+                self.issuer, discovery.issuer
+            ]));
+        }
+
+        Ok(discovery)
+    }
+
+    /// Exchange an authorization code (and our PKCE verifier) for an ID token.
+    fn id_token(
+        &self,
+        http: &hyper::Client,
+        discovery: &Discovery,
+        code: &str,
+        login: &LoginState,
+    ) -> Result<String, Error> {
+        let redirect_uri = self.redirect_uri()?;
+
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", &redirect_uri)
+            .append_pair("client_id", &self.client_id)
+            .append_pair("code_verifier", &login.verifier);
+
+        if let Some(ref secret) = self.client_secret {
+            form.append_pair("client_secret", secret);
+        }
+
+        let form_data = form.finish();
+
+        let mut response = http
+            .post(&discovery.token_endpoint)
+            .header(header::ContentType::form_url_encoded())
+            .header(header::Accept::json())
+            .body(&form_data)
+            .send()?;
+
+        let body = Json::from_str(&response_str(&mut response)?)?;
+
+        if let Some(token) = body.find("id_token").and_then(Json::as_string) {
+            return Ok(token.to_string());
+        }
+
+        let message = body
+            .find("error_description")
+            .or(body.find("error"))
+            .and_then(Json::as_string)
+            .unwrap_or("no ID token in token response");
+
+        Err(Error::AuthError(format!["{}: {}", self.label, message]))
+    }
+
+    ///
+    /// Check an ID token's signature and claims (OpenID Connect Core 1.0, section 3.1.3.7),
+    /// returning the subject that it vouches for.
+    ///
+    fn validate(
+        &self,
+        http: &hyper::Client,
+        discovery: &Discovery,
+        token: &str,
+        login: &LoginState,
+    ) -> Result<String, Error> {
+        let invalid = |msg: &str| Error::AuthError(format!["invalid ID token: {}", msg]);
+
+        let parts = token.split('.').collect::<Vec<_>>();
+        if parts.len() != 3 {
+            return Err(invalid("not a JSON Web Signature"));
+        }
+
+        let header = decode_segment(parts[0])?;
+        let claims = decode_segment(parts[1])?;
+        let signature = parts[2]
+            .from_base64()
+            .map_err(|_| invalid("malformed signature"))?;
+
+        // We request tokens directly from the token endpoint over TLS, but we still check the
+        // signature rather than trusting the transport alone.
+        if header.find("alg").and_then(Json::as_string) != Some("RS256") {
+            return Err(invalid("only RS256 signatures are supported"));
+        }
+
+        let kid = header.find("kid").and_then(Json::as_string);
+        let key = signing_key(http, &discovery.jwks_uri, kid)?;
+
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key)?;
+        verifier.update(parts[0].as_bytes())?;
+        verifier.update(b".")?;
+        verifier.update(parts[1].as_bytes())?;
+        if !verifier.verify(&signature)? {
+            return Err(invalid("bad signature"));
+        }
+
+        let string_claim = |name: &str| claims.find(name).and_then(Json::as_string);
+        let time_claim = |name: &str| claims.find(name).and_then(Json::as_i64);
+
+        if string_claim("iss") != Some(discovery.issuer.as_str()) {
+            return Err(invalid("wrong issuer"));
+        }
+
+        let audiences = match claims.find("aud") {
+            Some(&Json::String(ref aud)) => vec![aud.as_str()],
+            Some(&Json::Array(ref auds)) => auds.iter().filter_map(Json::as_string).collect(),
+            _ => vec![],
+        };
+
+        if !audiences.contains(&self.client_id.as_str()) {
+            return Err(invalid("not issued to this client"));
+        }
+
+        if audiences.len() > 1 && string_claim("azp") != Some(self.client_id.as_str()) {
+            return Err(invalid("not authorized for this client"));
+        }
+
+        let now = Utc::now().timestamp();
+        match time_claim("exp") {
+            Some(exp) if now < exp + CLOCK_LEEWAY => {}
+            _ => return Err(invalid("expired")),
+        }
+
+        match time_claim("iat") {
+            Some(iat) if iat <= now + CLOCK_LEEWAY => {}
+            _ => return Err(invalid("issued in the future")),
+        }
+
+        if string_claim("nonce") != Some(login.nonce.as_str()) {
+            return Err(invalid("wrong nonce"));
+        }
+
+        match string_claim("sub") {
+            Some(sub) if !sub.is_empty() => Ok(sub.to_string()),
+            _ => Err(invalid("no subject")),
+        }
+    }
+}
+
+impl IdentityProvider for OidcProvider {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn label(&self) -> &str {
+        &self.label
+    }
+
+    fn authorization_url(&self, login: &LoginState) -> Result<String, Error> {
+        let discovery = self.discover(&http_client()?)?;
+
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("response_type", "code")
+            .append_pair("scope", "openid")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri()?)
+            .append_pair("state", &login.state)
+            .append_pair("nonce", &login.nonce)
+            .append_pair("code_challenge", &pkce_challenge(&login.verifier))
+            .append_pair("code_challenge_method", "S256")
+            .finish();
+
+        let separator = if discovery.authorization_endpoint.contains('?') { '&' } else { '?' };
+
+        Ok(format!["{}{}{}", discovery.authorization_endpoint, separator, query])
+    }
+
+    fn authenticate(&self, code: &str, login: &LoginState) -> Result<String, Error> {
+        let http = http_client()?;
+        let discovery = self.discover(&http)?;
+        let token = self.id_token(&http, &discovery, code, login)?;
+
+        self.validate(&http, &discovery, &token, login)
+    }
+}
+
+fn http_client() -> Result<hyper::Client, Error> {
+    let tls_connector = hyper::net::HttpsConnector::new(NativeTlsClient::new()?);
+    Ok(hyper::Client::with_connector(tls_connector))
+}
+
+fn get_json<T: Decodable>(http: &hyper::Client, url: &str) -> Result<T, Error> {
+    let mut response = http
+        .get(url)
+        .header(header::Accept::json())
+        .header(header::UserAgent(String::from("musec/clowder")))
+        .send()?;
+
+    let body = response_str(&mut response)?;
+
+    rustc_serialize::json::decode(&body)
+        .map_err(|err| format!["failed to parse {}: {}", body, err])
+        .map_err(Error::InvalidData)
+}
+
+fn response_str(response: &mut hyper::client::response::Response) -> Result<String, Error> {
+    let mut body = String::new();
+
+    response
+        .read_to_string(&mut body)
+        .map(|_| body)
+        .map_err(|e| Error::InvalidData(format!["invalid response from identity provider: {}", e]))
+}
+
+/// The PKCE `S256` code challenge for a code verifier (RFC 7636, section 4.2).
+fn pkce_challenge(verifier: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input_str(verifier);
+
+    let mut digest = [0; 32];
+    hasher.result(&mut digest);
+
+    digest.to_base64(base64::URL_SAFE)
+}
+
+/// Decode a base64url-encoded JSON segment of a JSON Web Token.
+fn decode_segment(segment: &str) -> Result<Json, Error> {
+    let bytes = segment
+        .from_base64()
+        .map_err(|e| Error::AuthError(format!["malformed ID token: {}", e]))?;
+
+    let text = String::from_utf8(bytes)
+        .map_err(|e| Error::AuthError(format!["malformed ID token: {}", e]))?;
+
+    Ok(Json::from_str(&text)?)
+}
+
+/// Find the RSA key that an identity provider signed a token with in its JSON Web Key Set.
+fn signing_key(
+    http: &hyper::Client,
+    jwks_uri: &str,
+    kid: Option<&str>,
+) -> Result<PKey<Public>, Error> {
+    let jwks: Json = get_json(http, jwks_uri)?;
+    let keys = jwks.find("keys").and_then(Json::as_array).cloned().unwrap_or_default();
+
+    let key = keys
+        .iter()
+        .filter(|k| k.find("kty").and_then(Json::as_string) == Some("RSA"))
+        .filter(|k| k.find("use").and_then(Json::as_string).unwrap_or("sig") == "sig")
+        .find(|k| kid.is_none() || k.find("kid").and_then(Json::as_string) == kid)
+        .ok_or_else(|| Error::AuthError(String::from("ID token signed with an unknown key")))?;
+
+    let component = |name: &str| -> Result<BigNum, Error> {
+        let bytes = key
+            .find(name)
+            .and_then(Json::as_string)
+            .and_then(|s| s.from_base64().ok())
+            .ok_or_else(|| Error::InvalidData(format!["malformed RSA key ({})", name]))?;
+
+        Ok(BigNum::from_slice(&bytes)?)
+    };
+
+    let rsa = Rsa::from_public_components(component("n")?, component("e")?)?;
+    Ok(PKey::from_rsa(rsa)?)
+}
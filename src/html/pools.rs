@@ -0,0 +1,504 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use db::models::*;
+use diesel::pg::PgConnection as Connection;
+use diesel::Connection as DieselConnection;
+use maud::*;
+use rocket::request::{FlashMessage, LenientForm};
+use rocket::response::{Flash, Redirect};
+
+use super::auth::AuthContext;
+use super::bootstrap::Page;
+use super::link::Link;
+use super::{forms, page, route_prefix, tables};
+use super::Error;
+
+fn pool_redirect<S: Into<String>>(pool: &Pool, message: S) -> Flash<Redirect> {
+    Flash::new(
+        Redirect::to(format!["{}pool/{}", route_prefix(), pool.name]),
+        "info",
+        message.into(),
+    )
+}
+
+fn machine_redirect<S: Into<String>>(m: &Machine, message: S) -> Flash<Redirect> {
+    Flash::new(
+        Redirect::to(format!["{}machine/{}", route_prefix(), m.name]),
+        "info",
+        message.into(),
+    )
+}
+
+/// Look up a pool, checking that the current user may change its access rules.
+fn administered_pool(name: &str, auth: &AuthContext) -> Result<Pool, Error> {
+    let pool = Pool::with_name(name, &auth.conn)?;
+    if !AccessControl::for_user(&auth.user, &auth.conn)?.can_administer_pool(&pool) {
+        return Err(Error::NotAuthorized(format!["administer pool {}", pool.name]));
+    }
+
+    Ok(pool)
+}
+
+/// Look up a machine, checking that the current user may change its access rules.
+fn administered_machine(name: &str, auth: &AuthContext) -> Result<Machine, Error> {
+    let m = Machine::with_name(name, &auth.conn)?;
+    if !AccessControl::for_user(&auth.user, &auth.conn)?.can_administer(&m) {
+        return Err(Error::NotAuthorized(format!["administer machine {}", m.name]));
+    }
+
+    Ok(m)
+}
+
+/// A summary of the permissions granted by an access rule.
+fn describe(access: Access) -> &'static str {
+    if access.administer {
+        "view, reserve and administer"
+    } else if access.reserve {
+        "view and reserve"
+    } else if access.view {
+        "view"
+    } else {
+        "nothing"
+    }
+}
+
+///
+/// Render an access control list, with buttons to remove rules if `action` is given
+/// (removal requests are posted to `{action}/{rule id}/delete`, with the `csrf` field).
+///
+pub fn rule_table(
+    rules: &[AccessRule],
+    action: Option<&str>,
+    csrf: &Markup,
+    c: &Connection,
+) -> Result<Markup, Error> {
+    let mut rows = Vec::new();
+    for r in rules {
+        rows.push((r.id, r.grantee(c)?, describe(r.access())));
+    }
+
+    Ok(html! {
+        @if rows.is_empty() {
+            p { em { "No access rules: open to everyone." } }
+        } @else {
+            table.table.table-responsive {
+                (tables::TableHeader::new(&[ "Who", "May", "" ]))
+
+                tbody {
+                    @for &(id, ref who, may) in &rows {
+                        tr {
+                            td { (who) }
+                            td { (may) }
+                            td {
+                                @if let Some(a) = action {
+                                    form action={ (a) "/" (id) "/delete" } method="post" {
+                                        (csrf)
+                                        (forms::SubmitButton::new().label("Remove"))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// A form for adding an access rule (posted to `action`, with the `csrf` field).
+pub fn rule_form(action: &str, csrf: &Markup) -> Markup {
+    let kinds = vec![
+        forms::SelectOption::new("user", "User"),
+        forms::SelectOption::new("role", "Role"),
+        forms::SelectOption::new("team", "Team"),
+    ];
+
+    html! {
+        form action={ (action) } method="post" {
+            (csrf)
+            "Allow "
+            (forms::Select::new("kind").set_options(kinds))
+            " "
+            (forms::Input::new("name").size(15))
+            " to "
+            label { input type="checkbox" name="view" checked / " view" }
+            " "
+            label { input type="checkbox" name="reserve" / " reserve" }
+            " "
+            label { input type="checkbox" name="administer" / " administer" }
+            " "
+            (forms::SubmitButton::new().label("Add rule"))
+        }
+    }
+}
+
+#[derive(Debug, FromForm)]
+pub struct RuleForm {
+    kind: String,
+    name: String,
+    view: bool,
+    reserve: bool,
+    administer: bool,
+}
+
+impl RuleForm {
+    fn access(&self) -> Access {
+        Access {
+            view: self.view,
+            reserve: self.reserve,
+            administer: self.administer,
+        }
+    }
+}
+
+/// Look up whoever a new access rule names and pass them to `grant`.
+fn grant_with<F>(form: &RuleForm, c: &Connection, grant: F) -> Result<String, Error>
+where
+    F: Fn(Grantee) -> Result<AccessRule, Error>,
+{
+    let name = form.name.trim();
+
+    match form.kind.as_str() {
+        "user" => grant(Grantee::User(&User::with_username(name, c)?)),
+        "role" => grant(Grantee::Role(&Role::with_name(name, c)?)),
+        "team" => grant(Grantee::Team(&Team::with_name(name, c)?)),
+        other => return Err(Error::BadRequest(format!["invalid grantee kind '{}'", other])),
+    }?;
+
+    Ok(format!["Allowed {} {} to {}", form.kind, name, describe(form.access())])
+}
+
+#[get("/pools")]
+pub fn pools(auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let conn = &auth.conn;
+    let creator = auth.user.can(Capability::AlterMachines, conn)?;
+
+    let mut rows = Vec::new();
+    for p in Pool::all(conn)? {
+        let machines = p.machines(conn)?.len();
+        let rules = p.access_rules(conn)?.len();
+        rows.push((p, machines, rules));
+    }
+
+    Ok(page("Clowder: machine pools", &auth).flash(flash).content(html! {
+        h2 { "Machine pools" }
+
+        p {
+            "Access to the machines in a pool is governed by the pool's access rules (along "
+            "with any rules for the individual machines). Machines without any access rules are "
+            "open to everyone."
+        }
+
+        @if !rows.is_empty() {
+            table.table.table-responsive {
+                (tables::TableHeader::new(&[ "Pool", "Description", "Machines", "Access rules" ]))
+
+                tbody {
+                    @for &(ref p, machines, rules) in &rows {
+                        tr {
+                            td { a href={ (route_prefix()) "pool/" (p.name) } { (p.name) } }
+                            td { (p.description) }
+                            td { (machines) }
+                            td { @if rules == 0 { "open" } @else { (rules) } }
+                        }
+                    }
+                }
+            }
+        }
+
+        @if creator {
+            h3 { "Create a pool" }
+
+            form action={ (route_prefix()) "pool/create" } method="post" {
+                (csrf)
+                table {
+                    tr {
+                        th { "Name" }
+                        td { (forms::Input::new("name").size(20)) }
+                    }
+                    tr {
+                        th { "Description" }
+                        td { (forms::Input::new("description").size(45)) }
+                    }
+                    tr {
+                        th /
+                        td { (forms::SubmitButton::new().label("Create pool")) }
+                    }
+                }
+            }
+        }
+    }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct PoolCreateForm {
+    name: String,
+    description: String,
+}
+
+#[post("/pool/create", data = "<form>")]
+pub fn create(
+    form: LenientForm<PoolCreateForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    if !auth.user.can(Capability::AlterMachines, &auth.conn)? {
+        return Err(Error::NotAuthorized(String::from("create machine pools")));
+    }
+
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(Error::BadRequest(String::from("every pool needs a name")));
+    }
+
+    let pool = auth.conn.transaction::<_, Error, _>(|| {
+        let pool = Pool::insert(
+            name.to_string(),
+            form.description.trim().to_string(),
+            &auth.conn,
+        )?;
+        auth.audit(AuditEvent::build("pool.create", "pool", pool.id).after(&pool))?;
+        Ok(pool)
+    })?;
+
+    Ok(pool_redirect(&pool, format!["Created pool {}", pool.name]))
+}
+
+#[get("/pool/<name>")]
+pub fn pool(name: String, auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let conn = &auth.conn;
+    let pool = Pool::with_name(&name, conn)?;
+    let acl = AccessControl::for_user(&auth.user, conn)?;
+    let administrator = acl.can_administer_pool(&pool);
+    let machine_mover = auth.user.can(Capability::AlterMachines, conn)?;
+
+    let machines = pool
+        .machines(conn)?
+        .into_iter()
+        .filter(|m| acl.can_view(m))
+        .collect::<Vec<_>>();
+
+    let machine_options = if machine_mover {
+        Machine::all(conn)?
+            .into_iter()
+            .filter(|m| m.pool_id != Some(pool.id))
+            .map(|m| forms::SelectOption::new(m.name.clone(), m.name))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let action = format!["{}pool/{}", route_prefix(), pool.name];
+    let rule_action = format!["{}/rule", action];
+    let rules = rule_table(
+        &pool.access_rules(conn)?,
+        if administrator { Some(rule_action.as_str()) } else { None },
+        &csrf,
+        conn,
+    )?;
+
+    Ok(page(format!["Clowder: pool {}", pool.name], &auth)
+        .flash(flash)
+        .content(html! {
+            h2 { "Pool " (pool.name) }
+
+            @if !pool.description.is_empty() {
+                p { (pool.description) }
+            }
+
+            h4 { "Machines" }
+            @if machines.is_empty() {
+                p { em { "No machines." } }
+            } @else {
+                ul {
+                    @for m in &machines {
+                        li {
+                            (Link::from(m))
+                            @if machine_mover {
+                                form.form-inline action={ (action) "/remove" }
+                                     method="post" {
+                                    (csrf)
+                                    input type="hidden" name="machine" value=(m.name) /
+                                    (forms::SubmitButton::new().label("Remove from pool"))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if machine_mover {
+                form action={ (action) "/add" } method="post" {
+                    (csrf)
+                    "Add "
+                    (forms::Select::new("machine").set_options(machine_options))
+                    " to this pool "
+                    (forms::SubmitButton::new().label("Add"))
+                }
+            }
+
+            h4 { "Access rules" }
+            (rules)
+
+            @if administrator {
+                (rule_form(&rule_action, &csrf))
+            }
+        }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct PoolMachineForm {
+    machine: String,
+}
+
+#[post("/pool/<name>/add", data = "<form>")]
+pub fn add_machine(
+    name: String,
+    form: LenientForm<PoolMachineForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    if !auth.user.can(Capability::AlterMachines, &auth.conn)? {
+        return Err(Error::NotAuthorized(String::from("alter machines")));
+    }
+
+    let pool = Pool::with_name(&name, &auth.conn)?;
+    let before = Machine::with_name(&form.machine, &auth.conn)?;
+    let m = auth.conn.transaction::<_, Error, _>(|| {
+        let m = before.clone().set_pool(Some(&pool), &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("machine.pool", "machine", m.id)
+                .before(&before)
+                .after(&m),
+        )?;
+        Ok(m)
+    })?;
+
+    Ok(pool_redirect(&pool, format!["Added {} to pool {}", m.name, pool.name]))
+}
+
+#[post("/pool/<name>/remove", data = "<form>")]
+pub fn remove_machine(
+    name: String,
+    form: LenientForm<PoolMachineForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    if !auth.user.can(Capability::AlterMachines, &auth.conn)? {
+        return Err(Error::NotAuthorized(String::from("alter machines")));
+    }
+
+    let pool = Pool::with_name(&name, &auth.conn)?;
+    let m = Machine::with_name(&form.machine, &auth.conn)?;
+    if m.pool_id != Some(pool.id) {
+        return Err(Error::BadRequest(format![
+            "{} is not in pool {}",
+            m.name, pool.name
+        ]));
+    }
+
+    let before = m.clone();
+    let m = auth.conn.transaction::<_, Error, _>(|| {
+        let m = m.set_pool(None, &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("machine.pool", "machine", m.id)
+                .before(&before)
+                .after(&m),
+        )?;
+        Ok(m)
+    })?;
+
+    Ok(pool_redirect(&pool, format!["Removed {} from pool {}", m.name, pool.name]))
+}
+
+#[post("/pool/<name>/rule", data = "<form>")]
+pub fn add_pool_rule(
+    name: String,
+    form: LenientForm<RuleForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let pool = administered_pool(&name, &auth)?;
+    let message = grant_with(&form, conn, |g| {
+        conn.transaction(|| {
+            let rule = pool.grant(g, form.access(), conn)?;
+            auth.audit(AuditEvent::build("pool.grant", "pool", pool.id).after(&rule))?;
+            Ok(rule)
+        })
+    })?;
+
+    Ok(pool_redirect(&pool, message))
+}
+
+#[post("/pool/<name>/rule/<id>/delete")]
+pub fn delete_pool_rule(
+    name: String,
+    id: i32,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let pool = administered_pool(&name, &auth)?;
+    let rule = AccessRule::get(id, &auth.conn)?;
+    if rule.pool_id != Some(pool.id) {
+        return Err(Error::BadRequest(format![
+            "access rule {} does not belong to pool {}",
+            id, pool.name
+        ]));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        auth.audit(AuditEvent::build("pool.revoke", "pool", pool.id).before(&rule))?;
+        rule.delete(&auth.conn)?;
+        Ok(())
+    })?;
+
+    Ok(pool_redirect(&pool, format!["Removed access rule {}", id]))
+}
+
+#[post("/machine/<name>/rule", data = "<form>")]
+pub fn add_machine_rule(
+    name: String,
+    form: LenientForm<RuleForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let m = administered_machine(&name, &auth)?;
+    let message = grant_with(&form, conn, |g| {
+        conn.transaction(|| {
+            let rule = m.grant(g, form.access(), conn)?;
+            auth.audit(AuditEvent::build("machine.grant", "machine", m.id).after(&rule))?;
+            Ok(rule)
+        })
+    })?;
+
+    Ok(machine_redirect(&m, message))
+}
+
+#[post("/machine/<name>/rule/<id>/delete")]
+pub fn delete_machine_rule(
+    name: String,
+    id: i32,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let m = administered_machine(&name, &auth)?;
+    let rule = AccessRule::get(id, &auth.conn)?;
+    if rule.machine_id != Some(m.id) {
+        return Err(Error::BadRequest(format![
+            "access rule {} does not belong to {}",
+            id, m.name
+        ]));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        auth.audit(AuditEvent::build("machine.revoke", "machine", m.id).before(&rule))?;
+        rule.delete(&auth.conn)?;
+        Ok(())
+    })?;
+
+    Ok(machine_redirect(&m, format!["Removed access rule {}", id]))
+}
@@ -0,0 +1,457 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use std::collections::BTreeSet;
+
+use db::models::*;
+use diesel::Connection as DieselConnection;
+use maud::*;
+use rocket::request::{self, FlashMessage, LenientForm};
+use rocket::response::{Flash, Redirect};
+
+use super::auth::AuthContext;
+use super::bootstrap::Page;
+use super::link::Link;
+use super::{forms, ldap, optional_int, page, route_prefix, tables};
+use super::Error;
+
+/// Check that the current user may edit roles.
+fn check_role_editor(auth: &AuthContext) -> Result<(), Error> {
+    if auth.user.can(Capability::AlterUsers, &auth.conn)? {
+        Ok(())
+    } else {
+        Err(Error::NotAuthorized(String::from("edit roles")))
+    }
+}
+
+///
+/// Check that a change to roles (within the current transaction) leaves someone able to alter
+/// users and roles, so that administrators can't lock themselves out.
+///
+pub fn check_administered(auth: &AuthContext) -> Result<(), Error> {
+    if Capability::AlterUsers.is_held(&auth.conn)? {
+        Ok(())
+    } else {
+        Err(Error::BadRequest(format![
+            "this would leave nobody with the {} capability",
+            Capability::AlterUsers
+        ]))
+    }
+}
+
+/// Check that no other role already has a name.
+fn check_unique(name: &str, except: Option<&Role>, auth: &AuthContext) -> Result<(), Error> {
+    if name.is_empty() {
+        return Err(Error::BadRequest(String::from("every role needs a name")));
+    }
+
+    let clash = Role::all(&auth.conn)?
+        .into_iter()
+        .any(|r| r.name == name && except.map(|e| e.id != r.id).unwrap_or(true));
+
+    if clash {
+        Err(Error::BadRequest(format!["there is already a role called '{}'", name]))
+    } else {
+        Ok(())
+    }
+}
+
+fn role_redirect<S: Into<String>>(role: &Role, message: S) -> Flash<Redirect> {
+    Flash::new(
+        Redirect::to(format!["{}role/{}", route_prefix(), role.name]),
+        "info",
+        message.into(),
+    )
+}
+
+#[get("/roles")]
+pub fn roles(auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    check_role_editor(&auth)?;
+    let conn = &auth.conn;
+
+    let mut rows = Vec::new();
+    for r in Role::all(conn)? {
+        let capabilities = r
+            .capabilities(conn)?
+            .iter()
+            .map(Capability::name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let members = r.members(conn)?.len();
+
+        rows.push((r, capabilities, members));
+    }
+
+    Ok(page("Clowder: roles", &auth).flash(flash).content(html! {
+        h2 { "Roles" }
+
+        table.table.table-responsive {
+            (tables::TableHeader::new(&[ "Role", "Capabilities", "Members" ]))
+
+            tbody {
+                @for &(ref r, ref capabilities, members) in &rows {
+                    tr {
+                        td { a href={ (route_prefix()) "role/" (r.name) } { (r.name) } }
+                        td { (capabilities) }
+                        td { (members) }
+                    }
+                }
+            }
+        }
+
+        h3 { "Create a role" }
+
+        form action={ (route_prefix()) "role/create" } method="post" {
+            (csrf)
+            (forms::Input::new("name").size(20))
+            " "
+            (forms::SubmitButton::new().label("Create role"))
+        }
+    }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct RoleCreateForm {
+    name: String,
+}
+
+#[post("/role/create", data = "<form>")]
+pub fn create(
+    form: LenientForm<RoleCreateForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    check_role_editor(&auth)?;
+
+    let name = form.name.trim();
+    check_unique(name, None, &auth)?;
+
+    let role = auth.conn.transaction::<_, Error, _>(|| {
+        let role = Role::insert(name.to_string(), &auth.conn)?;
+        auth.audit(AuditEvent::build("role.create", "role", role.id).after(&role))?;
+        Ok(role)
+    })?;
+
+    Ok(role_redirect(&role, format!["Created role {}", role.name]))
+}
+
+#[get("/role/<name>")]
+pub fn role(name: String, auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    check_role_editor(&auth)?;
+    let conn = &auth.conn;
+
+    let role = Role::with_name(&name, conn)?;
+    let capabilities = role.capabilities(conn)?;
+    let members = role.members(conn)?;
+    let ldap_groups = role.ldap_groups(conn)?;
+    let show_ldap = ldap::configured() || !ldap_groups.is_empty();
+
+    let limit = |l: Option<i32>| l.map(|n| n.to_string()).unwrap_or_default();
+    let action = |a: &str| format!["{}role/{}/{}", route_prefix(), role.name, a];
+
+    Ok(page(format!["Clowder: role {}", role.name], &auth)
+        .flash(flash)
+        .content(html! {
+            h2 { "Role " (role.name) }
+
+            form action={ (action("update")) } method="post" {
+                (csrf)
+                table.lefty {
+                    tr {
+                        th { "Name" }
+                        td { (forms::Input::new("name").value(role.name.clone()).size(20)) }
+                    }
+                    tr {
+                        th { "Capabilities" }
+                        td {
+                            @for cap in Capability::all() {
+                                label {
+                                    input type="checkbox" name="capabilities" value=(cap.name())
+                                        checked?[capabilities.contains(cap)] /
+                                    " " (cap.description())
+                                }
+                                br /
+                            }
+                        }
+                    }
+                    tr {
+                        th { "Longest reservation" }
+                        td {
+                            (forms::Input::new("max_hours")
+                                .value(limit(role.max_reservation_hours))
+                                .size(5))
+                            " hours"
+                        }
+                    }
+                    tr {
+                        th { "Furthest in advance" }
+                        td {
+                            (forms::Input::new("max_advance_days")
+                                .value(limit(role.max_advance_days))
+                                .size(5))
+                            " days"
+                        }
+                    }
+                    tr {
+                        th { "Concurrent reservations" }
+                        td {
+                            (forms::Input::new("max_concurrent")
+                                .value(limit(role.max_concurrent_reservations))
+                                .size(5))
+                        }
+                    }
+                    tr {
+                        th { "Weekly machine time" }
+                        td {
+                            (forms::Input::new("max_weekly_hours")
+                                .value(limit(role.max_weekly_hours))
+                                .size(5))
+                            " hours"
+                        }
+                    }
+                    tr {
+                        th /
+                        td {
+                            small { "Leave a limit empty for no limit." }
+                            br /
+                            (forms::SubmitButton::new().label("Update role"))
+                        }
+                    }
+                }
+            }
+
+            h4 { "Members" }
+            @if members.is_empty() {
+                p { em { "Nobody inhabits this role." } }
+            } @else {
+                ul {
+                    @for u in &members {
+                        li { (Link::from(u)) }
+                    }
+                }
+            }
+
+            @if show_ldap {
+                h4 { "LDAP groups" }
+
+                p {
+                    "Members of these LDAP groups inhabit this role. Each time a user logs in "
+                    "via LDAP, they are added to or removed from the role to match the directory."
+                }
+
+                ul {
+                    @for g in &ldap_groups {
+                        li {
+                            form.form-inline
+                                action={ (action(&format!["ldap/{}/delete", g.id])) }
+                                method="post" {
+                                (csrf)
+                                code { (g.group_name) }
+                                " "
+                                (forms::SubmitButton::new().label("Remove"))
+                            }
+                        }
+                    }
+                }
+
+                form action={ (action("ldap")) } method="post" {
+                    (csrf)
+                    (forms::Input::new("group").size(20))
+                    " "
+                    (forms::SubmitButton::new().label("Add LDAP group"))
+                }
+            }
+
+            form action={ (action("delete")) } method="post" {
+                (csrf)
+                (forms::SubmitButton::new().label("Delete role"))
+            }
+        }))
+}
+
+pub struct RoleUpdate {
+    name: String,
+    capabilities: BTreeSet<Capability>,
+    max_hours: String,
+    max_advance_days: String,
+    max_concurrent: String,
+    max_weekly_hours: String,
+}
+
+// TODO: use #[derive(FromForm)] once SergioBenitez/Rocket#205 is resolved
+impl<'f> request::FromForm<'f> for RoleUpdate {
+    type Error = Error;
+
+    fn from_form(form_items: &mut request::FormItems<'f>, _: bool) -> Result<Self, Self::Error> {
+        let mut update = RoleUpdate {
+            name: String::new(),
+            capabilities: BTreeSet::new(),
+            max_hours: String::new(),
+            max_advance_days: String::new(),
+            max_concurrent: String::new(),
+            max_weekly_hours: String::new(),
+        };
+
+        for (key, value) in form_items.map(|i| i.key_value_decoded()) {
+            match &*key {
+                "name" => update.name = value,
+                "capabilities" => {
+                    update.capabilities.insert(value.parse()?);
+                }
+                "max_hours" => update.max_hours = value,
+                "max_advance_days" => update.max_advance_days = value,
+                "max_concurrent" => update.max_concurrent = value,
+                "max_weekly_hours" => update.max_weekly_hours = value,
+                forms::CSRF_PARAM => {}
+                _ => {
+                    return Err(Error::InvalidData(format![
+                        "invalid form data name: '{}'",
+                        key
+                    ]));
+                }
+            }
+        }
+
+        Ok(update)
+    }
+}
+
+#[post("/role/<name>/update", data = "<form>")]
+pub fn update(
+    name: String,
+    form: LenientForm<RoleUpdate>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    check_role_editor(&auth)?;
+    let conn = &auth.conn;
+
+    let role = Role::with_name(&name, conn)?;
+    let policy = ReservationPolicy {
+        max_hours: optional_int("longest reservation", &form.max_hours)?,
+        max_advance_days: optional_int("advance limit", &form.max_advance_days)?,
+        max_concurrent: optional_int("concurrent reservations", &form.max_concurrent)?,
+        max_weekly_hours: optional_int("weekly machine time", &form.max_weekly_hours)?,
+    };
+
+    let new_name = form.name.trim();
+    if new_name != role.name {
+        check_unique(new_name, Some(&role), &auth)?;
+    }
+
+    let role = conn.transaction::<_, Error, _>(|| {
+        let before = role.audit_snapshot(conn)?;
+
+        let mut role = role;
+        if new_name != role.name {
+            role = role.rename(new_name.to_string(), conn)?;
+        }
+
+        role.set_capabilities(&form.capabilities, conn)?;
+        let role = role.set_policy(&policy, conn)?;
+        check_administered(&auth)?;
+
+        auth.audit(
+            AuditEvent::build("role.update", "role", role.id)
+                .before(&before)
+                .after(&role.audit_snapshot(conn)?),
+        )?;
+
+        Ok(role)
+    })?;
+
+    Ok(role_redirect(&role, format!["Updated role {}", role.name]))
+}
+
+#[derive(Debug, FromForm)]
+pub struct LdapGroupForm {
+    group: String,
+}
+
+#[post("/role/<name>/ldap", data = "<form>")]
+pub fn add_ldap_group(
+    name: String,
+    form: LenientForm<LdapGroupForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    check_role_editor(&auth)?;
+
+    let role = Role::with_name(&name, &auth.conn)?;
+    let group = form.group.trim();
+    if group.is_empty() {
+        return Err(Error::BadRequest(String::from("no LDAP group given")));
+    }
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        let before = role.audit_snapshot(&auth.conn)?;
+        role.add_ldap_group(group.to_string(), &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("role.ldap_group", "role", role.id)
+                .before(&before)
+                .after(&role.audit_snapshot(&auth.conn)?),
+        )
+    })?;
+
+    Ok(role_redirect(
+        &role,
+        format!["Members of LDAP group {} will inhabit {}", group, role.name],
+    ))
+}
+
+#[post("/role/<name>/ldap/<id>/delete")]
+pub fn delete_ldap_group(
+    name: String,
+    id: i32,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    check_role_editor(&auth)?;
+
+    let role = Role::with_name(&name, &auth.conn)?;
+    let mapping = LdapGroupRole::get(id, &auth.conn)?;
+    if mapping.role_id != role.id {
+        return Err(Error::BadRequest(format!["LDAP group {} is not mapped to {}", id, name]));
+    }
+
+    let group = mapping.group_name.clone();
+    auth.conn.transaction::<_, Error, _>(|| {
+        let before = role.audit_snapshot(&auth.conn)?;
+        mapping.delete(&auth.conn)?;
+        auth.audit(
+            AuditEvent::build("role.ldap_group", "role", role.id)
+                .before(&before)
+                .after(&role.audit_snapshot(&auth.conn)?),
+        )
+    })?;
+
+    Ok(role_redirect(
+        &role,
+        format!["LDAP group {} no longer grants {}", group, role.name],
+    ))
+}
+
+#[post("/role/<name>/delete")]
+pub fn delete(name: String, auth: AuthContext) -> Result<Flash<Redirect>, Error> {
+    check_role_editor(&auth)?;
+
+    let role = Role::with_name(&name, &auth.conn)?;
+    let name = role.name.clone();
+    auth.conn.transaction::<_, Error, _>(|| {
+        let event = AuditEvent::build("role.delete", "role", role.id)
+            .before(&role.audit_snapshot(&auth.conn)?);
+        role.delete(&auth.conn)?;
+        check_administered(&auth)?;
+        auth.audit(event)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}roles", route_prefix()]),
+        "info",
+        format!["Deleted role {}", name],
+    ))
+}
@@ -58,7 +58,7 @@ impl Render for TableHeader {
 ///  - microarchitecture (e.g., "Sandy Bridge")
 ///  - processor variant (e.g., "Xeon E3-1240 v5")
 ///  - processor clock speed
-///  - number of physical cores
+///  - number of physical cores (summed over all sockets)
 ///  - size of physical memory
 ///
 /// The default is to show all of these values, but this can be disabled by calling various
@@ -110,23 +110,36 @@ impl MachineTable {
         html! {
             tr {
                 td { (Link::from(m.machine())) }
-                @if self.show_arch { td { (m.architecture().name) } }
+                @if self.show_arch {
+                    td {
+                        @for (i, s) in m.sockets().iter().enumerate() {
+                            @if i > 0 { br / }
+                            (s.arch.name)
+                        }
+                    }
+                }
                 @if self.show_processor_name {
                     td {
-                        @if let Some(ref url) = m.processor().url {
-                            a href=(url) { (m.processor().name) }
-                        } @else {
-                            (m.processor().name)
+                        @for (i, s) in m.sockets().iter().enumerate() {
+                            @if i > 0 { br / }
+                            @if s.count > 1 { (s.count) " × " }
+                            @if let Some(ref url) = s.processor.url {
+                                a href=(url) { (s.processor.name) }
+                            } @else {
+                                (s.processor.name)
+                            }
                         }
                     }
                 }
                 @if self.show_microarch {
                     td {
-                        @let microarch = m.microarchitecture();
-                        @if let Some(ref url) = microarch.url {
-                            a href=(url) { (microarch.name) }
-                        } @else {
-                            (microarch.name)
+                        @for (i, s) in m.sockets().iter().enumerate() {
+                            @if i > 0 { br / }
+                            @if let Some(ref url) = s.microarch.url {
+                                a href=(url) { (s.microarch.name) }
+                            } @else {
+                                (s.microarch.name)
+                            }
                         }
                     }
                 }
@@ -199,8 +212,9 @@ type ReservationData = (Reservation, Option<Machine>, Option<User>);
 ///
 ///  - the user
 ///  - the machine
+///  - the reservation's purpose
 ///  - scheduled start and end times
-///  - actual end status
+///  - actual end status (or cancellation)
 ///
 /// The default is to show all of these values, but this can be disabled by calling various
 /// builder methods, e.g.:
@@ -215,6 +229,7 @@ pub struct ReservationTable {
 
     show_actual_end: bool,
     show_machine: bool,
+    show_purpose: bool,
     show_scheduled_end: bool,
     show_scheduled_start: bool,
     show_user: bool,
@@ -226,6 +241,7 @@ impl ReservationTable {
             reservations: reservations,
             show_actual_end: true,
             show_machine: true,
+            show_purpose: true,
             show_scheduled_end: true,
             show_scheduled_start: true,
             show_user: true,
@@ -236,6 +252,7 @@ impl ReservationTable {
         TableHeader::new(&["#"])
             .add_if(self.show_machine, "Machine")
             .add_if(self.show_user, "User")
+            .add_if(self.show_purpose, "Purpose")
             .add_if(self.show_scheduled_start, "Start")
             .add_if(self.show_scheduled_end, "Scheduled end")
             .add_if(self.show_actual_end, "Actually ended")
@@ -246,7 +263,11 @@ impl ReservationTable {
 
         let now = Utc::now();
         let row_class = {
-            if r.scheduled_start <= now {
+            if r.is_cancelled() {
+                "text-muted"
+            } else if r.pending {
+                "table-warning"
+            } else if r.scheduled_start <= now {
                 if let Some(_) = r.actual_end {
                     ""
                 } else if let Some(end) = r.scheduled_end {
@@ -283,6 +304,10 @@ impl ReservationTable {
                     }
                 }
 
+                @if self.show_purpose {
+                    td { (r.purpose) }
+                }
+
                 @if self.show_scheduled_start {
                     td {
                         (HumanTime::from(r.start()))
@@ -301,6 +326,8 @@ impl ReservationTable {
                     td {
                         @if let Some(ref time) = r.actual_end {
                             (HumanTime::from(*time))
+                        } @else if r.is_cancelled() {
+                            "cancelled"
                         }
                     }
                 }
@@ -318,6 +345,11 @@ impl ReservationTable {
         self
     }
 
+    pub fn show_purpose(mut self, s: bool) -> ReservationTable {
+        self.show_purpose = s;
+        self
+    }
+
     pub fn show_scheduled_end(mut self, s: bool) -> ReservationTable {
         self.show_scheduled_end = s;
         self
@@ -0,0 +1,265 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use db::models::*;
+use diesel::Connection as DieselConnection;
+use maud::*;
+use rocket::request::{FlashMessage, LenientForm};
+use rocket::response::{Flash, Redirect};
+use rustc_serialize::json::ToJson;
+
+use super::auth::AuthContext;
+use super::bootstrap::Page;
+use super::link::Link;
+use super::{forms, page, route_prefix, tables};
+use super::Error;
+
+fn team_redirect<S: Into<String>>(team: &Team, message: S) -> Flash<Redirect> {
+    Flash::new(
+        Redirect::to(format!["{}team/{}", route_prefix(), team.name]),
+        "info",
+        message.into(),
+    )
+}
+
+/// Look up a team, checking that the current user may change its membership.
+fn managed_team(name: &str, auth: &AuthContext) -> Result<Team, Error> {
+    let team = Team::with_name(name, &auth.conn)?;
+    if !team.is_managed_by(&auth.user, &auth.conn)?
+        && !auth.user.can(Capability::AlterUsers, &auth.conn)?
+    {
+        return Err(Error::NotAuthorized(format!["manage team {}", team.name]));
+    }
+
+    Ok(team)
+}
+
+#[get("/teams")]
+pub fn teams(auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let conn = &auth.conn;
+
+    let mut rows = Vec::new();
+    for t in Team::all(conn)? {
+        let members = t.members(conn)?;
+        let mine = members.iter().any(|&(_, ref u)| u.id == auth.user.id);
+        rows.push((t, members.len(), mine));
+    }
+
+    Ok(page("Clowder: teams", &auth).flash(flash).content(html! {
+        h2 { "Teams" }
+
+        p {
+            "Reservations can be shared with a team: every member of the team can then end, "
+            "extend, cancel or reconfigure them."
+        }
+
+        @if !rows.is_empty() {
+            table.table.table-responsive {
+                (tables::TableHeader::new(&[ "Team", "Description", "Members", "" ]))
+
+                tbody {
+                    @for &(ref t, members, mine) in &rows {
+                        tr {
+                            td { (Link::from(t)) }
+                            td { (t.description) }
+                            td { (members) }
+                            td { @if mine { "member" } }
+                        }
+                    }
+                }
+            }
+        }
+
+        h3 { "Create a team" }
+
+        form action={ (route_prefix()) "team/create" } method="post" {
+            (csrf)
+            table {
+                tr {
+                    th { "Name" }
+                    td { (forms::Input::new("name").size(20)) }
+                }
+                tr {
+                    th { "Description" }
+                    td { (forms::Input::new("description").size(45)) }
+                }
+                tr {
+                    th /
+                    td { (forms::SubmitButton::new().label("Create team")) }
+                }
+            }
+        }
+    }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct TeamCreateForm {
+    name: String,
+    description: String,
+}
+
+#[post("/team/create", data = "<form>")]
+pub fn create(
+    form: LenientForm<TeamCreateForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        return Err(Error::BadRequest(String::from("every team needs a name")));
+    }
+
+    let team = auth.conn.transaction::<_, Error, _>(|| {
+        let team = Team::insert(
+            name.to_string(),
+            form.description.trim().to_string(),
+            &auth.user,
+            &auth.conn,
+        )?;
+        auth.audit(AuditEvent::build("team.create", "team", team.id).after(&team))?;
+        Ok(team)
+    })?;
+
+    Ok(team_redirect(&team, format!["Created team {}", team.name]))
+}
+
+#[get("/team/<name>")]
+pub fn team(name: String, auth: AuthContext, flash: Option<FlashMessage>) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let conn = &auth.conn;
+    let team = Team::with_name(&name, conn)?;
+    let members = team.members(conn)?;
+    let can_manage =
+        team.is_managed_by(&auth.user, conn)? || auth.user.can(Capability::AlterUsers, conn)?;
+
+    let reservations = team
+        .reservations(conn)?
+        .into_iter()
+        .map(|(r, m, u)| (r, Some(m), Some(u)))
+        .collect();
+
+    let user_options = if can_manage {
+        User::all(conn)?
+            .into_iter()
+            .map(|u| forms::SelectOption::new(u.username, u.name))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let action = |a: &str| format!["{}team/{}/{}", route_prefix(), team.name, a];
+
+    Ok(page(format!["Clowder: team {}", team.name], &auth)
+        .flash(flash)
+        .content(html! {
+            h2 { "Team " (team.name) }
+
+            @if !team.description.is_empty() {
+                p { (team.description) }
+            }
+
+            h4 { "Members" }
+            table.table.table-responsive {
+                (tables::TableHeader::new(&[ "User", "Name", "Role", "" ]))
+
+                tbody {
+                    @for &(ref m, ref u) in &members {
+                        tr {
+                            td { (Link::from(u)) }
+                            td { (u.name) }
+                            td { @if m.is_manager { "manager" } @else { "member" } }
+                            td {
+                                @if can_manage {
+                                    form action={ (action("remove")) } method="post" {
+                                        (csrf)
+                                        input type="hidden" name="user" value=(u.username) /
+                                        (forms::SubmitButton::new().label("Remove"))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            @if can_manage {
+                form action={ (action("add")) } method="post" {
+                    (csrf)
+                    "Add "
+                    (forms::Select::new("user").set_options(user_options))
+                    " "
+                    label { input type="checkbox" name="manager" / " as a manager" }
+                    " "
+                    (forms::SubmitButton::new().label("Add member"))
+                }
+            }
+
+            h4 { "Reservations" }
+            (tables::ReservationTable::new(reservations))
+        }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct MemberForm {
+    user: String,
+    manager: bool,
+}
+
+#[post("/team/<name>/add", data = "<form>")]
+pub fn add_member(
+    name: String,
+    form: LenientForm<MemberForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let team = managed_team(&name, &auth)?;
+    let user = User::with_username(&form.user, &auth.conn)?;
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        team.add_member(&user, form.manager, &auth.conn)?;
+        auth.audit(AuditEvent::build("team.add_member", "team", team.id).after(&json_object(
+            vec![
+                ("member", user.username.to_json()),
+                ("manager", form.manager.to_json()),
+            ],
+        )))
+    })?;
+
+    Ok(team_redirect(
+        &team,
+        format!["Added {} to team {}", user.username, team.name],
+    ))
+}
+
+#[derive(Debug, FromForm)]
+pub struct RemoveMemberForm {
+    user: String,
+}
+
+#[post("/team/<name>/remove", data = "<form>")]
+pub fn remove_member(
+    name: String,
+    form: LenientForm<RemoveMemberForm>,
+    auth: AuthContext,
+) -> Result<Flash<Redirect>, Error> {
+    let team = managed_team(&name, &auth)?;
+    let user = User::with_username(&form.user, &auth.conn)?;
+
+    auth.conn.transaction::<_, Error, _>(|| {
+        team.remove_member(&user, &auth.conn)?;
+        auth.audit(
+            AuditEvent::build("team.remove_member", "team", team.id)
+                .before(&json_object(vec![("member", user.username.to_json())])),
+        )
+    })?;
+
+    Ok(team_redirect(
+        &team,
+        format!["Removed {} from team {}", user.username, team.name],
+    ))
+}
@@ -0,0 +1,247 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use chrono::{DateTime, Duration, Timelike, Utc};
+use db::models::*;
+use maud::*;
+use std::collections::BTreeMap;
+
+/// Width of the machine-name column, in SVG user units.
+const LABEL_WIDTH: f64 = 120.0;
+
+/// Width of the time axis, in SVG user units.
+const AXIS_WIDTH: f64 = 880.0;
+
+/// Height of each machine's row, in SVG user units.
+const ROW_HEIGHT: f64 = 24.0;
+
+/// Height of the time-axis header, in SVG user units.
+const HEADER_HEIGHT: f64 = 20.0;
+
+///
+/// How much time a timeline covers.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Zoom {
+    Day,
+    Week,
+    Month,
+}
+
+impl Zoom {
+    pub fn parse(s: &str) -> Option<Zoom> {
+        match s {
+            "day" => Some(Zoom::Day),
+            "week" => Some(Zoom::Week),
+            "month" => Some(Zoom::Month),
+            _ => None,
+        }
+    }
+
+    pub fn all() -> [Zoom; 3] {
+        [Zoom::Day, Zoom::Week, Zoom::Month]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            &Zoom::Day => "day",
+            &Zoom::Week => "week",
+            &Zoom::Month => "month",
+        }
+    }
+
+    /// The length of time shown at this zoom level.
+    pub fn span(&self) -> Duration {
+        match self {
+            &Zoom::Day => Duration::days(1),
+            &Zoom::Week => Duration::weeks(1),
+            &Zoom::Month => Duration::days(30),
+        }
+    }
+
+    /// The interval between labelled ticks on the time axis.
+    fn tick(&self) -> Duration {
+        match self {
+            &Zoom::Day => Duration::hours(3),
+            &Zoom::Week => Duration::days(1),
+            &Zoom::Month => Duration::days(7),
+        }
+    }
+
+    fn tick_format(&self) -> &'static str {
+        match self {
+            &Zoom::Day => "%H:%M",
+            &Zoom::Week => "%a %e %b",
+            &Zoom::Month => "%e %b",
+        }
+    }
+}
+
+///
+/// A Gantt-style timeline of reservations, with machines as rows and time as the horizontal
+/// axis, rendered as inline SVG (so that it doesn't depend on any JavaScript).
+///
+/// ```rust
+/// let markup = Timeline::new(Zoom::Week, start)
+///     .machines(Machine::all(&conn)?)
+///     .reservations(Reservation::during(start, start + Zoom::Week.span(), &conn)?)
+///     .render();
+/// ```
+///
+pub struct Timeline {
+    zoom: Zoom,
+    start: DateTime<Utc>,
+    machines: Vec<Machine>,
+    reservations: Vec<(Reservation, Machine, User)>,
+}
+
+impl Timeline {
+    pub fn new(zoom: Zoom, start: DateTime<Utc>) -> Timeline {
+        Timeline {
+            zoom: zoom,
+            start: start,
+            machines: Vec::new(),
+            reservations: Vec::new(),
+        }
+    }
+
+    pub fn machines(mut self, machines: Vec<Machine>) -> Timeline {
+        self.machines = machines;
+        self
+    }
+
+    pub fn reservations(mut self, reservations: Vec<(Reservation, Machine, User)>) -> Timeline {
+        self.reservations = reservations;
+        self
+    }
+
+    fn end(&self) -> DateTime<Utc> {
+        self.start + self.zoom.span()
+    }
+
+    /// Convert a time into a horizontal SVG coordinate (clamped to the visible window).
+    fn x(&self, t: DateTime<Utc>) -> f64 {
+        let span = self.zoom.span().num_seconds() as f64;
+        let offset = (t - self.start).num_seconds() as f64;
+
+        LABEL_WIDTH + AXIS_WIDTH * (offset / span).max(0.0).min(1.0)
+    }
+
+    fn row(&self, machine_id: i32) -> Option<usize> {
+        self.machines.iter().position(|m| m.id == machine_id)
+    }
+
+    /// The users with reservations in this timeline, in username order.
+    fn users(&self) -> BTreeMap<&str, &User> {
+        self.reservations
+            .iter()
+            .map(|&(_, _, ref u)| (u.username.as_str(), u))
+            .collect()
+    }
+
+    /// Ticks along the time axis (aligned to whole hours or days).
+    fn ticks(&self) -> Vec<DateTime<Utc>> {
+        let aligned = match self.zoom {
+            Zoom::Day => self.start.with_minute(0).and_then(|t| t.with_second(0)),
+            Zoom::Week | Zoom::Month => self.start.date().and_hms_opt(0, 0, 0),
+        };
+        let first = aligned.unwrap_or(self.start);
+
+        let mut ticks = Vec::new();
+        let mut t = first;
+        while t < self.end() {
+            if t >= self.start {
+                ticks.push(t);
+            }
+            t = t + self.zoom.tick();
+        }
+
+        ticks
+    }
+}
+
+///
+/// A colour for a user's reservations: stable for any given user and spread around the colour
+/// wheel so that adjacent users are easy to tell apart.
+///
+pub fn user_colour(u: &User) -> String {
+    format!["hsl({}, 60%, 60%)", (u.id * 137) % 360]
+}
+
+impl Render for Timeline {
+    fn render(&self) -> Markup {
+        let height = HEADER_HEIGHT + ROW_HEIGHT * self.machines.len() as f64;
+        let now = Utc::now();
+        let prefix = super::route_prefix();
+
+        html! {
+            svg.timeline xmlns="http://www.w3.org/2000/svg"
+                viewBox={ "0 0 " (LABEL_WIDTH + AXIS_WIDTH) " " (height) }
+                width="100%" {
+
+                // Time axis
+                @for t in self.ticks() {
+                    line x1=(self.x(t)) x2=(self.x(t)) y1="0" y2=(height)
+                        stroke="#ddd" {}
+                    text x={ (self.x(t) + 2.0) } y="14" font-size="10" fill="#666" {
+                        (t.format(self.zoom.tick_format()))
+                    }
+                }
+
+                // Machine rows
+                @for (i, m) in self.machines.iter().enumerate() {
+                    @let y = HEADER_HEIGHT + ROW_HEIGHT * i as f64;
+
+                    line x1="0" x2={ (LABEL_WIDTH + AXIS_WIDTH) } y1=(y) y2=(y) stroke="#eee" {}
+                    a href={ (prefix) "machine/" (m.name) } {
+                        text x="4" y={ (y + ROW_HEIGHT * 0.7) } font-size="12" { (m.name) }
+                    }
+                }
+
+                // Reservations
+                @for &(ref r, ref m, ref u) in &self.reservations {
+                    @if let Some(row) = self.row(m.id) {
+                        @let y = HEADER_HEIGHT + ROW_HEIGHT * row as f64 + 3.0;
+                        @let x1 = self.x(r.start());
+                        @let x2 = self.x(r.busy_until().unwrap_or(self.end()));
+
+                        a href={ (prefix) "reservation/" (r.id()) } {
+                            rect x=(x1) y=(y) width={ ((x2 - x1).max(1.0)) }
+                                height={ (ROW_HEIGHT - 6.0) } rx="3"
+                                fill=(user_colour(u))
+                                fill-opacity=(if r.pending { "0.4" } else { "1" }) {
+                                title {
+                                    "Reservation " (r.id()) ": " (m.name) " reserved by "
+                                    (u.username)
+                                    @if !r.purpose.is_empty() { ": " (r.purpose) }
+                                    @if r.pending { " (awaiting approval)" }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // The current time
+                @if now >= self.start && now < self.end() {
+                    line x1=(self.x(now)) x2=(self.x(now)) y1="0" y2=(height)
+                        stroke="#c00" stroke-width="2" {}
+                }
+            }
+
+            ul.list-inline {
+                @for (username, u) in self.users() {
+                    li.list-inline-item {
+                        span style={ "color: " (user_colour(u)) } { "■ " }
+                        (username)
+                    }
+                }
+            }
+        }
+    }
+}
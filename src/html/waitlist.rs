@@ -0,0 +1,268 @@
+/*
+ * Copyright 2026 Jonathan Anderson
+ *
+ * Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+ * http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+ * http://opensource.org/licenses/MIT>, at your option. This file may not be
+ * copied, modified, or distributed except according to those terms.
+ */
+
+use chrono_humanize::HumanTime;
+use db::models::*;
+use diesel::Connection as DieselConnection;
+use maud::*;
+use rocket::request::{FlashMessage, LenientForm};
+use rocket::response::{Flash, Redirect};
+
+use super::auth::AuthContext;
+use super::bootstrap::Page;
+use super::link::Link;
+use super::{forms, page, parse_daterange, parse_spec, route_prefix, spec_form_rows, tables};
+use super::Error;
+
+fn waitlist_redirect<S: Into<String>>(kind: &str, message: S) -> Flash<Redirect> {
+    Flash::new(
+        Redirect::to(format!["{}waitlist", route_prefix()]),
+        kind,
+        message.into(),
+    )
+}
+
+/// Look up a waitlist entry, checking that it belongs to the current user.
+fn own_entry(id: i32, auth: &AuthContext) -> Result<WaitlistEntry, Error> {
+    let entry = WaitlistEntry::get(id, &auth.conn)?;
+    if entry.user_id != auth.user.id {
+        return Err(Error::NotAuthorized(format!["modify waitlist entry {}", id]));
+    }
+
+    Ok(entry)
+}
+
+#[get("/waitlist?<machine>&<dates>")]
+pub fn waitlist(
+    machine: Option<String>,
+    dates: Option<String>,
+    auth: AuthContext,
+    flash: Option<FlashMessage>,
+) -> Result<Page, Error> {
+    let csrf = forms::csrf(auth.csrf_token());
+    let conn = &auth.conn;
+    WaitlistEntry::expire_offers(conn)?;
+
+    let mut entries = Vec::new();
+    for e in WaitlistEntry::for_user(&auth.user, conn)? {
+        let wanted = match e.machine_id {
+            Some(m) => html! { (Link::from(&Machine::get(m, conn)?)) },
+            None => html! { (e.spec(conn)?.summary()) },
+        };
+        let offered = match e.state() {
+            WaitlistState::Offered(m, _) => Some(Machine::get(m, conn)?),
+            _ => None,
+        };
+
+        entries.push((e, wanted, offered));
+    }
+
+    let acl = AccessControl::for_user(&auth.user, conn)?;
+    let reservable = Machine::all(conn)?.into_iter().filter(|m| acl.can_reserve(m));
+    let mut machine_options = vec![forms::SelectOption::new("", "(any machine matching:)")];
+    machine_options.extend(reservable.map(|m| {
+        let selected = machine.as_ref() == Some(&m.name);
+        forms::SelectOption::new(m.name.clone(), m.name).selected(selected)
+    }));
+
+    let spec_rows = spec_form_rows(conn)?;
+    let action = |e: &WaitlistEntry, a: &str| format!["{}waitlist/{}/{}", route_prefix(), e.id, a];
+
+    Ok(page("Clowder: waitlist", &auth).flash(flash).content(html! {
+        h2 { "Waitlist" }
+
+        p {
+            "If the machine you want is already reserved, join the waitlist. If a conflicting "
+            "reservation is cancelled or ended early, the slot will be offered to waiting users "
+            "in the order that they joined; each offer must be claimed within "
+            (WAITLIST_OFFER_MINUTES) " minutes."
+        }
+
+        @if !entries.is_empty() {
+            table.table.table-responsive {
+                (tables::TableHeader::new(&[ "#", "Wanted", "From", "Until", "Status", "" ]))
+
+                tbody {
+                    @for &(ref e, ref wanted, ref offered) in &entries {
+                        tr id={ "entry-" (e.id) } {
+                            td { (e.id) }
+                            td { (wanted) }
+                            td { (e.window_start) }
+                            td { (e.window_end) }
+                            @match e.state() {
+                                WaitlistState::Waiting => {
+                                    td { "waiting" }
+                                    td {
+                                        form action={ (action(e, "withdraw")) }
+                                             method="post" {
+                                            (csrf)
+                                            (forms::SubmitButton::new().label("Leave waitlist"))
+                                        }
+                                    }
+                                },
+                                WaitlistState::Offered(_, expires) => {
+                                    td {
+                                        strong {
+                                            @if let Some(ref m) = *offered { (Link::from(m)) }
+                                            " is free"
+                                        }
+                                        " (offer expires " (HumanTime::from(expires)) ")"
+                                    }
+                                    td {
+                                        form action={ (action(e, "accept")) } method="post" {
+                                            (csrf)
+                                            (forms::SubmitButton::new().label("Claim"))
+                                        }
+                                        form action={ (action(e, "withdraw")) }
+                                             method="post" {
+                                            (csrf)
+                                            (forms::SubmitButton::new().label("Decline"))
+                                        }
+                                    }
+                                },
+                                WaitlistState::Accepted(r) => {
+                                    td {
+                                        "claimed as reservation "
+                                        a href={ (route_prefix()) "reservation/" (r) } { (r) }
+                                    }
+                                    td {}
+                                },
+                                WaitlistState::Closed => {
+                                    td { "closed" }
+                                    td {}
+                                },
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        h3 { "Join the waitlist" }
+
+        form action={ (route_prefix()) "waitlist/join" } method="post" {
+            (csrf)
+            table {
+                tr {
+                    th { "Dates" }
+                    td {
+                        (forms::Input::new("dates")
+                                      .class("daterange")
+                                      .value(dates.unwrap_or(String::new()))
+                                      .size(45))
+                    }
+                }
+                tr {
+                    th { "Purpose" }
+                    td { (forms::Input::new("purpose").size(45)) }
+                }
+                tr {
+                    th { "Machine" }
+                    td { (forms::Select::new("machine").set_options(machine_options)) }
+                }
+                (spec_rows)
+                tr {
+                    th /
+                    td { (forms::SubmitButton::new().label("Join waitlist")) }
+                }
+            }
+        }
+    }))
+}
+
+#[derive(Debug, FromForm)]
+pub struct WaitlistForm {
+    dates: String,
+    purpose: String,
+    machine: String,
+    memory_gb: String,
+    cores: String,
+    arch: String,
+    microarch: String,
+    tags: String,
+    disk: String,
+}
+
+#[post("/waitlist/join", data = "<form>")]
+pub fn join(form: LenientForm<WaitlistForm>, auth: AuthContext) -> Result<Flash<Redirect>, Error> {
+    let conn = &auth.conn;
+    let (start, end) = parse_daterange(&form.dates)?;
+
+    let purpose = form.purpose.trim().to_string();
+    if purpose.is_empty() {
+        return Err(Error::BadRequest(String::from(
+            "every reservation needs a purpose",
+        )));
+    }
+
+    let machine = match form.machine.as_str() {
+        "" => None,
+        name => Some(Machine::with_name(name, conn)?),
+    };
+
+    if let Some(ref m) = machine {
+        if !AccessControl::for_user(&auth.user, conn)?.can_reserve(m) {
+            return Err(Error::NotAuthorized(format!["reserve {}", m.name]));
+        }
+    }
+
+    let spec = parse_spec(
+        &form.memory_gb,
+        &form.cores,
+        &form.arch,
+        &form.microarch,
+        &form.tags,
+        &form.disk,
+        conn,
+    )?;
+
+    let entry = conn.transaction::<_, Error, _>(|| {
+        let entry =
+            WaitlistEntry::insert(&auth.user, machine.as_ref(), &spec, start, end, purpose, conn)?;
+        auth.audit(AuditEvent::build("waitlist.join", "waitlist_entry", entry.id))?;
+        Ok(entry)
+    })?;
+
+    Ok(waitlist_redirect(
+        "info",
+        format!["Joined the waitlist (entry {})", entry.id],
+    ))
+}
+
+#[post("/waitlist/<id>/accept")]
+pub fn accept(id: i32, auth: AuthContext) -> Result<Flash<Redirect>, Error> {
+    let entry = own_entry(id, &auth)?;
+    let entry_id = entry.id;
+    let r = auth.conn.transaction::<_, Error, _>(|| {
+        let r = entry.accept(&auth.conn)?;
+        auth.audit(AuditEvent::build("waitlist.accept", "waitlist_entry", entry_id))?;
+        auth.audit(AuditEvent::build("reservation.create", "reservation", r.id).after(&r))?;
+        Ok(r)
+    })?;
+
+    Ok(Flash::new(
+        Redirect::to(format!["{}reservation/{}", route_prefix(), r.id()]),
+        "info",
+        format!["Claimed waitlisted slot as reservation {}", r.id()],
+    ))
+}
+
+#[post("/waitlist/<id>/withdraw")]
+pub fn withdraw(id: i32, auth: AuthContext) -> Result<Flash<Redirect>, Error> {
+    let entry = own_entry(id, &auth)?;
+    auth.conn.transaction::<_, Error, _>(|| {
+        entry.close(&auth.conn)?;
+        auth.audit(AuditEvent::build("waitlist.withdraw", "waitlist_entry", id))
+    })?;
+
+    Ok(waitlist_redirect(
+        "info",
+        format!["Left the waitlist (entry {})", id],
+    ))
+}
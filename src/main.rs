@@ -14,6 +14,7 @@
 
 extern crate chrono;
 extern crate chrono_humanize;
+extern crate chrono_tz;
 extern crate crypto;
 #[macro_use]
 extern crate diesel;
@@ -21,11 +22,14 @@ extern crate dotenv;
 extern crate hyper;
 extern crate hyper_native_tls;
 extern crate itertools;
+extern crate ldap3;
 #[macro_use]
 extern crate log;
 extern crate marksman_escape;
 extern crate maud;
 extern crate native_tls;
+extern crate openssl;
+extern crate pulldown_cmark;
 extern crate rand;
 #[macro_use]
 extern crate rocket;
@@ -58,6 +62,7 @@ fn main() {
 
     rocket::ignite()
         .register(html::error_catchers())
+        .attach(html::csrf_fairing())
         .mount(&route_prefix, html::all_routes())
         .launch();
 }